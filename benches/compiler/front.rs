@@ -1,18 +1,39 @@
 use std::hint::black_box;
 
-use criterion::{criterion_group, Criterion};
+use criterion::{criterion_group, BenchmarkId, Criterion};
 
 use hydent_lang_compiler::{
     compiler::{
-        arena::Arena, context::frontend::CompilerFrontendContext, source_holder::SourceHolder,
-        symbol::SymbolFactory,
+        arena::Arena,
+        context::frontend::CompilerFrontendContext,
+        source_holder::SourceHolder,
+        symbol::{sym, SymbolFactory},
     },
     diagnostic::stream::IgnoreDiagnosticStream,
-    parser::{parse::Parser, tracer::NopeTracer, Ast},
-    tokenizer::{token_stream::TokenStream, tokenize::Tokenizer},
+    parser::{
+        ast::Identifier,
+        base_parser::BaseParser,
+        fuzz::ProgramGenerator,
+        grammar::{Grammar, TokenMap, GRAMMAR_PATH, TOKEN_MAP_PATH},
+        memo::MemoTable,
+        parse::Parser,
+        parse_module,
+        tracer::NopeTracer,
+        Ast,
+    },
+    tokenizer::{
+        token_stream::TokenStream,
+        tokenize::Tokenizer,
+        tokens::{Delimiter, Token},
+    },
 };
 
-criterion_group!(front_benches, bench_parser,);
+criterion_group!(
+    front_benches,
+    bench_parser,
+    bench_backtracking,
+    bench_fuzz_corpus
+);
 
 fn bench_parser(c: &mut Criterion) {
     c.bench_function("parse_line_18_program", |b| {
@@ -54,12 +75,103 @@ pub fn fizz_buzz(n: Int) {
             );
             let ast = parser.parse();
 
-            Ast::new(
-                ast,
-                ast_arena,
-                SourceHolder::new(source, line_starts),
-                symbols,
-            );
+            Ast::new(ast, ast_arena, SourceHolder::new(source, line_starts));
         }));
     });
 }
+
+type BenchParser<'ctx, 'src, 's> = Parser<'ctx, 'src, 's, IgnoreDiagnosticStream, NopeTracer>;
+type NestedResult =
+    Result<Identifier, <BenchParser<'static, 'static, 'static> as BaseParser>::Error>;
+
+/// `Nested := "(" Nested ")" "," | "(" Nested ")" | main`
+///
+/// Both alternatives share their prefix, so without memoization every level
+/// parses the level below it twice.
+fn parse_nested(parser: &mut BenchParser) -> NestedResult {
+    if parser.peek::<0>() != Some(Token::Delimiter(Delimiter::LeftParen)) {
+        parser.expect(&Token::Identifier(sym::main))?;
+        return Ok(Identifier { symbol: sym::main });
+    }
+    parser
+        .backtrack(|this| {
+            let inner = parse_parenthesized(this)?;
+            this.expect(&Token::Delimiter(Delimiter::Comma))?;
+            Ok(inner)
+        })
+        .or_else(|_| parser.backtrack(parse_parenthesized))
+}
+
+fn parse_parenthesized(parser: &mut BenchParser) -> NestedResult {
+    parser.expect(&Token::Delimiter(Delimiter::LeftParen))?;
+    let inner = parser.memoize(parse_nested)?;
+    parser.expect(&Token::Delimiter(Delimiter::RightParen))?;
+    Ok(inner)
+}
+
+fn bench_backtracking(c: &mut Criterion) {
+    let mut group = c.benchmark_group("backtrack_nested_parens");
+    for (memoize, depths) in [(true, &[4, 8, 16, 64, 256][..]), (false, &[4, 8, 16][..])] {
+        let name = if memoize { "memoized" } else { "plain" };
+        for &depth in depths {
+            let source = format!("{}main{}", "(".repeat(depth), ")".repeat(depth));
+            group.bench_with_input(BenchmarkId::new(name, depth), &source, |b, source| {
+                b.iter(|| {
+                    let mut diagnostic_stream = IgnoreDiagnosticStream::new();
+                    let mut symbols = SymbolFactory::new(source);
+                    let tokenizer = Tokenizer::new(source, &mut symbols);
+                    let (tokens, _) = tokenizer.tokenize(&mut diagnostic_stream);
+                    let ast_arena = Arena::new();
+                    let mut parser: BenchParser = Parser::new(
+                        TokenStream::new(tokens),
+                        CompilerFrontendContext {
+                            source,
+                            symbol_factory: &mut symbols,
+                            ast_arena: &ast_arena,
+                        },
+                        &mut diagnostic_stream,
+                    );
+                    if !memoize {
+                        parser.memo = MemoTable::disabled();
+                    }
+                    black_box(parser.memoize(parse_nested).unwrap());
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+/// How many programs the fuzz corpus has of each kind.
+const FUZZ_CORPUS_SIZE: u64 = 64;
+
+/// Parses programs generated from the grammar, and the same programs broken
+/// by `ProgramGenerator::mutate`, which makes the parser recover from errors.
+/// The seeds are fixed so that every run parses the same corpus.
+fn bench_fuzz_corpus(c: &mut Criterion) {
+    let grammar = Grammar::parse(&std::fs::read_to_string(GRAMMAR_PATH).unwrap()).unwrap();
+    let token_map = TokenMap::parse(&std::fs::read_to_string(TOKEN_MAP_PATH).unwrap()).unwrap();
+    let (mut generated, mut mutated) = (Vec::new(), Vec::new());
+    for seed in 0..FUZZ_CORPUS_SIZE {
+        let mut generator = ProgramGenerator::new(&grammar, &token_map, seed);
+        let program = generator.generate("Module");
+        mutated.extend(
+            generator
+                .mutate("Module", &program)
+                .map(|mutated| mutated.to_string()),
+        );
+        generated.push(program.to_string());
+    }
+
+    let mut group = c.benchmark_group("fuzz_corpus");
+    for (name, corpus) in [("generated", &generated), ("mutated", &mutated)] {
+        group.bench_with_input(name, corpus, |b, corpus| {
+            b.iter(|| {
+                for source in corpus {
+                    black_box(parse_module(source));
+                }
+            });
+        });
+    }
+    group.finish();
+}
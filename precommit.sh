@@ -1,7 +1,6 @@
 cd script
 npm run self-test
 npm start -- ci
-npm run compiler-test
 cd ../
 cargo build
 cargo fmt --all -- --check
@@ -1,10 +1,11 @@
 import { readFileSync } from "fs";
 
-import { BranchRule, Grammar, ProductRule, Rule } from "./ast";
+import { BranchRule, Grammar, HookRule, ProductRule, Rule } from "./ast";
 import {
     astName,
     BranchParserFunction,
     fnName,
+    HookParserFunction,
     IR,
     ParserFunction,
     ProductParserFunction,
@@ -15,6 +16,10 @@ import {
 
 type TokenSeq = RustTokenTypeName[];
 
+// The second token of a hook. Hooks are parsed by hand, so which tokens follow
+// their first one is unknown.
+const ANY_TOKEN = tokenName("_");
+
 function seqKey(seq: TokenSeq): string {
     return seq.join(",");
 }
@@ -101,6 +106,9 @@ export class Analyzer {
                 case "Product":
                     funcs.push(this.analyzeProductRule(rule));
                     break;
+                case "Hook":
+                    funcs.push(this.analyzeHookRule(rule));
+                    break;
             }
         }
         this.computeCycle(funcs);
@@ -240,6 +248,10 @@ export class Analyzer {
                             currentSet.add(seqKey(s));
                         }
                     }
+                } else if (rule.kind === "Hook") {
+                    for (const t of rule.firstTerminals) {
+                        currentSet.add(seqKey([this.tokenMap.get(t), ANY_TOKEN]));
+                    }
                 }
 
                 if (currentSet.size !== oldSize) {
@@ -473,7 +485,8 @@ export class Analyzer {
                 if (!mapPeek0[t0]) mapPeek0[t0] = [];
                 if (!mapPeek0[t0].includes(v.name)) mapPeek0[t0].push(v.name);
 
-                if (seq.length > 1) {
+                // a hook can go on with any token, like a rule of one token
+                if (seq.length > 1 && seq[1] !== ANY_TOKEN) {
                     const t1 = seq[1];
                     if (!mapPeek1[t0]) mapPeek1[t0] = {};
                     if (!mapPeek1[t0][t1]) mapPeek1[t0][t1] = [];
@@ -630,6 +643,29 @@ export class Analyzer {
             elements,
         };
     }
+
+    private analyzeHookRule(rule: HookRule): HookParserFunction {
+        const syncPointsTerminals: RustTokenTypeName[] = [];
+        const follow = this.followSets.get(rule.name);
+        if (follow) {
+            [...follow].sort().forEach(t => syncPointsTerminals.push(tokenName(t)));
+        }
+
+        let firstTerminals: RustTokenTypeName[] = [];
+        const firstSets = this.firstSets.get(rule.name);
+        if (firstSets) {
+            firstTerminals = [...firstSets].map(tokenName);
+        }
+
+        return {
+            kind: "hook",
+            functionName: fnName(rule.name),
+            astTypeName: astName(rule.name),
+            syncPointsTerminals,
+            firstTerminals,
+            isBoxed: false,
+        };
+    }
 }
 
 export function analyze(grammar: Grammar, tokenMap?: Record<string, string>): [IR, string] {
@@ -1,7 +1,7 @@
 // ```
 // <bnf> ::= { <rule> }
 // 
-// <rule> ::= <branch_rule> | <product_rule>
+// <rule> ::= <branch_rule> | <product_rule> | <hook_rule>
 // 
 // <branch_rule> ::= "branch" <identifier> "{" <branch_rule_inner> "}"
 // <branch_rule_inner> ::= { <identifier> [ "with" <string_literal> ] }
@@ -14,7 +14,14 @@
 // <option_item> ::= "?" <item>
 // <item> ::= <identifier>
 // <terminal> ::= <string_literal>
+//
+// <hook_rule> ::= "hook" <identifier> "{" { <terminal> } "}"
 // ```
+//
+// A hook is a rule parsed by hand instead of by generated code, like an
+// expression. Its terminals are the tokens it can start with. The node type,
+// `walk_*`, `rebuild_*` and `parse_*` of a hook are written by hand, and the
+// node type has an `Invalid` variant to stand for a hook which failed to parse.
 
 export type TypeModifier = 'None' | 'List' | 'Option';
 
@@ -44,18 +51,25 @@ export interface ProductRule {
     members: ProductMember[];
 }
 
-export type Rule = BranchRule | ProductRule;
+export interface HookRule {
+    kind: 'Hook';
+    name: string;
+    firstTerminals: string[];
+}
+
+export type Rule = BranchRule | ProductRule | HookRule;
 
 export type Grammar = Rule[];
 
 export type TokenKind =
-    | 'Branch' | 'Product' | 'With'
+    | 'Branch' | 'Product' | 'Hook' | 'With'
     | 'LBrace' | 'RBrace' | 'Colon' | 'Star' | 'Question'
     | 'Identifier' | 'StringLiteral' | 'EOF';
 
 export type Token =
     | { kind: 'Branch' }
     | { kind: 'Product' }
+    | { kind: 'Hook' }
     | { kind: 'With' }
     | { kind: 'LBrace' }
     | { kind: 'RBrace' }
@@ -69,6 +83,7 @@ export type Token =
 export const TOKEN_PATTERNS: { kind: TokenKind; regex: RegExp }[] = [
     { kind: 'Branch', regex: /^branch\b/ },
     { kind: 'Product', regex: /^product\b/ },
+    { kind: 'Hook', regex: /^hook\b/ },
     { kind: 'With', regex: /^with\b/ },
     { kind: 'LBrace', regex: /^\{/ },
     { kind: 'RBrace', regex: /^\}/ },
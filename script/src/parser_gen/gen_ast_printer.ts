@@ -9,13 +9,13 @@ export class ASTPrinterGenerator {
         ret += `//  DO NOT EDIT THIS FILE DIRECTLY\n`;
         ret += `//  In "/src/parser/generated_ast_printer.rs"\n`;
         ret += `// ==========================================\n\n`;
-        ret += "use crate::compiler::{arena::Arena, symbol::SymbolFactory, source_holder::SourceHolder};\n";
+        ret += "use crate::compiler::{arena::Arena, symbol::Interner, source_holder::SourceHolder};\n";
         ret += "use crate::parser::generated_ast::*;\n";
         ret += "use crate::parser::ast_node::{ASTNode, Node};\n";
         ret += "\n";
         ret += "pub struct ASTPrinter<'a, 'f, 'b, 'src> {\n";
         ret += "    pub arena: &'a Arena,\n";
-        ret += "    pub symbols: &'a SymbolFactory<'src>,\n";
+        ret += "    pub interner: &'a Interner,\n";
         ret += "    pub source_holder: &'a SourceHolder<'src>,\n";
         ret += "    pub writer: &'f mut std::fmt::Formatter<'b>,\n";
         ret += "}\n";
@@ -57,7 +57,7 @@ export class ASTPrinterGenerator {
 
                     // WARNING: hardcode identifier and string literal behavior
                     if (func.astTypeName == "Identifier") {
-                        ret += `        self.write(&format!("\\"{}\\"", self.symbols.get(&node.symbol)))?;\n`;
+                        ret += `        self.write(&format!("\\"{}\\"", node.symbol.display(self.interner)))?;\n`;
                     } else if (func.astTypeName == "StringLiteral") {
                         ret += `        self.write(&format!("\\"{}\\"", node.span.into(self.source_holder.get())))?;\n`;
                     } else {
@@ -100,7 +100,8 @@ export class ASTPrinterGenerator {
                 }
 
             } else if (func.kind === "hook") {
-                ret += `        self.write(r#"{"type":"${func.astTypeName}","hook":true}"#)?;\n`;
+                // hooks are printed by hand, see `manual_ast_printer`
+                ret += `        self.print_${func.astTypeName}(node)?;\n`;
             }
 
             ret += "        Ok(())\n";
@@ -1,4 +1,5 @@
 import { IR, BranchParserFunction, ProductParserFunction, HookParserFunction } from "./ir";
+import { getUniqueVariants } from "./gen";
 
 export class ASTTypeGenerator {
     public generateASTType(ir: IR): string {
@@ -26,9 +27,9 @@ export class ASTTypeGenerator {
         ret += `// ==========================================\n\n`;
         ret += `#![allow(non_snake_case)]\n`;
         ret += `#![allow(nonstandard_style)]\n\n`;
-        ret += `use crate::compiler::arena::{ArenaBox, ArenaIter};\n`;
-        ret += `use crate::parser::ast_node::{ASTNode, Node};\n`;
-        ret += `use crate::parser::ast_node::TokenBitMap;\n`;
+        ret += `use crate::compiler::arena::{Arena, ArenaBox, ArenaIter};\n`;
+        ret += `use crate::parser::ast_node::{ASTNode, Node, fold_iter, visit_boxed_mut, visit_iter_mut};\n`;
+        ret += `use crate::parser::ast_node::TokenSet;\n`;
         ret += `use crate::parser::errors::ParseErr;\n`;
         ret += `use crate::tokenizer::tokens::{Token, Delimiter, Keyword, Operator};\n\n`;
         ret += decls.join("");
@@ -45,6 +46,155 @@ export class ASTTypeGenerator {
         }
         ret += `}\n\n`;
 
+        // `visit_*` of `ASTVisitorMut` default to `walk_*`, which visits the children.
+        ret += `pub trait ASTVisitorMut: Sized {\n`;
+        for (const element of elements) {
+            ret += `    fn visit_${element}(&mut self, node: &mut ${element}, arena: &mut Arena) {\n`;
+            ret += `        walk_${element}(self, node, arena)\n`;
+            ret += `    }\n`;
+        }
+        ret += `}\n\n`;
+
+        // `fold_*` of `ASTFolder` default to `rebuild_*`, which folds the children.
+        ret += `pub trait ASTFolder: Sized {\n`;
+        for (const element of elements) {
+            ret += `    fn fold_${element}(&mut self, node: &${element}, from: &Arena, to: &Arena) -> ${element} {\n`;
+            ret += `        rebuild_${element}(self, node, from, to)\n`;
+            ret += `    }\n`;
+        }
+        ret += `}\n\n`;
+
+        return ret;
+    }
+
+    private generateNodeImpl(astTypeName: string): string {
+        let ret = "";
+        ret += `impl Node for ${astTypeName} {\n`;
+        ret += `    fn accept<V: ASTVisitor>(&self, visitor: &mut V) -> V::ReturnType {\n`;
+        ret += `        visitor.visit_${astTypeName}(self)\n`;
+        ret += `    }\n\n`;
+        ret += `    fn accept_mut<V: ASTVisitorMut>(&mut self, visitor: &mut V, arena: &mut Arena) {\n`;
+        ret += `        visitor.visit_${astTypeName}(self, arena)\n`;
+        ret += `    }\n\n`;
+        ret += `    fn fold<F: ASTFolder>(&self, folder: &mut F, from: &Arena, to: &Arena) -> Self {\n`;
+        ret += `        folder.fold_${astTypeName}(self, from, to)\n`;
+        ret += `    }\n`;
+        ret += `}\n\n`;
+        return ret;
+    }
+
+    /// The constants of `ASTNode`, which tell the parser the tokens a node
+    /// can start with and the ones it recovers at.
+    private generateTokenSets(syncPointsTerminals: string[], firstTerminals: string[]): string {
+        const tokenSet = (terminals: string[], eof: boolean) => {
+            const hasIdentifier = terminals.some(t => t.includes("Identifier"));
+            const hasLiteral = terminals.some(t => t.includes("Literal"));
+            const points = [...new Set(terminals.filter(t => !t.includes("$") && t !== "_"))].sort().join(", ");
+            return `TokenSet::build_map(${hasIdentifier}, ${hasLiteral}, ${eof}, &[${points}])`;
+        };
+        const first1Sets = firstTerminals.map(v => v.split(",")[0]);
+        const first2Sets = firstTerminals.map(v => v.split(",")[1]).filter(v => v !== undefined);
+        let ret = "";
+        ret += `    const FOLLOW_SETS: TokenSet = ${tokenSet(syncPointsTerminals, true)};\n`;
+        ret += `    const FIRST_1_SETS: TokenSet = ${tokenSet(first1Sets, false)};\n`;
+        // a hook can be followed by any token after its first one
+        if (first2Sets.includes("_")) {
+            ret += `    const FIRST_2_SETS: TokenSet = TokenSet::all();\n\n`;
+        } else {
+            ret += `    const FIRST_2_SETS: TokenSet = ${tokenSet(first2Sets, false)};\n\n`;
+        }
+        return ret;
+    }
+
+    /// `walk_*` and `rebuild_*` of a node without children.
+    private generateLeafTraversal(astTypeName: string): string {
+        let ret = "";
+        ret += `pub fn walk_${astTypeName}<V: ASTVisitorMut>(_visitor: &mut V, _node: &mut ${astTypeName}, _arena: &mut Arena) {}\n\n`;
+        ret += `pub fn rebuild_${astTypeName}<F: ASTFolder>(_folder: &mut F, node: &${astTypeName}, _from: &Arena, _to: &Arena) -> ${astTypeName} {\n`;
+        ret += `    *node\n`;
+        ret += `}\n\n`;
+        return ret;
+    }
+
+    private generateBranchTraversal(func: BranchParserFunction): string {
+        const name = func.astTypeName;
+        const variants = getUniqueVariants(func);
+        // a branch without variants only matches `Invalid`
+        const unused = variants.length === 0 ? "_" : "";
+        let ret = "";
+        ret += `pub fn walk_${name}<V: ASTVisitorMut>(${unused}visitor: &mut V, node: &mut ${name}, ${unused}arena: &mut Arena) {\n`;
+        ret += `    match node {\n`;
+        for (const variant of variants) {
+            if (variant.isBoxed) {
+                ret += `        ${name}::${variant.name}(v) => visit_boxed_mut(*v, arena, |v, arena| visitor.visit_${variant.name}(v, arena)),\n`;
+            } else {
+                ret += `        ${name}::${variant.name}(v) => visitor.visit_${variant.name}(v, arena),\n`;
+            }
+        }
+        ret += `        ${name}::Invalid => {}\n`;
+        ret += `    }\n`;
+        ret += `}\n\n`;
+
+        ret += `pub fn rebuild_${name}<F: ASTFolder>(${unused}folder: &mut F, node: &${name}, ${unused}from: &Arena, ${unused}to: &Arena) -> ${name} {\n`;
+        ret += `    match node {\n`;
+        for (const variant of variants) {
+            if (variant.isBoxed) {
+                ret += `        ${name}::${variant.name}(v) => ${name}::${variant.name}(to.alloc(folder.fold_${variant.name}(v.get(from), from, to))),\n`;
+            } else {
+                ret += `        ${name}::${variant.name}(v) => ${name}::${variant.name}(folder.fold_${variant.name}(v, from, to)),\n`;
+            }
+        }
+        ret += `        ${name}::Invalid => ${name}::Invalid,\n`;
+        ret += `    }\n`;
+        ret += `}\n\n`;
+        return ret;
+    }
+
+    private generateProductTraversal(func: ProductParserFunction): string {
+        const name = func.astTypeName;
+        let walk = "";
+        let rebuild = "";
+        for (const element of func.elements) {
+            switch (element.kind) {
+                case "terminal":
+                    continue;
+                case "normal":
+                    walk += `    visitor.visit_${element.astTypeName}(&mut node.${element.name}, arena);\n`;
+                    rebuild += `        ${element.name}: folder.fold_${element.astTypeName}(&node.${element.name}, from, to),\n`;
+                    break;
+                case "boxed":
+                    walk += `    visit_boxed_mut(node.${element.name}, arena, |v, arena| visitor.visit_${element.astTypeName}(v, arena));\n`;
+                    rebuild += `        ${element.name}: to.alloc(folder.fold_${element.astTypeName}(node.${element.name}.get(from), from, to)),\n`;
+                    break;
+                case "option":
+                    walk += `    if let Some(v) = &mut node.${element.name} {\n`;
+                    walk += `        visitor.visit_${element.astTypeName}(v, arena);\n`;
+                    walk += `    }\n`;
+                    rebuild += `        ${element.name}: node.${element.name}.map(|v| folder.fold_${element.astTypeName}(&v, from, to)),\n`;
+                    break;
+                case "optionWithBox":
+                    walk += `    if let Some(v) = node.${element.name} {\n`;
+                    walk += `        visit_boxed_mut(v, arena, |v, arena| visitor.visit_${element.astTypeName}(v, arena));\n`;
+                    walk += `    }\n`;
+                    rebuild += `        ${element.name}: node.${element.name}.map(|v| to.alloc(folder.fold_${element.astTypeName}(v.get(from), from, to))),\n`;
+                    break;
+                case "repeat":
+                    walk += `    visit_iter_mut(node.${element.name}, arena, |v, arena| visitor.visit_${element.astTypeName}(v, arena));\n`;
+                    rebuild += `        ${element.name}: fold_iter(node.${element.name}, from, to, |v| folder.fold_${element.astTypeName}(v, from, to)),\n`;
+                    break;
+            }
+        }
+        // a product without children leaves the parameters unused
+        const unused = walk === "" ? "_" : "";
+        let ret = "";
+        ret += `pub fn walk_${name}<V: ASTVisitorMut>(${unused}visitor: &mut V, ${unused}node: &mut ${name}, ${unused}arena: &mut Arena) {\n`;
+        ret += walk;
+        ret += `}\n\n`;
+        ret += `pub fn rebuild_${name}<F: ASTFolder>(${unused}folder: &mut F, ${unused}node: &${name}, ${unused}from: &Arena, ${unused}to: &Arena) -> ${name} {\n`;
+        ret += `    ${name} {\n`;
+        ret += rebuild;
+        ret += `    }\n`;
+        ret += `}\n\n`;
         return ret;
     }
 
@@ -73,20 +223,7 @@ export class ASTTypeGenerator {
         ret += `}\n\n`;
 
         ret += `impl ASTNode for ${func.astTypeName} {\n`;
-        const syncHasIdentifier = func.syncPointsTerminals.some(t => t.includes("Identifier"));
-        const syncHasLiteral = func.syncPointsTerminals.some(t => t.includes("Literal"));
-        const syncPoints = [...new Set(func.syncPointsTerminals.filter(t => !t.includes("$") && !t.includes("_")))].sort().join(", ");
-        const first1Sets = func.firstTerminals.map(v => v.split(",")[0]);
-        const first1HasIdentifier = first1Sets.some(t => t.includes("Identifier"));
-        const first1HasLiteral = first1Sets.some(t => t.includes("Literal"));
-        const first1Points = [...new Set(first1Sets.filter(t => !t.includes("$")))].sort().join(", ");
-        const first2Sets = func.firstTerminals.map(v => v.split(",")[1]).filter(v => v !== undefined);
-        const first2HasIdentifier = first2Sets.some(t => t.includes("Identifier"));
-        const first2HasLiteral = first2Sets.some(t => t.includes("Literal"));
-        const first2Points = [...new Set(first2Sets.filter(t => !t.includes("$")))].sort().join(", ");
-        ret += `    const FOLLOW_SETS: TokenBitMap = TokenBitMap::build_map(${syncHasIdentifier}, ${syncHasLiteral}, true, &[${syncPoints}]);\n`;
-        ret += `    const FIRST_1_SETS: TokenBitMap = TokenBitMap::build_map(${first1HasIdentifier}, ${first1HasLiteral}, false, &[${first1Points}]);\n`;
-        ret += `    const FIRST_2_SETS: TokenBitMap = TokenBitMap::build_map(${first2HasIdentifier}, ${first2HasLiteral}, false, &[${first2Points}]);\n\n`;
+        ret += this.generateTokenSets(func.syncPointsTerminals, func.firstTerminals);
         ret += `    fn get_error_situation(_: ParseErr) -> Option<Self> {\n`;
         ret += `        Some(Self::Invalid)\n`;
         ret += `    }\n\n`;
@@ -122,11 +259,8 @@ export class ASTTypeGenerator {
         }
         ret += `}\n\n`;
 
-        ret += `impl Node for ${func.astTypeName} {\n`;
-        ret += `    fn accept<V: ASTVisitor>(&self, visitor: &mut V) -> V::ReturnType {\n`;
-        ret += `        visitor.visit_${func.astTypeName}(self)\n`;
-        ret += `    }\n`
-        ret += `}\n\n`;
+        ret += this.generateNodeImpl(func.astTypeName);
+        ret += this.generateBranchTraversal(func);
         return ret;
     }
 
@@ -160,20 +294,7 @@ export class ASTTypeGenerator {
             ret += `}\n\n`;
         }
         ret += `impl ASTNode for ${func.astTypeName} {\n`;
-        const syncHasIdentifier = func.syncPointsTerminals.some(t => t.includes("Identifier"));
-        const syncHasLiteral = func.syncPointsTerminals.some(t => t.includes("Literal"));
-        const syncPoints = func.syncPointsTerminals.filter(t => !t.includes("$")).join(", ");
-        const first1Sets = func.firstTerminals.map(v => v.split(",")[0]);
-        const first1HasIdentifier = first1Sets.some(t => t.includes("Identifier"));
-        const first1HasLiteral = first1Sets.some(t => t.includes("Literal"));
-        const first1Points = first1Sets.filter(t => !t.includes("$")).join(", ");
-        const first2Sets = func.firstTerminals.map(v => v.split(",")[1]).filter(v => v !== undefined);
-        const first2HasIdentifier = first2Sets.some(t => t.includes("Identifier"));
-        const first2HasLiteral = first2Sets.some(t => t.includes("Literal"));
-        const first2Points = first2Sets.filter(t => !t.includes("$")).join(", ");
-        ret += `    const FOLLOW_SETS: TokenBitMap = TokenBitMap::build_map(${syncHasIdentifier}, ${syncHasLiteral}, true, &[${syncPoints}]);\n`;
-        ret += `    const FIRST_1_SETS: TokenBitMap = TokenBitMap::build_map(${first1HasIdentifier}, ${first1HasLiteral}, false, &[${first1Points}]);\n`;
-        ret += `    const FIRST_2_SETS: TokenBitMap = TokenBitMap::build_map(${first2HasIdentifier}, ${first2HasLiteral}, false, &[${first2Points}]);\n\n`;
+        ret += this.generateTokenSets(func.syncPointsTerminals, func.firstTerminals);
         ret += `    fn get_error_situation(_: ParseErr) -> Option<Self> {\n`;
         ret += `        None\n`;
         ret += `    }\n\n`;
@@ -211,16 +332,29 @@ export class ASTTypeGenerator {
         }
         ret += `}\n\n`;
 
-        ret += `impl Node for ${func.astTypeName} {\n`;
-        ret += `    fn accept<V: ASTVisitor>(&self, visitor: &mut V) -> V::ReturnType {\n`;
-        ret += `        visitor.visit_${func.astTypeName}(self)\n`;
-        ret += `    }\n`
-        ret += `}\n\n`
+        ret += this.generateNodeImpl(func.astTypeName);
+        if (isManual) {
+            ret += this.generateLeafTraversal(func.astTypeName);
+        } else {
+            ret += this.generateProductTraversal(func);
+        }
 
         return ret;
     }
 
     private generateHookASTType(func: HookParserFunction): string {
-        return `pub use crate::parser::manual_ast::${func.astTypeName};\n\n`;
+        const name = func.astTypeName;
+        // the children of a hook are only known to the hand written code
+        let ret = `pub use crate::parser::manual_ast::{${name}, walk_${name}, rebuild_${name}};\n\n`;
+        ret += `impl ASTNode for ${name} {\n`;
+        ret += this.generateTokenSets(func.syncPointsTerminals, func.firstTerminals);
+        ret += `    fn get_error_situation(_: ParseErr) -> Option<Self> {\n`;
+        ret += `        Some(Self::Invalid)\n`;
+        ret += `    }\n\n`;
+        ret += `    fn ast_name() -> &'static str { "${name}" }\n`;
+        ret += `    type Target = Self;\n`;
+        ret += `}\n\n`;
+        ret += this.generateNodeImpl(name);
+        return ret;
     }
 }
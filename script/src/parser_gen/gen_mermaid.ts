@@ -25,6 +25,10 @@ function generateMermaid(grammar: Grammar): string {
                     lines.push(`    ${rule.name} --> ${member.type.name}`);
                 }
             }
+        } else if (rule.kind === "Hook") {
+            lines.push(`    class ${rule.name} {`);
+            lines.push(`        <<hook>>`);
+            lines.push(`    }`);
         }
     }
     return lines.join("\n");
@@ -104,7 +104,8 @@ export class ParserGenerator {
                 ret += `                _ => {\n`;
                 for (const b of backtrack) {
                     const wrapCall = b.isBoxed ? `self.alloc(node)` : `node`;
-                    ret += `                    if let Ok(node) = self.backtrack(|this| this.parse_${b.astTypeName}()) {\n`;
+                    // alternatives sharing a prefix would parse it again for every try
+                    ret += `                    if let Ok(node) = self.backtrack(|this| this.memoize(Self::parse_${b.astTypeName})) {\n`;
                     ret += `                        return Ok(${func.astTypeName}::${b.astTypeName}(${wrapCall}));\n`;
                     ret += `                    }\n`;
                 }
@@ -16,6 +16,8 @@ export type HookParserFunction = {
     kind: "hook",
     functionName: RustFunctionName,
     astTypeName: RustASTTypeName,
+    syncPointsTerminals: RustTokenTypeName[],
+    firstTerminals: RustTokenTypeName[],
     isBoxed: boolean,
 };
 
@@ -1,4 +1,4 @@
-import { Token, TOKEN_PATTERNS, Rule, BranchRule, BranchVariant, ProductRule, ProductMember, TypeReference, Grammar } from "./ast";
+import { Token, TOKEN_PATTERNS, Rule, BranchRule, BranchVariant, ProductRule, ProductMember, TypeReference, Grammar, HookRule } from "./ast";
 
 class Lexer {
     private input: string;
@@ -51,6 +51,7 @@ class Lexer {
                         switch (kind) {
                             case 'Branch': tokens.push({ kind: 'Branch' }); break;
                             case 'Product': tokens.push({ kind: 'Product' }); break;
+                            case 'Hook': tokens.push({ kind: 'Hook' }); break;
                             case 'With': tokens.push({ kind: 'With' }); break;
                             case 'LBrace': tokens.push({ kind: 'LBrace' }); break;
                             case 'RBrace': tokens.push({ kind: 'RBrace' }); break;
@@ -111,7 +112,7 @@ class Parser {
         throw new Error(`Expected StringLiteral, but got ${token.kind} at position ${this.position}`);
     }
 
-    private consumeKeyword(kind: 'Branch' | 'Product' | 'LBrace' | 'RBrace' | 'Colon' | 'With' | 'Star' | 'Question'): void {
+    private consumeKeyword(kind: 'Branch' | 'Product' | 'Hook' | 'LBrace' | 'RBrace' | 'Colon' | 'With' | 'Star' | 'Question'): void {
         const token = this.peek();
         if (token.kind === kind) {
             this.advance();
@@ -137,8 +138,10 @@ class Parser {
             return this.parseBranchRule();
         } else if (token.kind === 'Product') {
             return this.parseProductRule();
+        } else if (token.kind === 'Hook') {
+            return this.parseHookRule();
         } else {
-            throw new Error(`Expected 'branch' or 'product', but got ${token.kind} at position ${this.position}`);
+            throw new Error(`Expected 'branch', 'product' or 'hook', but got ${token.kind} at position ${this.position}`);
         }
     }
 
@@ -208,6 +211,21 @@ class Parser {
 
     }
 
+    // <hook_rule> ::= "hook" <identifier> "{" { <terminal> } "}"
+    private parseHookRule(): HookRule {
+        this.consumeKeyword('Hook');
+        const name = this.consumeIdentifier();
+        this.consumeKeyword('LBrace');
+
+        const firstTerminals: string[] = [];
+        while (this.peek().kind !== 'RBrace' && this.peek().kind !== 'EOF') {
+            firstTerminals.push(this.consumeStringLiteral());
+        }
+
+        this.consumeKeyword('RBrace');
+        return { kind: 'Hook', name, firstTerminals };
+    }
+
     // <product_item> ::= ( <identifier> ":" <nonterminal> ) | <terminal>
     private parseProductItem(): ProductMember {
         const token = this.peek();
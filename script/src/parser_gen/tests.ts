@@ -42,6 +42,16 @@ describe('Parser (parse.ts)', () => {
         match(members[0].type.modifier).toBe('List');
         match(members[1].type.modifier).toBe('Option');
     });
+
+    it('should parse Hook rules with the tokens they start with', () => {
+        const src = `
+            hook Expr { "a" "b" }
+        `;
+        const ast = parse(src);
+        match(ast.length).toBe(1);
+        match(ast[0].kind).toBe('Hook');
+        match((ast[0] as any).firstTerminals.join(' ')).toBe('a b');
+    });
 });
 
 describe('Analyzer', () => {
@@ -202,6 +212,52 @@ describe('Analyzer', () => {
         });
     });
 
+    describe('analyzeHookRule', () => {
+        it('should not know the tokens after the first one of a hook', () => {
+            const grammar: Grammar = [
+                // Stmt -> Let | ExprStmt
+                {
+                    kind: 'Branch', name: 'Stmt', variants: [
+                        { name: 'Let', note: '' },
+                        { name: 'ExprStmt', note: '' }
+                    ]
+                },
+                // Let -> "a" "b"
+                { kind: 'Product', name: 'Let', members: [{ kind: 'Terminal', value: 'a', note: '' }, { kind: 'Terminal', value: 'b', note: '' }] },
+                // ExprStmt -> Expr "c"
+                {
+                    kind: 'Product', name: 'ExprStmt', members: [
+                        { kind: 'Field', name: 'expr', type: { name: 'Expr', modifier: 'None' }, note: '' },
+                        { kind: 'Terminal', value: 'c', note: '' }
+                    ]
+                },
+                // Expr is parsed by hand and starts with "a"
+                { kind: 'Hook', name: 'Expr', firstTerminals: ['a'] },
+            ];
+
+            const analyzer = new Analyzer(grammar, mockTokenMap);
+            const [ir] = analyzer.analyze();
+
+            // "c" is not the second token of ExprStmt, the hook is longer
+            const firstExprStmt = (analyzer as any).firstSets.get('ExprStmt');
+            match([...firstExprStmt].join(' ')).toBe('TokenA,_');
+
+            const followExpr = (analyzer as any).followSets.get('Expr');
+            match([...followExpr].join(' ')).toBe('TokenC');
+
+            const branchIR = ir.find(i => i.kind === 'branch') as any;
+            match(branchIR.branchesJudgebleInPeek1).toContainEqual(
+                { astTypeName: 'Let', firstTerminal: 'TokenA', secondTerminal: 'TokenB', isBoxed: false }
+            );
+            match(branchIR.branchesFallbackInPeek1).toContainEqual(
+                { astTypeName: 'ExprStmt', firstTerminal: 'TokenA', isBoxed: false }
+            );
+
+            const hookIR = ir.find(i => i.kind === 'hook') as any;
+            match(hookIR.firstTerminals.join(' ')).toBe('TokenA,_');
+        });
+    });
+
     describe('computeCycle (Boxed Types)', () => {
         it('should mark recursive fields as boxed to prevent infinite size in Rust', () => {
             const grammar: Grammar = [
@@ -2,36 +2,122 @@
 //! It handles parsing command-line arguments and orchestrating the compilation process
 //! based on user input.
 
+use std::path::{Path, PathBuf};
+
 use clap::{Parser, Subcommand, ValueEnum};
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::compiler::arena::Arena;
+use crate::compiler::runtime::persist::{CacheErr, Decoder, Encoder, Persist};
+use crate::compiler::runtime::profile::Profiler;
+use crate::compiler::runtime::query_sys::{Database, PersistentQuery, Query, DEFAULT_CACHE_DIR};
+use crate::compiler::source_holder::SourceHolder;
+use crate::diagnostic::diagnotice_patterns::{self, DiagnosticCode};
+use crate::diagnostic::emitter::{EmitterDiagnosticStream, ErrorFormat, FixNote};
+use crate::diagnostic::fixer::{self, Fixed};
+use crate::diagnostic::stream::DiagnosticStream;
+use crate::parser::tracer::{NopeTracer, TreeTracer};
+use crate::parser::{parse_module, parse_module_into};
+use crate::utility::read_file_query::ReadFileQuery;
 
-use crate::parser::parse_for_test;
+/// The file that marks the root of a workspace.
+const WORKSPACE_MANIFEST: &str = "deps.json";
+/// Where `--timings` writes the Chrome trace of the build, under the project root.
+const TIMINGS_TRACE_PATH: &str = "target/hydent-timings.json";
+/// How many queries the `--timings` table lists.
+const TIMINGS_TABLE_LEN: usize = 10;
+/// How many rules the `--trace-parser=hot` table lists.
+const HOT_RULES_TABLE_LEN: usize = 20;
 
 pub fn call_cli() {
     let parsed = Cli::parse();
 
+    // stdout is kept for the diagnostics of the machine-readable formats
     let log = |msg: &str| {
         if parsed.verbose {
-            println!("{}", msg);
+            eprintln!("{}", msg);
         }
     };
 
+    let mut db = Database::new();
+    db.register_persistent::<ReadFileQuery>();
+    db.register_persistent::<EmitAstQuery>();
+    let root = match &parsed.command {
+        Commands::Build { path, .. } => Some(project_root(path)),
+        _ => None,
+    };
+    // a traced build has to run the parser to trace it
+    let cache_dir = root
+        .as_ref()
+        .filter(|_| parsed.trace_parser.is_none())
+        .map(|root| root.join(DEFAULT_CACHE_DIR));
+    if let Some(dir) = &cache_dir {
+        match db.load(dir) {
+            Ok(()) => {}
+            // an unreadable cache is dropped, and the build starts cold
+            Err(err) => log(&format!("The query cache is not used: {:?}", err)),
+        }
+    }
+    if parsed.timings || parsed.query_graph.is_some() {
+        let mut profiler = Profiler::new();
+        if parsed.timings {
+            let progress = ProgressBar::new_spinner().with_style(
+                ProgressStyle::with_template("{spinner} {pos} queries {wide_msg}").unwrap(),
+            );
+            profiler = profiler.with_progress(progress);
+        }
+        db.enable_profiling(profiler);
+    }
+
     match parsed.command {
         Commands::Build { path, emit, out } => match emit {
             EmitItems::Ast => {
-                log("Getting file contents...");
-                let source = std::fs::read_to_string(&path)
-                    .expect("We can't find file or permission denied.");
+                let root = root.expect("A build has a project root.");
+                let fixes = if parsed.fix {
+                    log("Fixing...");
+                    fix_file(&path)
+                } else {
+                    Vec::new()
+                };
                 log("Parsing...");
-                let (ast, diagnostics) = parse_for_test(&source);
-                if diagnostics.is_empty() {
-                    log("Writing into file...");
-                    std::fs::write(&out, ast.to_string()).expect("We failed to write the results.");
+                let parsed_file = db
+                    .fetch::<EmitAstQuery>(EmitAstInput::new(
+                        root.clone(),
+                        &path,
+                        parsed.trace_parser,
+                        parsed.error_format,
+                        fixes,
+                        parsed.arena_stats,
+                    ))
+                    .unwrap();
+                if let (Some(format), Some(trace)) = (parsed.trace_parser, &parsed_file.trace) {
+                    write_parser_trace(&root, format, trace);
+                }
+                if let Some(dir) = &cache_dir {
+                    save_cache(&db, dir);
+                }
+                // stdout is kept for the machine-readable formats, so that
+                // they can be piped
+                if parsed.error_format == ErrorFormat::Human {
+                    eprint!("{}", parsed_file.diagnostics);
                 } else {
-                    log("Error occured while parsing.");
-                    for diagnostic in diagnostics {
-                        println!("{}", diagnostic);
+                    print!("{}", parsed_file.diagnostics);
+                }
+                match parsed_file.ast {
+                    Some(emitted) => {
+                        log("Writing into file...");
+                        std::fs::write(&out, emitted.printed)
+                            .expect("We failed to write the results.");
+                        if parsed.arena_stats {
+                            eprint!("AST arena: {}", emitted.arena_stats);
+                        }
+                        report_profile(&db, &root, parsed.timings, parsed.query_graph.as_deref());
+                    }
+                    None => {
+                        log("Error occured while parsing.");
+                        report_profile(&db, &root, parsed.timings, parsed.query_graph.as_deref());
+                        std::process::exit(1);
                     }
-                    panic!();
                 }
             }
             EmitItems::Hir => {
@@ -47,6 +133,290 @@ pub fn call_cli() {
                 unimplemented!()
             }
         },
+        Commands::Explain { code } => match diagnotice_patterns::lookup(&code) {
+            Some(code) => print!("{}", explain(code)),
+            None => {
+                eprintln!("error: `{code}` is not a diagnostic code");
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+/// Writes the query cache for the next build. A build which can't write it
+/// still succeeds.
+fn save_cache(db: &Database, dir: &Path) {
+    if let Err(err) = db.save(dir) {
+        eprintln!("warning: failed to write the query cache: {:?}", err);
+    }
+}
+
+/// What `hydent explain` prints for `code`.
+fn explain(code: &DiagnosticCode) -> String {
+    format!(
+        "# {} ({}): {}\n\n{}",
+        code.code(),
+        code.level,
+        code.name,
+        code.published_explanation()
+    )
+}
+
+#[derive(Clone, Hash)]
+struct EmittedAst {
+    printed: String,
+    /// The printed stats of the AST arena.
+    arena_stats: String,
+}
+
+impl Persist for EmittedAst {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        self.printed.encode(encoder);
+        self.arena_stats.encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        Ok(EmittedAst {
+            printed: String::decode(decoder)?,
+            arena_stats: String::decode(decoder)?,
+        })
+    }
+}
+
+#[derive(Clone, Hash)]
+struct EmitAstInput {
+    /// The absolute path of the project the file belongs to.
+    root: PathBuf,
+    /// The path of the file relative to `root`, so that a build started from
+    /// another directory of the project finds the same query.
+    path: PathBuf,
+    /// How to print the trace of the parser, if it should be traced.
+    trace: Option<TraceFormat>,
+    error_format: ErrorFormat,
+    /// What `--fix` did to the file before it was read.
+    fixes: Vec<FixNote>,
+    /// Whether the AST arena records the types it allocates.
+    arena_stats: bool,
+}
+
+impl EmitAstInput {
+    fn new(
+        root: PathBuf,
+        path: &Path,
+        trace: Option<TraceFormat>,
+        error_format: ErrorFormat,
+        fixes: Vec<FixNote>,
+        arena_stats: bool,
+    ) -> Self {
+        let path = std::path::absolute(path)
+            .expect("We can't find the current directory.")
+            .strip_prefix(&root)
+            .expect("The project root is a parent of the file.")
+            .to_path_buf();
+        EmitAstInput {
+            root,
+            path,
+            trace,
+            error_format,
+            fixes,
+            arena_stats,
+        }
+    }
+}
+
+impl Persist for EmitAstInput {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_str(&self.root.to_string_lossy());
+        encoder.write_str(&self.path.to_string_lossy());
+        self.trace.encode(encoder);
+        self.error_format.encode(encoder);
+        self.fixes.encode(encoder);
+        self.arena_stats.encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        Ok(EmitAstInput {
+            root: PathBuf::from(decoder.read_str()?),
+            path: PathBuf::from(decoder.read_str()?),
+            trace: Option::decode(decoder)?,
+            error_format: ErrorFormat::decode(decoder)?,
+            fixes: Vec::decode(decoder)?,
+            arena_stats: bool::decode(decoder)?,
+        })
+    }
+}
+
+#[derive(Clone, Hash)]
+struct ParsedFile {
+    /// The printed AST, unless parsing failed.
+    ast: Option<EmittedAst>,
+    /// The fixes and diagnostics of the file, printed in the asked format.
+    diagnostics: String,
+    /// The printed trace of the parser, if it was asked for.
+    trace: Option<String>,
+}
+
+impl Persist for ParsedFile {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        self.ast.encode(encoder);
+        self.diagnostics.encode(encoder);
+        self.trace.encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        Ok(ParsedFile {
+            ast: Option::decode(decoder)?,
+            diagnostics: String::decode(decoder)?,
+            trace: Option::decode(decoder)?,
+        })
+    }
+}
+
+/// Reads and parses a source file.
+struct EmitAstQuery;
+impl Query for EmitAstQuery {
+    type From = EmitAstInput;
+    type To = ParsedFile;
+    fn run(db: &Database, input: Self::From) -> Self::To {
+        let path = input.path.to_string_lossy().into_owned();
+        let source = db
+            .fetch::<ReadFileQuery>(input.root.join(&input.path).to_string_lossy().into_owned())
+            .unwrap()
+            .expect("We can't find file or permission denied.");
+        let ast_arena = if input.arena_stats {
+            Arena::new().with_type_stats()
+        } else {
+            Arena::new()
+        };
+        let ((ast, diagnostics), trace) = match input.trace {
+            Some(format) => {
+                TreeTracer::start();
+                let parsed = parse_module_into::<TreeTracer>(source.text(), ast_arena);
+                let mut trace = Vec::new();
+                let trace_result = match format {
+                    TraceFormat::Text => TreeTracer::finish().write_text(&mut trace),
+                    TraceFormat::Chrome => TreeTracer::finish().write_chrome_trace(&mut trace),
+                    TraceFormat::Hot => {
+                        TreeTracer::finish().write_hot_rules(HOT_RULES_TABLE_LEN, &mut trace)
+                    }
+                };
+                trace_result.expect("We failed to print the parser trace.");
+                (parsed, Some(String::from_utf8(trace).unwrap()))
+            }
+            None => (
+                parse_module_into::<NopeTracer>(source.text(), ast_arena),
+                None,
+            ),
+        };
+        let mut emitter =
+            EmitterDiagnosticStream::new(input.error_format, &path, source.text(), Vec::new());
+        for note in &input.fixes {
+            emitter.note(note);
+        }
+        for diagnostic in diagnostics {
+            emitter.pour(diagnostic, &());
+        }
+        let ast = (emitter.error_count() == 0).then(|| EmittedAst {
+            printed: ast.to_string(),
+            arena_stats: ast.arena_stats().to_string(),
+        });
+        let diagnostics = emitter
+            .finish()
+            .expect("We failed to print the diagnostics.");
+        ParsedFile {
+            ast,
+            diagnostics: String::from_utf8(diagnostics).unwrap(),
+            trace,
+        }
+    }
+}
+impl PersistentQuery for EmitAstQuery {
+    const NAME: &'static str = "EmitAst";
+}
+
+/// The root of the project the file at `path` belongs to: the nearest
+/// directory above it holding a `deps.json`, or the directory of the file
+/// when it is not in a workspace.
+fn project_root(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).expect("We can't find the current directory.");
+    let dir = path.parent().unwrap_or(&path);
+    dir.ancestors()
+        .find(|dir| dir.join(WORKSPACE_MANIFEST).is_file())
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
+/// Applies the machine-applicable suggestions of the file at `path` in place
+/// and returns what changed, which the build reports with its diagnostics.
+fn fix_file(path: &Path) -> Vec<FixNote> {
+    let source = std::fs::read_to_string(path).expect("We can't find file or permission denied.");
+    let Fixed {
+        source: fixed,
+        applied,
+        conflicting,
+    } = match fixer::fix(&source, |source| parse_module(source).1) {
+        Ok(fixed) => fixed,
+        Err(err) => {
+            return vec![FixNote::Rejected {
+                errors_before: err.errors_before,
+                errors_after: err.errors_after,
+            }];
+        }
+    };
+    let holder = SourceHolder::from_source(&source);
+    let notes = applied
+        .iter()
+        .map(|fix| FixNote::new(&holder, fix, true))
+        .chain(
+            conflicting
+                .iter()
+                .map(|fix| FixNote::new(&holder, fix, false)),
+        )
+        .collect();
+    if !applied.is_empty() {
+        std::fs::write(path, fixed).expect("We failed to write the fixed file.");
+    }
+    notes
+}
+
+/// Writes what `--trace-parser` asked for under the project root `root`.
+fn write_parser_trace(root: &Path, format: TraceFormat, trace: &str) {
+    let path = root.join(format.path());
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).expect("We failed to create the parser trace directory.");
+    }
+    std::fs::write(&path, trace).expect("We failed to write the parser trace.");
+}
+
+/// Writes what `--timings` and `--query-graph` asked for. The timings go
+/// under the project root `root`.
+fn report_profile(db: &Database, root: &Path, timings: bool, query_graph: Option<&Path>) {
+    let Some(profiler) = db.profiler() else {
+        return;
+    };
+    profiler.finish();
+    if timings {
+        let mut stderr = std::io::stderr().lock();
+        profiler
+            .write_slowest_table(TIMINGS_TABLE_LEN, &mut stderr)
+            .expect("We failed to print the timings.");
+        let path = root.join(TIMINGS_TRACE_PATH);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).expect("We failed to create the timings directory.");
+        }
+        let mut trace = std::io::BufWriter::new(
+            std::fs::File::create(&path).expect("We failed to write the timings."),
+        );
+        profiler
+            .write_chrome_trace(&mut trace)
+            .expect("We failed to write the timings.");
+    }
+    if let Some(path) = query_graph {
+        let mut graph = std::io::BufWriter::new(
+            std::fs::File::create(path).expect("We failed to write the query graph."),
+        );
+        db.write_query_graph(&mut graph)
+            .expect("We failed to write the query graph.");
     }
 }
 
@@ -55,6 +425,34 @@ struct Cli {
     #[arg(long, global = true, default_value_t = false)]
     verbose: bool,
 
+    /// Print the slowest queries and write a Chrome trace of the build to
+    /// `target/hydent-timings.json` under the project root.
+    #[arg(long, global = true, default_value_t = false)]
+    timings: bool,
+
+    /// Print how much memory the AST arena uses and which node types take the most.
+    #[arg(long, global = true, default_value_t = false)]
+    arena_stats: bool,
+
+    /// Trace the rules the parser enters and write the trace to
+    /// `target/hydent-parser-trace.*` under the project root.
+    #[arg(long, global = true, value_enum, value_name = "FORMAT")]
+    trace_parser: Option<TraceFormat>,
+
+    /// Write the query dependency graph as a Graphviz DOT file.
+    #[arg(long, global = true, value_name = "PATH")]
+    query_graph: Option<PathBuf>,
+
+    /// How to print the diagnostics: rendered for people, as JSON lines, or
+    /// as a SARIF log for code scanning.
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Human)]
+    error_format: ErrorFormat,
+
+    /// Apply the suggestions which are certainly right to the source files
+    /// in place, then check them again.
+    #[arg(long, global = true, default_value_t = false)]
+    fix: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -69,6 +467,8 @@ enum Commands {
         #[arg(long, short)]
         out: std::path::PathBuf,
     },
+    /// Print the explanation of a diagnostic code, like `E0005`.
+    Explain { code: String },
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
@@ -79,3 +479,42 @@ enum EmitItems {
     Llvmir,
     Bin,
 }
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, ValueEnum, Debug)]
+enum TraceFormat {
+    /// An indented tree of the rules with their tokens and times.
+    Text,
+    /// A Chrome trace, which can be opened in `chrome://tracing` or Perfetto.
+    Chrome,
+    /// A table of the rules the parser spent the most time in.
+    Hot,
+}
+
+impl Persist for TraceFormat {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_u8(match self {
+            TraceFormat::Text => 0,
+            TraceFormat::Chrome => 1,
+            TraceFormat::Hot => 2,
+        });
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        match decoder.read_u8()? {
+            0 => Ok(TraceFormat::Text),
+            1 => Ok(TraceFormat::Chrome),
+            2 => Ok(TraceFormat::Hot),
+            _ => Err(CacheErr::Corrupted),
+        }
+    }
+}
+
+impl TraceFormat {
+    fn path(self) -> &'static str {
+        match self {
+            TraceFormat::Text => "target/hydent-parser-trace.txt",
+            TraceFormat::Chrome => "target/hydent-parser-trace.json",
+            TraceFormat::Hot => "target/hydent-parser-trace-hot.txt",
+        }
+    }
+}
@@ -3,12 +3,52 @@
 use core::{
     cell::{Cell, UnsafeCell},
     marker::PhantomData,
+    mem::MaybeUninit,
     ptr::NonNull,
 };
 use std::alloc::{alloc, Layout};
+use std::collections::HashMap;
+#[cfg(debug_assertions)]
+use std::sync::atomic::{AtomicU32, Ordering};
 
 static ALIGNMENT: usize = 64;
 
+/// The id of handles to zero-sized values and empty iterators, which own no memory.
+const DANGLING_ID: u32 = u32::MAX;
+
+#[cfg(debug_assertions)]
+static NEXT_ARENA_TAG: AtomicU32 = AtomicU32::new(0);
+
+/// Identifies the arena a handle was allocated in.
+///
+/// The tag is only stored and checked in debug builds, so handles stay
+/// as small as their id in release builds.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+struct ArenaTag {
+    #[cfg(debug_assertions)]
+    id: u32,
+}
+
+impl ArenaTag {
+    fn new() -> Self {
+        Self {
+            #[cfg(debug_assertions)]
+            id: NEXT_ARENA_TAG.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    #[inline]
+    fn check(self, handle: ArenaTag) {
+        #[cfg(debug_assertions)]
+        assert_eq!(
+            self.id, handle.id,
+            "an arena handle was used with an arena it was not allocated in"
+        );
+        #[cfg(not(debug_assertions))]
+        let _ = handle;
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct AllocState {
     item_size: usize,
@@ -17,125 +57,311 @@ struct AllocState {
     buffer_start_offset: usize, // iter_buffer内でのこの階層の開始位置
 }
 
+/// Memory obtained from the global allocator, which backs `pages` consecutive pages.
+struct Chunk {
+    ptr: *mut u8,
+    first_page: usize,
+    pages: usize,
+}
+
+/// What `Arena::stats` reports about the memory of an arena.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ArenaStats {
+    /// Bytes taken by allocated values.
+    pub bytes_used: usize,
+    /// Bytes obtained from the global allocator.
+    pub bytes_reserved: usize,
+    pub pages: usize,
+    /// Bytes skipped to align values.
+    pub alignment_waste: usize,
+    /// The allocated types, the ones taking the most bytes first. Only
+    /// filled for arenas made with `Arena::with_type_stats`.
+    pub types: Vec<TypeStats>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TypeStats {
+    pub name: &'static str,
+    pub size: usize,
+    pub count: usize,
+}
+
+impl TypeStats {
+    pub fn bytes(&self) -> usize {
+        self.size * self.count
+    }
+}
+
+impl std::fmt::Display for ArenaStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} bytes used of {} reserved in {} pages, {} bytes lost to alignment",
+            self.bytes_used, self.bytes_reserved, self.pages, self.alignment_waste
+        )?;
+        for stats in &self.types {
+            writeln!(
+                f,
+                "{:>12} bytes {:>9} x {:>4} bytes  {}",
+                stats.bytes(),
+                stats.count,
+                stats.size,
+                stats.name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A bump allocator handing out `Copy` values by `u32` handles.
+///
+/// Handles are byte offsets into the arena, with the arena divided into pages
+/// of `block_size` bytes. A value larger than a page gets a chunk of several
+/// consecutive pages, so offsets stay valid across page boundaries. Since
+/// handles are `u32`s, one arena holds at most 4 GiB; allocating beyond that
+/// panics instead of handing out aliasing handles.
 pub struct Arena {
+    tag: ArenaTag,
+    block_size: usize,
     index: Cell<usize>,
     page_index: Cell<usize>,
+    chunk_index: Cell<usize>,
     ptrs: Box<UnsafeCell<Vec<*mut u8>>>,
+    chunks: UnsafeCell<Vec<Chunk>>,
+    bytes_used: Cell<usize>,
+    alignment_waste: Cell<usize>,
+    /// Whether `types` is filled, see `Arena::with_type_stats`.
+    track_types: bool,
+    types: UnsafeCell<HashMap<&'static str, TypeStats>>,
     // ネストを管理するスタック
     states: UnsafeCell<Vec<AllocState>>,
     // すべてのネストで共有する作業用バッファ
-    iter_buffer: UnsafeCell<Vec<u8>>,
+    // (padding bytes of the items are uninitialized, so they are not `u8`s)
+    iter_buffer: UnsafeCell<Vec<MaybeUninit<u8>>>,
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub struct ArenaBox<T: Copy> {
     id: u32,
+    tag: ArenaTag,
     _marker: PhantomData<T>,
 }
 
 impl<T: Copy> ArenaBox<T> {
     #[inline]
     pub fn get<'a>(&self, arena: &'a Arena) -> &'a T {
-        if std::mem::size_of::<T>() == 0 {
-            return unsafe { &*NonNull::dangling().as_ptr() };
-        }
-        let page = (self.id as usize) / Arena::BLOCK_SIZE;
-        let offset = (self.id as usize) % Arena::BLOCK_SIZE;
-        unsafe {
-            let pages = &*arena.ptrs.get();
-            let page_ptr = *(*pages).as_ptr().add(page);
-            &*(page_ptr.add(offset) as *const T)
-        }
+        arena.tag.check(self.tag);
+        // SAFETY: the value was written by `Arena::alloc` and lives as long as the arena.
+        unsafe { &*arena.resolve::<T>(self.id) }
     }
 
     #[inline]
     pub fn get_mut<'a>(&mut self, arena: &'a mut Arena) -> &'a mut T {
-        if std::mem::size_of::<T>() == 0 {
-            return unsafe { &mut *NonNull::dangling().as_ptr() };
-        }
-        let page = (self.id as usize) / Arena::BLOCK_SIZE;
-        let offset = (self.id as usize) % Arena::BLOCK_SIZE;
-        unsafe {
-            let pages = &*arena.ptrs.get();
-            let page_ptr = *(*pages).as_ptr().add(page);
-            &mut *(page_ptr.add(offset) as *mut T)
-        }
+        arena.tag.check(self.tag);
+        // SAFETY: as in `get`, and the arena is borrowed exclusively.
+        unsafe { &mut *arena.resolve::<T>(self.id) }
     }
 }
 
+/// A handle to items allocated next to each other in an `Arena`.
+///
+/// The items of one `ArenaIter` are always stored contiguously, so they can
+/// be viewed as a slice.
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub struct ArenaIter<T: Copy> {
     start_id: u32,
     len: u32,
+    tag: ArenaTag,
     _marker: PhantomData<T>,
 }
 
 impl<T: Copy> ArenaIter<T> {
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get<'a>(&self, index: usize, arena: &'a Arena) -> Option<&'a T> {
+        self.as_slice(arena).get(index)
+    }
+
+    pub fn as_slice<'a>(&self, arena: &'a Arena) -> &'a [T] {
+        arena.tag.check(self.tag);
+        // SAFETY: `finish_iter_allocation` wrote `len` items from `start_id`
+        // on, and they live as long as the arena.
+        unsafe { std::slice::from_raw_parts(arena.resolve::<T>(self.start_id), self.len()) }
+    }
+
+    /// Views the items as a mutable slice. This borrows the arena
+    /// exclusively, so no other reference into the arena can be alive.
+    pub fn as_mut_slice<'a>(&self, arena: &'a mut Arena) -> &'a mut [T] {
+        arena.tag.check(self.tag);
+        // SAFETY: as in `as_slice`, and the arena is borrowed exclusively.
+        unsafe { std::slice::from_raw_parts_mut(arena.resolve::<T>(self.start_id), self.len()) }
+    }
+
+    /// Splits the handle into one to the first item and one to the rest,
+    /// without touching the arena.
+    pub fn split_first(&self) -> Option<(ArenaBox<T>, ArenaIter<T>)> {
+        if self.is_empty() {
+            return None;
+        }
+        let size = std::mem::size_of::<T>() as u32;
+        let rest_start_id = if size == 0 || self.len == 1 {
+            DANGLING_ID
+        } else {
+            self.start_id + size
+        };
+        let first = ArenaBox {
+            id: self.start_id,
+            tag: self.tag,
+            _marker: PhantomData,
+        };
+        let rest = ArenaIter {
+            start_id: rest_start_id,
+            len: self.len - 1,
+            tag: self.tag,
+            _marker: PhantomData,
+        };
+        Some((first, rest))
+    }
+
+    /// Iterates over shared references to the items.
     pub fn into_ref<'a>(&self, arena: &'a Arena) -> ArenaIterReader<'a, T> {
         ArenaIterReader {
-            arena,
-            current_id: self.start_id,
-            len: self.len,
-            _marker: PhantomData,
+            items: self.as_slice(arena).iter(),
+        }
+    }
+
+    /// Iterates over mutable references to the items.
+    pub fn iter_mut<'a>(&self, arena: &'a mut Arena) -> ArenaIterMut<'a, T> {
+        ArenaIterMut {
+            items: self.as_mut_slice(arena).iter_mut(),
         }
     }
 }
 
 pub struct ArenaIterReader<'a, T: Copy> {
-    arena: &'a Arena,
-    current_id: u32,
-    len: u32,
-    _marker: PhantomData<T>,
+    items: std::slice::Iter<'a, T>,
 }
 
-impl<'a, T: Copy + 'static> std::iter::Iterator for ArenaIterReader<'a, T> {
-    type Item = &'a mut T;
+impl<'a, T: Copy> std::iter::Iterator for ArenaIterReader<'a, T> {
+    type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.len == 0 {
-            return None;
-        }
-        self.len -= 1;
-        let size = std::mem::size_of::<T>();
-        if size == 0 {
-            return Some(unsafe { &mut *NonNull::<T>::dangling().as_ptr() });
-        }
+        self.items.next()
+    }
 
-        let mut page = (self.current_id as usize) / Arena::BLOCK_SIZE;
-        let mut offset = (self.current_id as usize) % Arena::BLOCK_SIZE;
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.items.size_hint()
+    }
+}
 
-        if offset + size > Arena::BLOCK_SIZE {
-            page += 1;
-            offset = 0;
-            self.current_id = (page * Arena::BLOCK_SIZE) as u32;
-        }
+impl<T: Copy> ExactSizeIterator for ArenaIterReader<'_, T> {}
 
-        let ptr = unsafe {
-            let pages = &*self.arena.ptrs.get();
-            let page_base = *pages.as_ptr().add(page);
-            page_base.add(offset) as *mut T
-        };
+pub struct ArenaIterMut<'a, T: Copy> {
+    items: std::slice::IterMut<'a, T>,
+}
 
-        self.current_id += size as u32;
-        Some(unsafe { &mut *ptr })
+impl<'a, T: Copy> std::iter::Iterator for ArenaIterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.items.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.items.size_hint()
     }
 }
 
+impl<T: Copy> ExactSizeIterator for ArenaIterMut<'_, T> {}
+
 impl Arena {
+    /// The default page size.
     pub const BLOCK_SIZE: usize = 1024 * 1024 * 8;
 
     pub fn new() -> Self {
-        let layout = unsafe { Layout::from_size_align_unchecked(Self::BLOCK_SIZE, ALIGNMENT) };
-        let ptr = unsafe { alloc(layout) };
-        if ptr.is_null() {
-            panic!("OOM");
-        }
-        Self {
+        Self::with_block_size(Self::BLOCK_SIZE)
+    }
+
+    /// Creates an arena with pages of `block_size` bytes.
+    ///
+    /// Small arenas, e.g. for a single expression, can use a small block size
+    /// to avoid reserving 8 MiB up front. `block_size` has to be a multiple of 64.
+    pub fn with_block_size(block_size: usize) -> Self {
+        assert!(
+            block_size > 0 && block_size.is_multiple_of(ALIGNMENT),
+            "the block size of an arena must be a positive multiple of {}",
+            ALIGNMENT
+        );
+        assert!(
+            block_size <= Self::MAX_CAPACITY,
+            "the block size of an arena must not exceed its 4 GiB capacity"
+        );
+        let arena = Self {
+            tag: ArenaTag::new(),
+            block_size,
             index: Cell::new(0),
             page_index: Cell::new(0),
-            ptrs: Box::new(UnsafeCell::new(vec![ptr])),
+            chunk_index: Cell::new(0),
+            ptrs: Box::new(UnsafeCell::new(Vec::new())),
+            chunks: UnsafeCell::new(Vec::new()),
+            bytes_used: Cell::new(0),
+            alignment_waste: Cell::new(0),
+            track_types: false,
+            types: UnsafeCell::new(HashMap::new()),
             states: UnsafeCell::new(Vec::with_capacity(4)),
             iter_buffer: UnsafeCell::new(Vec::with_capacity(1024)),
+        };
+        arena.add_chunk(1);
+        arena
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Makes the arena count its allocations per type for `Arena::stats`.
+    ///
+    /// This costs a hash map lookup per allocation, so it is off by default.
+    pub fn with_type_stats(mut self) -> Self {
+        self.track_types = true;
+        self
+    }
+
+    /// Rewinds the arena, so that the next allocations reuse its pages
+    /// instead of allocating new ones.
+    ///
+    /// Every handle allocated before is invalidated. In debug builds, using
+    /// one of them panics.
+    pub fn reset(&mut self) {
+        assert!(
+            self.states.get_mut().is_empty(),
+            "an arena can not be reset during an iterator allocation"
+        );
+        self.tag = ArenaTag::new();
+        self.index.set(0);
+        self.page_index.set(0);
+        self.chunk_index.set(0);
+        self.bytes_used.set(0);
+        self.alignment_waste.set(0);
+        self.types.get_mut().clear();
+    }
+
+    pub fn stats(&self) -> ArenaStats {
+        let pages = unsafe { (*self.ptrs.get()).len() };
+        let mut types = unsafe { (*self.types.get()).values().cloned().collect::<Vec<_>>() };
+        types.sort_by(|a, b| b.bytes().cmp(&a.bytes()).then(a.name.cmp(b.name)));
+        ArenaStats {
+            bytes_used: self.bytes_used.get(),
+            bytes_reserved: pages * self.block_size,
+            pages,
+            alignment_waste: self.alignment_waste.get(),
+            types,
         }
     }
 
@@ -143,29 +369,34 @@ impl Arena {
         let size = std::mem::size_of::<T>();
         if size == 0 {
             return ArenaBox {
-                id: u32::MAX,
+                id: DANGLING_ID,
+                tag: self.tag,
                 _marker: PhantomData,
             };
         }
 
         let align = std::mem::align_of::<T>();
         let mut start = (self.index.get() + align - 1) & !(align - 1);
+        let mut padding = start - self.index.get();
 
-        if size + start > Self::BLOCK_SIZE {
-            self.grow();
+        if size + start > self.block_size {
+            self.grow(size);
             start = 0;
+            padding = 0;
         }
 
-        let id = (self.page_index.get() * Self::BLOCK_SIZE + start) as u32;
+        let id = (self.page_index.get() * self.block_size + start) as u32;
         unsafe {
             let pages = &*self.ptrs.get();
             let page_ptr = *pages.as_ptr().add(self.page_index.get());
             let ptr = page_ptr.add(start) as *mut T;
             ptr.write(value);
         }
-        self.index.set(start + size);
+        self.bump(start + size);
+        self.record::<T>(padding, 1);
         ArenaBox {
             id,
+            tag: self.tag,
             _marker: PhantomData,
         }
     }
@@ -192,7 +423,7 @@ impl Arena {
             current.zst_count += 1;
         } else {
             let buffer = unsafe { &mut *self.iter_buffer.get() };
-            let ptr = value as *const T as *const u8;
+            let ptr = value as *const T as *const MaybeUninit<u8>;
             let slice = unsafe { std::slice::from_raw_parts(ptr, current.item_size) };
             buffer.extend_from_slice(slice);
         }
@@ -205,8 +436,9 @@ impl Arena {
 
         if state.item_size == 0 {
             return ArenaIter {
-                start_id: u32::MAX,
+                start_id: DANGLING_ID,
                 len: state.zst_count,
+                tag: self.tag,
                 _marker: PhantomData,
             };
         }
@@ -216,37 +448,34 @@ impl Arena {
 
         if count == 0 {
             return ArenaIter {
-                start_id: u32::MAX,
+                start_id: DANGLING_ID,
                 len: 0,
+                tag: self.tag,
                 _marker: PhantomData,
             };
         }
 
         // Arenaへの書き込み
+        // all items go into one chunk, so that they can be viewed as a slice
+        let total = count as usize * state.item_size;
         let mut start_pos = (self.index.get() + state.align - 1) & !(state.align - 1);
-        if start_pos + state.item_size > Self::BLOCK_SIZE {
-            self.grow();
+        let mut padding = start_pos - self.index.get();
+        if start_pos + total > self.block_size {
+            self.grow(total);
             start_pos = 0;
+            padding = 0;
         }
 
-        self.index.set(start_pos);
-
-        let start_id = (self.page_index.get() * Self::BLOCK_SIZE + start_pos) as u32;
-
-        for i in 0..count {
-            if self.index.get() + state.item_size > Self::BLOCK_SIZE {
-                self.grow();
-            }
-            unsafe {
-                let pages = &*self.ptrs.get();
-                let dest = (*pages.as_ptr().add(self.page_index.get())).add(self.index.get());
-                let src = buffer
-                    .as_ptr()
-                    .add(state.buffer_start_offset + (i as usize * state.item_size));
-                dest.copy_from_nonoverlapping(src, state.item_size);
-            }
-            self.index.set(self.index.get() + state.item_size);
+        let start_id = (self.page_index.get() * self.block_size + start_pos) as u32;
+        unsafe {
+            let pages = &*self.ptrs.get();
+            let dest =
+                (*pages.as_ptr().add(self.page_index.get())).add(start_pos) as *mut MaybeUninit<u8>;
+            let src = buffer.as_ptr().add(state.buffer_start_offset);
+            dest.copy_from_nonoverlapping(src, total);
         }
+        self.bump(start_pos + total);
+        self.record::<T>(padding, count as usize);
 
         // バッファをこの階層の開始位置まで巻き戻して再利用可能にする
         buffer.truncate(state.buffer_start_offset);
@@ -254,10 +483,20 @@ impl Arena {
         ArenaIter {
             start_id,
             len: count,
+            tag: self.tag,
             _marker: PhantomData,
         }
     }
 
+    /// Drops the items of the innermost iteration without allocating them,
+    /// like when the list they belong to failed to parse.
+    pub fn abandon_iter_allocation(&self) {
+        let states = unsafe { &mut *self.states.get() };
+        let state = states.pop().expect("No active iteration");
+        let buffer = unsafe { &mut *self.iter_buffer.get() };
+        buffer.truncate(state.buffer_start_offset);
+    }
+
     pub fn alloc_iter<T, I>(&self, value: I) -> ArenaIter<T>
     where
         T: Copy,
@@ -278,24 +517,113 @@ impl Arena {
         self.finish_iter_allocation::<T>()
     }
 
-    fn grow(&self) {
-        let layout = unsafe { Layout::from_size_align_unchecked(Self::BLOCK_SIZE, ALIGNMENT) };
+    /// Returns a pointer to the value with the given id.
+    ///
+    /// Zero-sized values and empty iterators own no memory and resolve to a
+    /// dangling pointer.
+    #[inline]
+    fn resolve<T>(&self, id: u32) -> *mut T {
+        if std::mem::size_of::<T>() == 0 || id == DANGLING_ID {
+            return NonNull::dangling().as_ptr();
+        }
+        let page = (id as usize) / self.block_size;
+        let offset = (id as usize) % self.block_size;
+        unsafe {
+            let pages = &*self.ptrs.get();
+            let page_ptr = *pages.as_ptr().add(page);
+            page_ptr.add(offset) as *mut T
+        }
+    }
+
+    /// The number of bytes handles can address. `DANGLING_ID` is never a valid offset.
+    const MAX_CAPACITY: usize = DANGLING_ID as usize;
+
+    /// Moves to a chunk with room for at least `size` bytes. After a reset,
+    /// the chunks after the current one are reused in order, skipping the
+    /// ones which are too small, so a new chunk is only allocated once none
+    /// of them fits.
+    fn grow(&self, size: usize) {
+        let pages = size.div_ceil(self.block_size).max(1);
+        let chunks = unsafe { &*self.chunks.get() };
+        let next = self.chunk_index.get() + 1;
+        let reusable = chunks
+            .iter()
+            .enumerate()
+            .skip(next)
+            .find(|(_, chunk)| chunk.pages >= pages);
+        match reusable {
+            Some((index, chunk)) => {
+                self.chunk_index.set(index);
+                self.page_index.set(chunk.first_page);
+                self.index.set(0);
+            }
+            None => self.add_chunk(pages),
+        }
+    }
+
+    fn add_chunk(&self, pages: usize) {
+        let ptrs = unsafe { &mut *self.ptrs.get() };
+        let capacity = (ptrs.len() + pages).checked_mul(self.block_size);
+        if capacity.is_none_or(|capacity| capacity > Self::MAX_CAPACITY) {
+            panic!(
+                "arena overflow: {} more bytes do not fit in the 4 GiB an arena can address",
+                pages * self.block_size
+            );
+        }
+
+        let layout = Layout::from_size_align(pages * self.block_size, ALIGNMENT).unwrap();
         let ptr = unsafe { alloc(layout) };
         if ptr.is_null() {
-            panic!("OOM");
+            std::alloc::handle_alloc_error(layout);
         }
-        unsafe { (*self.ptrs.get()).push(ptr) };
-        self.page_index.set(self.page_index.get() + 1);
+        let first_page = ptrs.len();
+        let chunks = unsafe { &mut *self.chunks.get() };
+        chunks.push(Chunk {
+            ptr,
+            first_page,
+            pages,
+        });
+        self.chunk_index.set(chunks.len() - 1);
+        ptrs.extend((0..pages).map(|page| unsafe { ptr.add(page * self.block_size) }));
+        self.page_index.set(first_page);
         self.index.set(0);
     }
+
+    fn record<T>(&self, padding: usize, count: usize) {
+        self.bytes_used
+            .set(self.bytes_used.get() + count * std::mem::size_of::<T>());
+        self.alignment_waste
+            .set(self.alignment_waste.get() + padding);
+        if self.track_types {
+            let types = unsafe { &mut *self.types.get() };
+            let name = std::any::type_name::<T>();
+            types
+                .entry(name)
+                .or_insert(TypeStats {
+                    name,
+                    size: std::mem::size_of::<T>(),
+                    count: 0,
+                })
+                .count += count;
+        }
+    }
+
+    /// Moves the allocation position to `end` bytes after the start of the
+    /// current page, which may lie in a later page of the same chunk.
+    fn bump(&self, end: usize) {
+        let pages = end.saturating_sub(1) / self.block_size;
+        self.page_index.set(self.page_index.get() + pages);
+        self.index.set(end - pages * self.block_size);
+    }
 }
 
 impl Drop for Arena {
     fn drop(&mut self) {
-        for ptr in unsafe { (*self.ptrs.get()).iter() } {
+        for chunk in self.chunks.get_mut().iter() {
             unsafe {
-                let layout = Layout::from_size_align_unchecked(Arena::BLOCK_SIZE, ALIGNMENT);
-                std::alloc::dealloc(*ptr, layout);
+                let layout =
+                    Layout::from_size_align_unchecked(chunk.pages * self.block_size, ALIGNMENT);
+                std::alloc::dealloc(chunk.ptr, layout);
             }
         }
     }
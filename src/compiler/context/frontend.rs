@@ -23,10 +23,12 @@ pub struct CompilerFrontendContext<'ctx, 'src> {
 impl Mergeble for CompilerFrontendContext<'_, '_> {
     /// Merges this context with another `CompilerFrontendContext`.
     ///
-    /// The specific merging logic is currently unimplemented.
-    fn merge(self, _other: Self) -> Self {
-        // TODO: merge ctx
-        unimplemented!()
+    /// The symbol cache of `other` is added to the symbol factory of `self`.
+    /// Arenas are not merged: nodes allocated while parsing `other` stay in
+    /// the arena of `other`.
+    fn merge(self, other: Self) -> Self {
+        self.symbol_factory.absorb(other.symbol_factory);
+        self
     }
 }
 
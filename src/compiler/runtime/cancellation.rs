@@ -0,0 +1,36 @@
+//! # Cancellation
+//!
+//! A language server keeps one `Database` alive while the user is typing.
+//! When an edit arrives, the queries still running for the previous revision
+//! are stale. The server cancels them through a `CancellationToken`, and the
+//! next `Database::fetch` made by any of them unwinds back to
+//! `Database::catch_cancellation`, which turns it into `RuntimeErr::Cancelled`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A handle which cancels the queries running on a `Database`.
+///
+/// Tokens are cheap to clone and can be sent to another thread. A token
+/// belongs to one revision: `Database::new_revision` replaces it with a
+/// fresh one, so cancelling an old token has no effect on the new revision.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// The payload `Database::fetch` unwinds with when its token was cancelled.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Cancelled;
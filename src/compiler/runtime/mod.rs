@@ -1,8 +1,15 @@
+pub mod cancellation;
+pub mod persist;
+pub mod profile;
 pub mod query_sys;
 
 use std::hash::Hash;
 
-pub enum RuntimeErr {}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeErr {
+    /// The revision the query was running in has been cancelled.
+    Cancelled,
+}
 
 pub trait Engine {
     fn fetch<Q: Query>(&self, from: Q::From) -> Result<Q::To, RuntimeErr>;
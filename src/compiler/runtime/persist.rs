@@ -0,0 +1,476 @@
+//! # Persistent Encoding
+//!
+//! This module provides the binary format used by the on-disk query cache.
+//! Values are written field by field in little-endian order with length
+//! prefixes, so the format does not depend on the memory layout of the types
+//! being written. Arena-allocated values are written by value and allocated
+//! again into the arena of the `Decoder` when they are read back.
+//!
+//! `Symbol`s are written as their raw ids. They are only meaningful together
+//! with the symbol table they were interned into, so whoever writes them
+//! should write the `Interner` as well, and read them back through a decoder
+//! created with `Decoder::with_symbols`. An encoder made with
+//! `Encoder::with_symbol_table` numbers the symbols it writes itself instead,
+//! so only the strings of those symbols have to be written next to them.
+
+use std::collections::HashMap;
+
+use crate::compiler::arena::{Arena, ArenaBox, ArenaIter};
+use crate::compiler::span::Span;
+use crate::compiler::symbol::{Symbol, SymbolRemap};
+use crate::utility::hashable_float::HashableFloat;
+
+/// Errors which can occur while reading or writing the query cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheErr {
+    Io(std::io::ErrorKind),
+    NotACacheFile,
+    VersionMismatch,
+    Corrupted,
+    /// An `ArenaBox` or `ArenaIter` was written by an encoder without an arena.
+    MissingArena,
+}
+
+impl From<std::io::Error> for CacheErr {
+    fn from(err: std::io::Error) -> Self {
+        CacheErr::Io(err.kind())
+    }
+}
+
+/// A type which can be written to and read back from the query cache.
+pub trait Persist: Sized {
+    fn encode(&self, encoder: &mut Encoder<'_>);
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr>;
+}
+
+pub struct Encoder<'a> {
+    buf: Vec<u8>,
+    arena: Option<&'a Arena>,
+    /// The symbols written so far, if the encoder numbers them itself.
+    symbols: Option<SymbolTable>,
+    /// The first value which could not be written.
+    error: Option<CacheErr>,
+}
+
+/// The symbols an encoder has written, numbered in the order it wrote them.
+#[derive(Default)]
+struct SymbolTable {
+    ids: HashMap<Symbol, u32>,
+    symbols: Vec<Symbol>,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            arena: None,
+            symbols: None,
+            error: None,
+        }
+    }
+
+    /// Creates an encoder which reads `ArenaBox` and `ArenaIter` values from `arena`.
+    pub fn with_arena(arena: &'a Arena) -> Self {
+        Self {
+            arena: Some(arena),
+            ..Self::new()
+        }
+    }
+
+    /// Makes the encoder write the `n`-th distinct symbol it is given as `n`
+    /// instead of its raw id. See `symbols`.
+    pub fn with_symbol_table(mut self) -> Self {
+        self.symbols = Some(SymbolTable::default());
+        self
+    }
+
+    /// Creates an encoder for a part of the output, which is written with
+    /// `write_nested`. It shares the arena and the symbol table of `self`.
+    pub fn nested(&mut self) -> Encoder<'a> {
+        Encoder {
+            buf: Vec::new(),
+            arena: self.arena,
+            symbols: self.symbols.take(),
+            error: None,
+        }
+    }
+
+    /// Writes the bytes of an encoder made by `nested`, prefixed with their length.
+    pub fn write_nested(&mut self, nested: Encoder<'a>) {
+        self.write_bytes(&nested.buf);
+        self.symbols = nested.symbols;
+        if let Some(err) = nested.error {
+            self.fail(err);
+        }
+    }
+
+    pub fn write_symbol(&mut self, symbol: Symbol) {
+        let id = match &mut self.symbols {
+            Some(table) => *table.ids.entry(symbol).or_insert_with(|| {
+                table.symbols.push(symbol);
+                (table.symbols.len() - 1) as u32
+            }),
+            None => symbol.raw(),
+        };
+        self.write_u32(id);
+    }
+
+    /// The symbols written so far by an encoder made with `with_symbol_table`,
+    /// in the order of the numbers they were written as.
+    pub fn symbols(&self) -> &[Symbol] {
+        self.symbols
+            .as_ref()
+            .map_or(&[], |table| table.symbols.as_slice())
+    }
+
+    /// Records that a value could not be written. Only the first error is kept.
+    pub fn fail(&mut self, err: CacheErr) {
+        self.error.get_or_insert(err);
+    }
+
+    /// The first error of a value which could not be written, if there was one.
+    pub fn error(&self) -> Option<CacheErr> {
+        self.error
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn write_str(&mut self, string: &str) {
+        self.write_bytes(string.as_bytes());
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+impl Default for Encoder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+    cursor: usize,
+    arena: Option<&'a Arena>,
+    symbols: Option<&'a SymbolRemap>,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            cursor: 0,
+            arena: None,
+            symbols: None,
+        }
+    }
+
+    /// Creates a decoder which allocates `ArenaBox` and `ArenaIter` values into `arena`.
+    pub fn with_arena(bytes: &'a [u8], arena: &'a Arena) -> Self {
+        Self {
+            bytes,
+            cursor: 0,
+            arena: Some(arena),
+            symbols: None,
+        }
+    }
+
+    /// Makes the decoder translate every `Symbol` it reads through `symbols`.
+    pub fn with_symbols(mut self, symbols: &'a SymbolRemap) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CacheErr> {
+        let end = self.cursor.checked_add(len).ok_or(CacheErr::Corrupted)?;
+        let taken = self
+            .bytes
+            .get(self.cursor..end)
+            .ok_or(CacheErr::Corrupted)?;
+        self.cursor = end;
+        Ok(taken)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, CacheErr> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, CacheErr> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, CacheErr> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], CacheErr> {
+        let len = self.read_u32()? as usize;
+        self.take(len)
+    }
+
+    pub fn read_str(&mut self) -> Result<&'a str, CacheErr> {
+        std::str::from_utf8(self.read_bytes()?).map_err(|_| CacheErr::Corrupted)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cursor == self.bytes.len()
+    }
+}
+
+macro_rules! impl_persist_for_int {
+    ($($ty:ty => $write:ident, $read:ident, $wide:ty;)*) => {
+        $(
+            impl Persist for $ty {
+                fn encode(&self, encoder: &mut Encoder<'_>) {
+                    encoder.$write(*self as $wide);
+                }
+
+                fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+                    <$ty>::try_from(decoder.$read()?).map_err(|_| CacheErr::Corrupted)
+                }
+            }
+        )*
+    };
+}
+
+impl_persist_for_int! {
+    u8 => write_u8, read_u8, u8;
+    u16 => write_u32, read_u32, u32;
+    u32 => write_u32, read_u32, u32;
+    u64 => write_u64, read_u64, u64;
+    usize => write_u64, read_u64, u64;
+}
+
+impl Persist for i32 {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_u32(*self as u32);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        Ok(decoder.read_u32()? as i32)
+    }
+}
+
+impl Persist for i64 {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_u64(*self as u64);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        Ok(decoder.read_u64()? as i64)
+    }
+}
+
+impl Persist for bool {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_u8(*self as u8);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        match decoder.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(CacheErr::Corrupted),
+        }
+    }
+}
+
+impl Persist for char {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_u32(*self as u32);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        char::from_u32(decoder.read_u32()?).ok_or(CacheErr::Corrupted)
+    }
+}
+
+impl Persist for () {
+    fn encode(&self, _: &mut Encoder<'_>) {}
+
+    fn decode(_: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        Ok(())
+    }
+}
+
+impl Persist for String {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_str(self);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        decoder.read_str().map(str::to_string)
+    }
+}
+
+impl<T: Persist> Persist for Vec<T> {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_u32(self.len() as u32);
+        for item in self {
+            item.encode(encoder);
+        }
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        let len = decoder.read_u32()?;
+        (0..len).map(|_| T::decode(decoder)).collect()
+    }
+}
+
+impl<T: Persist> Persist for Option<T> {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        match self {
+            Some(value) => {
+                encoder.write_u8(1);
+                value.encode(encoder);
+            }
+            None => encoder.write_u8(0),
+        }
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        match decoder.read_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(decoder)?)),
+            _ => Err(CacheErr::Corrupted),
+        }
+    }
+}
+
+impl<T: Persist, E: Persist> Persist for Result<T, E> {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        match self {
+            Ok(value) => {
+                encoder.write_u8(0);
+                value.encode(encoder);
+            }
+            Err(err) => {
+                encoder.write_u8(1);
+                err.encode(encoder);
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        match decoder.read_u8()? {
+            0 => Ok(Ok(T::decode(decoder)?)),
+            1 => Ok(Err(E::decode(decoder)?)),
+            _ => Err(CacheErr::Corrupted),
+        }
+    }
+}
+
+impl<A: Persist, B: Persist> Persist for (A, B) {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        self.0.encode(encoder);
+        self.1.encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        Ok((A::decode(decoder)?, B::decode(decoder)?))
+    }
+}
+
+impl Persist for Span {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_u32(self.begin);
+        encoder.write_u32(self.end);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        let begin = decoder.read_u32()?;
+        let end = decoder.read_u32()?;
+        Ok(Span { begin, end })
+    }
+}
+
+impl Persist for Symbol {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_symbol(*self);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        let symbol = Symbol::from_raw(decoder.read_u32()?);
+        match decoder.symbols {
+            Some(symbols) => symbols.try_get(symbol).ok_or(CacheErr::Corrupted),
+            None => Ok(symbol),
+        }
+    }
+}
+
+impl Persist for HashableFloat<f32> {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_u32(self.get().to_bits());
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        Ok(HashableFloat::new(f32::from_bits(decoder.read_u32()?)))
+    }
+}
+
+impl Persist for HashableFloat<f64> {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_u64(self.get().to_bits());
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        Ok(HashableFloat::new(f64::from_bits(decoder.read_u64()?)))
+    }
+}
+
+impl<T: Copy + Persist + 'static> Persist for ArenaBox<T> {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        let Some(arena) = encoder.arena else {
+            encoder.fail(CacheErr::MissingArena);
+            return;
+        };
+        self.get(arena).encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        let arena = decoder.arena.ok_or(CacheErr::Corrupted)?;
+        let value = T::decode(decoder)?;
+        Ok(arena.alloc(value))
+    }
+}
+
+impl<T: Copy + Persist + 'static> Persist for ArenaIter<T> {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        let Some(arena) = encoder.arena else {
+            encoder.fail(CacheErr::MissingArena);
+            return;
+        };
+        self.as_slice(arena).to_vec().encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        let arena = decoder.arena.ok_or(CacheErr::Corrupted)?;
+        let items = Vec::<T>::decode(decoder)?;
+        Ok(arena.alloc_iter(items.into_iter()))
+    }
+}
@@ -0,0 +1,190 @@
+//! # Query Profiling
+//!
+//! A `Profiler` attached to a `Database` records one `QueryEvent` for every
+//! call to `Database::fetch`. Times are wall times and include the time spent
+//! in the queries fetched from inside the query.
+//!
+//! The events can be written as a Chrome trace, which can be opened in
+//! `chrome://tracing` or Perfetto, or summarized as a table of the slowest
+//! queries. The dependency graph itself is written by `Database::write_query_graph`.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Mutex;
+use std::thread::ThreadId;
+use std::time::{Duration, Instant};
+
+use indicatif::ProgressBar;
+
+/// How `Database::fetch` produced its result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchOutcome {
+    /// The result was already verified in the current revision.
+    Hit,
+    /// The result was cached in an older revision and none of its dependencies changed.
+    Verified,
+    /// The query was run.
+    Recomputed,
+}
+
+impl FetchOutcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            FetchOutcome::Hit => "hit",
+            FetchOutcome::Verified => "verified",
+            FetchOutcome::Recomputed => "recomputed",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryEvent {
+    pub name: &'static str,
+    /// The hash of the input of the query.
+    pub from: u64,
+    /// A small number identifying the thread the query ran on.
+    pub thread: u32,
+    /// The time the fetch started at, relative to the creation of the profiler.
+    pub start: Duration,
+    pub duration: Duration,
+    pub outcome: FetchOutcome,
+}
+
+pub struct Profiler {
+    started: Instant,
+    events: Mutex<Vec<QueryEvent>>,
+    threads: Mutex<HashMap<ThreadId, u32>>,
+    progress: Option<ProgressBar>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            events: Mutex::new(Vec::new()),
+            threads: Mutex::new(HashMap::new()),
+            progress: None,
+        }
+    }
+
+    /// Ticks `progress` with the name of every finished query.
+    pub fn with_progress(mut self, progress: ProgressBar) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub(crate) fn record(
+        &self,
+        name: &'static str,
+        from: u64,
+        started: Instant,
+        outcome: FetchOutcome,
+    ) {
+        let thread = {
+            let mut threads = self.threads.lock().unwrap();
+            let next = threads.len() as u32;
+            *threads.entry(std::thread::current().id()).or_insert(next)
+        };
+        self.events.lock().unwrap().push(QueryEvent {
+            name,
+            from,
+            thread,
+            start: started.duration_since(self.started),
+            duration: started.elapsed(),
+            outcome,
+        });
+        if let Some(progress) = &self.progress {
+            progress.set_message(name);
+            progress.inc(1);
+        }
+    }
+
+    /// Clears the progress bar, if there is one.
+    pub fn finish(&self) {
+        if let Some(progress) = &self.progress {
+            progress.finish_and_clear();
+        }
+    }
+
+    /// Returns every recorded event in the order the fetches started in.
+    pub fn events(&self) -> Vec<QueryEvent> {
+        let mut events = self.events.lock().unwrap().clone();
+        events.sort_by_key(|event| event.start);
+        events
+    }
+
+    /// Returns the `n` slowest fetches, slowest first.
+    pub fn slowest(&self, n: usize) -> Vec<QueryEvent> {
+        let mut events = self.events();
+        events.sort_by_key(|event| std::cmp::Reverse(event.duration));
+        events.truncate(n);
+        events
+    }
+
+    /// Writes the events in the Chrome trace event format.
+    pub fn write_chrome_trace(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writeln!(writer, "{{\"traceEvents\":[")?;
+        let events = self.events();
+        for (i, event) in events.iter().enumerate() {
+            let separator = if i + 1 == events.len() { "" } else { "," };
+            writeln!(
+                writer,
+                "{{\"name\":\"{}\",\"cat\":\"query\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{},\"args\":{{\"from\":\"{:016x}\",\"outcome\":\"{}\"}}}}{}",
+                escape_json(event.name),
+                event.start.as_micros(),
+                event.duration.as_micros(),
+                event.thread,
+                event.from,
+                event.outcome.as_str(),
+                separator,
+            )?;
+        }
+        writeln!(writer, "]}}")
+    }
+
+    /// Writes a table of the `n` slowest fetches.
+    pub fn write_slowest_table(&self, n: usize, writer: &mut impl Write) -> std::io::Result<()> {
+        let slowest = self.slowest(n);
+        let name_width = slowest
+            .iter()
+            .map(|event| event.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("query".len());
+        writeln!(
+            writer,
+            "{:>12}  {:<10}  {:<name_width$}  input",
+            "time", "outcome", "query",
+        )?;
+        for event in slowest {
+            writeln!(
+                writer,
+                "{:>12}  {:<10}  {:<name_width$}  {:016x}",
+                format!("{:.3?}", event.duration),
+                event.outcome.as_str(),
+                event.name,
+                event.from,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) fn escape_json(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
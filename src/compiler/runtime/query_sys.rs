@@ -1,23 +1,57 @@
 use std::any::{Any, TypeId};
-use std::collections::{hash_map::DefaultHasher, HashMap};
+use std::collections::{hash_map::DefaultHasher, BTreeMap, HashMap};
 use std::hash::{Hash, Hasher};
+use std::io::Write;
 use std::marker::PhantomData;
-use std::sync::{Arc, RwLock};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::ThreadId;
+use std::time::Instant;
 
-use crate::compiler::arena::ArenaBox;
+use crate::compiler::arena::{Arena, ArenaBox};
+use crate::compiler::context::frontend::CompilerFrontendContext;
+use crate::compiler::runtime::cancellation::{CancellationToken, Cancelled};
+use crate::compiler::runtime::persist::{CacheErr, Decoder, Encoder, Persist};
+use crate::compiler::runtime::profile::{FetchOutcome, Profiler};
+use crate::compiler::runtime::RuntimeErr;
+use crate::compiler::symbol::{Interner, SymbolFactory};
 use crate::diagnostic::Diagnostic;
+use crate::utility::vfs::SourceFiles;
 
 pub trait Query: 'static {
-    type From: Hash + Clone;
-    type To: Hash + Clone;
+    type From: Hash + Clone + Send + Sync;
+    type To: Hash + Clone + Send + Sync;
+
+    /// Whether this query reads the world outside of the database, e.g. a file.
+    ///
+    /// Inputs are run again in every revision. Queries depending on an input
+    /// are reused as long as the input produces an output with the same hash.
+    const IS_INPUT: bool = false;
+
     fn run(db: &Database, src: Self::From) -> Self::To;
 }
 
+/// A query whose results are written to the on-disk cache by `Database::save`.
+pub trait PersistentQuery: Query<From: Persist, To: Persist> {
+    /// The name identifying this query in the cache across compiler runs.
+    const NAME: &'static str;
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum QuerySysFetchErr {
     DependentCycleDetected,
 }
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+/// The directory the compiler keeps its query cache in, relative to the project root.
+pub const DEFAULT_CACHE_DIR: &str = "target/hydent-cache";
+
+const CACHE_FILE_NAME: &str = "queries.bin";
+const CACHE_MAGIC: &[u8] = b"HYDQ";
+/// Bump this whenever the layout of the cache file changes.
+const CACHE_FORMAT_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord)]
 struct Revision(u32);
 impl Revision {
     fn new() -> Self {
@@ -32,12 +66,16 @@ impl Revision {
 struct HashedQueryFrom(u64);
 impl HashedQueryFrom {
     fn new<T: Hash>(t: &T) -> Self {
-        let mut hasher = DefaultHasher::new();
-        t.hash(&mut hasher);
-        HashedQueryFrom(hasher.finish())
+        HashedQueryFrom(hash_of(t))
     }
 }
 
+fn hash_of<T: Hash>(t: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    t.hash(&mut hasher);
+    hasher.finish()
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 struct QueryId {
     query_type: TypeId,
@@ -54,83 +92,924 @@ impl QueryId {
 
 type Storage<T> = HashMap<TypeId, HashMap<HashedQueryFrom, T>>;
 
+/// A query which is being executed on some thread, with the queries it
+/// fetched so far.
+struct ActiveQuery {
+    id: QueryId,
+    dependencies: Vec<QueryId>,
+}
+
+/// Pops the frame of a query whose verification or `run` unwinds, e.g.
+/// because the query was cancelled.
+struct UnwindGuard<'db> {
+    db: &'db Database,
+}
+
+impl Drop for UnwindGuard<'_> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.db.with_stack(|stack| stack.pop());
+        }
+    }
+}
+
+/// The least recently used order of the memoized outputs of one query type.
+struct Lru {
+    capacity: usize,
+    clock: u64,
+    by_tick: BTreeMap<u64, HashedQueryFrom>,
+    ticks: HashMap<HashedQueryFrom, u64>,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            clock: 0,
+            by_tick: BTreeMap::new(),
+            ticks: HashMap::new(),
+        }
+    }
+
+    /// Marks `from` as the most recently used and returns the outputs which
+    /// do not fit in the capacity anymore.
+    fn touch(&mut self, from: HashedQueryFrom) -> Vec<HashedQueryFrom> {
+        if let Some(old_tick) = self.ticks.insert(from, self.clock) {
+            self.by_tick.remove(&old_tick);
+        }
+        self.by_tick.insert(self.clock, from);
+        self.clock += 1;
+
+        let mut evicted = Vec::new();
+        while self.ticks.len() > self.capacity {
+            let (_, oldest) = self.by_tick.pop_first().unwrap();
+            self.ticks.remove(&oldest);
+            evicted.push(oldest);
+        }
+        evicted
+    }
+}
+
+/// Type-erased operations on every query of one type.
+#[derive(Clone, Copy)]
+struct QueryType {
+    name: &'static str,
+    /// Brings a query up to date and returns the revision its output last changed at.
+    refresh: fn(&Database, QueryId) -> Option<Revision>,
+    persist: Option<PersistFns>,
+}
+
+#[derive(Clone, Copy)]
+struct PersistFns {
+    name: &'static str,
+    save: fn(&Database, &HashMap<TypeId, &'static str>, &mut Encoder<'_>),
+    load: fn(&mut Decoder<'_>) -> Result<Vec<LoadedQuery>, CacheErr>,
+}
+
+/// A query read back from the cache whose dependency edges still refer to the
+/// hashes of the run which wrote them.
+struct LoadedQuery {
+    name: &'static str,
+    id: QueryId,
+    saved_from: HashedQueryFrom,
+    from: Arc<dyn Any + Send + Sync>,
+    output: Arc<dyn Any + Send + Sync>,
+    output_hash: u64,
+    changed_at: Revision,
+    dependencies: Vec<(String, HashedQueryFrom)>,
+}
+
+/// The memoizing query database.
+///
+/// Every method takes `&self`, so one `Database` can be shared by the worker
+/// threads spawned from [`Database::fetch_parallel`] and
+/// [`Database::fetch_parallel_frontend`]. Each thread keeps its own
+/// stack of active queries, which is used for cycle detection and for
+/// recording dependency edges. A worker thread starts with the stack of the
+/// thread which spawned it, so the queries it fetches are dependencies of
+/// the query which spawned it.
 pub struct Database {
     current_rivision: Revision,
-    queries_outputs: Storage<Box<dyn Any /* Query::To */>>,
-    queries_metadata: Storage<QueryMetadata>,
-    stack: Vec<QueryId>,
+    queries_outputs: RwLock<Storage<Arc<dyn Any + Send + Sync /* Query::To */>>>,
+    queries_metadata: RwLock<Storage<QueryMetadata>>,
+    query_types: RwLock<HashMap<TypeId, QueryType>>,
+    stacks: Mutex<HashMap<ThreadId, Vec<ActiveQuery>>>,
+    profiler: Option<Profiler>,
+    cancellation: CancellationToken,
+    lrus: RwLock<HashMap<TypeId, Mutex<Lru>>>,
+    source_files: SourceFiles,
 }
 
 pub struct QueryMetadata {
     verified_at: Revision,
     changed_at: Revision,
     dependencies: Vec<QueryId>,
+    output_hash: u64,
+    from: Arc<dyn Any + Send + Sync /* Query::From */>,
 }
 
 impl Database {
-    fn fetch<Q: Query>(&mut self, from: Q::From) -> Result<Q::To, QuerySysFetchErr> {
+    pub fn new() -> Self {
+        Self {
+            current_rivision: Revision::new(),
+            queries_outputs: RwLock::new(HashMap::new()),
+            queries_metadata: RwLock::new(HashMap::new()),
+            query_types: RwLock::new(HashMap::new()),
+            stacks: Mutex::new(HashMap::new()),
+            profiler: None,
+            cancellation: CancellationToken::new(),
+            lrus: RwLock::new(HashMap::new()),
+            source_files: SourceFiles::default(),
+        }
+    }
+
+    /// Makes `ReadFileQuery` read through `source_files` instead of the disk.
+    pub fn set_source_files(&mut self, source_files: SourceFiles) {
+        self.source_files = source_files;
+    }
+
+    pub fn source_files(&self) -> &SourceFiles {
+        &self.source_files
+    }
+
+    /// Starts a new revision, in which every input query is run again.
+    ///
+    /// The cancellation token of the previous revision is replaced, so the
+    /// queries of the new revision are not cancelled.
+    pub fn new_revision(&mut self) {
+        self.current_rivision.increment();
+        self.cancellation = CancellationToken::new();
+    }
+
+    /// Returns a token which cancels every query running in the current revision.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// Runs `f`, catching the unwinding of a cancelled `fetch`.
+    ///
+    /// Panics which are not caused by cancellation are resumed.
+    pub fn catch_cancellation<T>(&self, f: impl FnOnce(&Self) -> T) -> Result<T, RuntimeErr> {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(self))).map_err(|payload| {
+            if payload.is::<Cancelled>() {
+                RuntimeErr::Cancelled
+            } else {
+                std::panic::resume_unwind(payload)
+            }
+        })
+    }
+
+    /// Keeps at most `capacity` outputs of `Q` in memory.
+    ///
+    /// The least recently used outputs are evicted first. Their metadata is
+    /// kept, so queries depending on an evicted output can still be verified,
+    /// and the output is recomputed the next time it is fetched.
+    pub fn set_capacity<Q: Query>(&mut self, capacity: usize) {
+        let mut lru = Lru::new(capacity);
+        let outputs = self.queries_outputs.get_mut().unwrap();
+        if let Some(found) = outputs.get_mut(&TypeId::of::<Q>()) {
+            let mut existing = found.keys().copied().collect::<Vec<_>>();
+            existing.sort_by_key(|from| from.0);
+            for from in existing {
+                for evicted in lru.touch(from) {
+                    found.remove(&evicted);
+                }
+            }
+        }
+        self.lrus
+            .get_mut()
+            .unwrap()
+            .insert(TypeId::of::<Q>(), Mutex::new(lru));
+    }
+
+    /// Records every fetch from now on into `profiler`.
+    pub fn enable_profiling(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Returns the output of `Q` for `from`, reusing the memoized output if
+    /// none of the queries it depends on has changed.
+    ///
+    /// If the cancellation token of this revision was cancelled, this unwinds
+    /// instead of returning. See [`Database::catch_cancellation`].
+    pub fn fetch<Q: Query>(&self, from: Q::From) -> Result<Q::To, QuerySysFetchErr> {
+        if self.cancellation.is_cancelled() {
+            std::panic::resume_unwind(Box::new(Cancelled));
+        }
+        let started = Instant::now();
+        self.register::<Q>();
+
         // 1. check there is no dependent cycle
         let current_query = QueryId::new::<Q>(HashedQueryFrom::new(&from));
-        if self.stack.contains(&current_query) {
+        if self.with_stack(|stack| stack.iter().any(|active| active.id == current_query)) {
             return Err(QuerySysFetchErr::DependentCycleDetected);
         }
 
-        // 2. add self to stack
-        self.stack.push(current_query);
+        // 2. save that parent query depends on this query
+        self.with_stack(|stack| {
+            if let Some(parent_query) = stack.last_mut() {
+                parent_query.dependencies.push(current_query);
+            } else {
+                // This query is root
+            }
+        });
 
-        // 3. save that parent query depends on this query
-        if let Some(parent_query) = self.stack.last() {
-            self.find_query_metadata_mut(*parent_query)
-                .unwrap()
+        // 3. add self to stack
+        self.with_stack(|stack| {
+            stack.push(ActiveQuery {
+                id: current_query,
+                dependencies: Vec::new(),
+            })
+        });
+
+        let unwind_guard = UnwindGuard { db: self };
+
+        // 4. check if there is a verified cached result
+        let cached = self
+            .find_query_output::<Q>(current_query)
+            .and_then(|output| Some((output, self.verify_query::<Q>(current_query)?)));
+
+        let (to, outcome) = match cached {
+            Some((output, outcome)) => {
+                self.with_stack(|stack| stack.pop());
+                self.touch_lru(current_query);
+                (output, outcome)
+            }
+            None => {
+                // no usable cached result, run the query
+                self.with_stack(|stack| stack.last_mut().unwrap().dependencies.clear());
+                let to = Q::run(self, from.clone());
+                let finished = self.with_stack(|stack| stack.pop()).unwrap();
+                self.store_query_output::<Q>(
+                    current_query,
+                    from,
+                    to.clone(),
+                    finished.dependencies,
+                );
+                self.touch_lru(current_query);
+                (to, FetchOutcome::Recomputed)
+            }
+        };
+        drop(unwind_guard);
+
+        if let Some(profiler) = &self.profiler {
+            profiler.record(
+                std::any::type_name::<Q>(),
+                current_query.from.0,
+                started,
+                outcome,
+            );
+        }
+        Ok(to)
+    }
+
+    /// Fetches `Q` for every input on a pool of scoped threads.
+    ///
+    /// Inputs are handed out to the workers one at a time, so the order in which
+    /// they are computed depends on scheduling. The returned vector, the
+    /// memoized outputs and the recorded dependency edges do not: the `n`-th
+    /// result always belongs to the `n`-th input.
+    pub fn fetch_parallel<Q: Query>(
+        &self,
+        froms: Vec<Q::From>,
+    ) -> Vec<Result<Q::To, QuerySysFetchErr>> {
+        let workers = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(froms.len());
+        if workers <= 1 {
+            return froms
+                .into_iter()
+                .map(|from| self.fetch::<Q>(from))
+                .collect();
+        }
+
+        let next = AtomicUsize::new(0);
+        let active = self.active_queries();
+        let mut results = std::thread::scope(|scope| {
+            let handles = (0..workers)
+                .map(|_| {
+                    scope.spawn(|| {
+                        self.within(&active, || {
+                            let mut done = Vec::new();
+                            loop {
+                                let index = next.fetch_add(1, Ordering::Relaxed);
+                                let Some(from) = froms.get(index) else {
+                                    break done;
+                                };
+                                done.push((index, self.fetch::<Q>(from.clone())));
+                            }
+                        })
+                        .0
+                    })
+                })
+                .collect::<Vec<_>>();
+            join_all(handles).into_iter().flatten().collect::<Vec<_>>()
+        });
+        results.sort_by_key(|(index, _)| *index);
+        // the workers fetched the inputs in any order, so the edges are added
+        // in the order of the inputs
+        self.add_dependencies(
+            froms
+                .iter()
+                .map(|from| QueryId::new::<Q>(HashedQueryFrom::new(from))),
+        );
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    /// Runs `run` for every source on at most `workers` scoped threads.
+    ///
+    /// Every thread has an `Arena` and an `Interner` of its own, and gives `run`
+    /// a `CompilerFrontendContext` over them. The outputs are moved into the
+    /// arena of `context` and their symbols into the interner of its symbol
+    /// factory, which absorbs the interners of the threads in the order of the
+    /// sources. So the outputs don't depend on the number of threads or on
+    /// scheduling, down to the ids of their symbols.
+    ///
+    /// The queries `run` fetches are dependencies of the query calling this.
+    pub fn fetch_parallel_frontend<T: Persist>(
+        &self,
+        context: CompilerFrontendContext<'_, '_>,
+        sources: &[&str],
+        workers: usize,
+        run: impl Fn(&Database, CompilerFrontendContext<'_, '_>) -> T + Sync,
+    ) -> Vec<T> {
+        let chunk = sources.len().div_ceil(workers.max(1)).max(1);
+        let active = self.active_queries();
+        let run = &run;
+        let active = &active;
+        let encoded = std::thread::scope(|scope| {
+            let handles = sources
+                .chunks(chunk)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        self.within(active, || {
+                            let interner = Interner::new();
+                            // the arena stays on this thread, the outputs
+                            // leave it encoded
+                            let arena = Arena::new();
+                            let mut encoder = Encoder::with_arena(&arena);
+                            for source in chunk {
+                                let mut symbols = SymbolFactory::with_interner(source, &interner);
+                                let context =
+                                    CompilerFrontendContext::new(source, &arena, &mut symbols);
+                                run(self, context).encode(&mut encoder);
+                            }
+                            (chunk.len(), interner, encoder.into_bytes())
+                        })
+                    })
+                })
+                .collect::<Vec<_>>();
+            join_all(handles)
+        });
+
+        let interner = context.symbol_factory.interner();
+        let mut outputs = Vec::with_capacity(sources.len());
+        for ((len, worker_interner, bytes), dependencies) in encoded {
+            self.add_dependencies(dependencies);
+            let symbols = interner.absorb(&worker_interner);
+            let mut decoder = Decoder::with_arena(&bytes, context.ast_arena).with_symbols(&symbols);
+            for _ in 0..len {
+                outputs
+                    .push(T::decode(&mut decoder).expect("a worker output is decoded as written"));
+            }
+        }
+        outputs
+    }
+
+    /// The queries running on this thread, the outermost first.
+    fn active_queries(&self) -> Vec<QueryId> {
+        self.with_stack(|stack| stack.iter().map(|active| active.id).collect())
+    }
+
+    /// Runs `f` on a worker thread as if it ran inside the queries `active`
+    /// of the thread which spawned it.
+    ///
+    /// # Returns
+    ///
+    /// What `f` returned and the queries it fetched directly, which are
+    /// dependencies of the innermost query of `active`.
+    fn within<R>(&self, active: &[QueryId], f: impl FnOnce() -> R) -> (R, Vec<QueryId>) {
+        /// Removes the frames of the thread, even if `f` unwinds.
+        struct Frames<'db> {
+            db: &'db Database,
+        }
+        impl Drop for Frames<'_> {
+            fn drop(&mut self) {
+                self.db
+                    .stacks
+                    .lock()
+                    .unwrap()
+                    .remove(&std::thread::current().id());
+            }
+        }
+
+        self.with_stack(|stack| {
+            stack.extend(active.iter().map(|id| ActiveQuery {
+                id: *id,
+                dependencies: Vec::new(),
+            }))
+        });
+        let frames = Frames { db: self };
+        let result = f();
+        let dependencies = self.with_stack(|stack| {
+            stack
+                .last_mut()
+                .map(|parent| std::mem::take(&mut parent.dependencies))
+                .unwrap_or_default()
+        });
+        drop(frames);
+        (result, dependencies)
+    }
+
+    /// Records `dependencies` as dependencies of the query running on this
+    /// thread, if there is one.
+    fn add_dependencies(&self, dependencies: impl IntoIterator<Item = QueryId>) {
+        self.with_stack(|stack| {
+            if let Some(parent_query) = stack.last_mut() {
+                parent_query.dependencies.extend(dependencies);
+            }
+        });
+    }
+
+    /// Makes `Q` part of the on-disk cache written by `save` and read by `load`.
+    ///
+    /// Every persistent query has to be registered before `load` is called,
+    /// otherwise its cached results are skipped.
+    pub fn register_persistent<Q: PersistentQuery>(&self) {
+        self.query_types.write().unwrap().insert(
+            TypeId::of::<Q>(),
+            QueryType {
+                name: std::any::type_name::<Q>(),
+                refresh: refresh_query::<Q>,
+                persist: Some(PersistFns {
+                    name: Q::NAME,
+                    save: save_queries::<Q>,
+                    load: load_queries::<Q>,
+                }),
+            },
+        );
+    }
+
+    /// Writes the results of every persistent query into `dir`.
+    ///
+    /// Results depending on a query which is not persistent are left out,
+    /// because they could not be verified by the next run. Outputs holding
+    /// an `ArenaBox` or `ArenaIter` need `save_with_arena`, without it this
+    /// fails with `CacheErr::MissingArena`.
+    pub fn save(&self, dir: &Path) -> Result<(), CacheErr> {
+        self.save_sections(dir, Encoder::new())
+    }
+
+    /// Writes the results of every persistent query into `dir` like `save`,
+    /// reading the values their `ArenaBox`es and `ArenaIter`s point to from
+    /// `arena`.
+    pub fn save_with_arena(&self, dir: &Path, arena: &Arena) -> Result<(), CacheErr> {
+        self.save_sections(dir, Encoder::with_arena(arena))
+    }
+
+    fn save_sections(&self, dir: &Path, sections: Encoder<'_>) -> Result<(), CacheErr> {
+        let query_types = self.query_types.read().unwrap().clone();
+        let names = query_types
+            .iter()
+            .filter_map(|(type_id, query_type)| Some((*type_id, query_type.persist?.name)))
+            .collect::<HashMap<_, _>>();
+        let mut persistent = query_types
+            .values()
+            .filter_map(|query_type| query_type.persist)
+            .collect::<Vec<_>>();
+        persistent.sort_by_key(|fns| fns.name);
+
+        let mut sections = sections.with_symbol_table();
+        sections.write_u32(persistent.len() as u32);
+        for fns in persistent {
+            let mut section = sections.nested();
+            (fns.save)(self, &names, &mut section);
+            sections.write_str(fns.name);
+            sections.write_nested(section);
+        }
+        if let Some(err) = sections.error() {
+            return Err(err);
+        }
+
+        let mut encoder = Encoder::new();
+        encoder.write_bytes(CACHE_MAGIC);
+        encoder.write_u32(CACHE_FORMAT_VERSION);
+        encoder.write_str(env!("CARGO_PKG_VERSION"));
+        encoder.write_u32(self.current_rivision.0);
+        // only the symbols the outputs reach are written, so the strings of
+        // earlier builds don't pile up in the cache
+        encoder.write_u32(sections.symbols().len() as u32);
+        for symbol in sections.symbols() {
+            encoder.write_str(Interner::global().get(*symbol));
+        }
+        encoder.write_bytes(&sections.into_bytes());
+
+        std::fs::create_dir_all(dir)?;
+        let temporary = dir.join(format!("{}.tmp", CACHE_FILE_NAME));
+        std::fs::write(&temporary, encoder.into_bytes())?;
+        std::fs::rename(temporary, dir.join(CACHE_FILE_NAME))?;
+        Ok(())
+    }
+
+    /// Reads the results written by `save` from `dir`.
+    ///
+    /// A missing cache is not an error. The loaded results belong to the
+    /// previous revision, so each of them is verified against its inputs the
+    /// first time it is fetched.
+    pub fn load(&mut self, dir: &Path) -> Result<(), CacheErr> {
+        self.load_sections(dir, None)
+    }
+
+    /// Reads the results written by `save_with_arena` from `dir` like `load`,
+    /// allocating the values of their `ArenaBox`es and `ArenaIter`s into `arena`.
+    pub fn load_with_arena(&mut self, dir: &Path, arena: &Arena) -> Result<(), CacheErr> {
+        self.load_sections(dir, Some(arena))
+    }
+
+    fn load_sections(&mut self, dir: &Path, arena: Option<&Arena>) -> Result<(), CacheErr> {
+        let bytes = match std::fs::read(dir.join(CACHE_FILE_NAME)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut decoder = Decoder::new(&bytes);
+        if decoder.read_bytes().ok() != Some(CACHE_MAGIC) {
+            return Err(CacheErr::NotACacheFile);
+        }
+        if decoder.read_u32()? != CACHE_FORMAT_VERSION
+            || decoder.read_str()? != env!("CARGO_PKG_VERSION")
+        {
+            return Err(CacheErr::VersionMismatch);
+        }
+        let saved_revision = Revision(decoder.read_u32()?);
+        let symbols = Interner::global().decode_into(&mut decoder)?;
+        let sections = decoder.read_bytes()?;
+        if !decoder.is_finished() {
+            return Err(CacheErr::Corrupted);
+        }
+        let mut decoder = Decoder::new(sections);
+
+        let loaders = self
+            .query_types
+            .read()
+            .unwrap()
+            .values()
+            .filter_map(|query_type| query_type.persist)
+            .map(|fns| (fns.name, fns))
+            .collect::<HashMap<_, _>>();
+        let mut loaded = Vec::new();
+        for _ in 0..decoder.read_u32()? {
+            let name = decoder.read_str()?;
+            let section = decoder.read_bytes()?;
+            if let Some(fns) = loaders.get(name) {
+                let section = match arena {
+                    Some(arena) => Decoder::with_arena(section, arena),
+                    None => Decoder::new(section),
+                };
+                loaded.extend((fns.load)(&mut section.with_symbols(&symbols))?);
+            }
+        }
+        if !decoder.is_finished() {
+            return Err(CacheErr::Corrupted);
+        }
+
+        // dependency edges were saved with the hashes of the previous run
+        let ids = loaded
+            .iter()
+            .map(|query| ((query.name, query.saved_from), query.id))
+            .collect::<HashMap<_, _>>();
+        let mut outputs = self.queries_outputs.write().unwrap();
+        let mut metadata = self.queries_metadata.write().unwrap();
+        let mut inserted = Vec::new();
+        for query in loaded {
+            let Some(dependencies) = query
                 .dependencies
-                .push(current_query);
-        } else {
-            // This query is root
+                .iter()
+                .map(|(name, from)| ids.get(&(name.as_str(), *from)).copied())
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            outputs
+                .entry(query.id.query_type)
+                .or_default()
+                .insert(query.id.from, query.output);
+            metadata.entry(query.id.query_type).or_default().insert(
+                query.id.from,
+                QueryMetadata {
+                    verified_at: saved_revision,
+                    changed_at: query.changed_at,
+                    dependencies,
+                    output_hash: query.output_hash,
+                    from: query.from,
+                },
+            );
+            inserted.push(query.id);
+        }
+        drop((outputs, metadata));
+        for id in inserted {
+            self.touch_lru(id);
         }
 
-        // 4. check if there is a verified cached result
-        let to = if self.find_query_output::<Q>(current_query).is_some() {
-            let metadata = self.find_query_metadata(current_query).unwrap() as &QueryMetadata;
-            if metadata.verified_at == self.current_rivision {
-                // verified to have runned in this rivision
-                self.find_query_output::<Q>(current_query).unwrap().clone()
-            } else {
-                // let's check if the cach can be used
-                if self.check_query_is_up_to_date(current_query) {
-                    self.find_query_output::<Q>(current_query).unwrap().clone()
-                } else {
-                    Q::run(self, from)
+        self.current_rivision = saved_revision;
+        self.current_rivision.increment();
+        Ok(())
+    }
+
+    /// Writes every memoized query and its dependency edges as a Graphviz DOT graph.
+    ///
+    /// Edges point from a query to the queries it fetched. Nodes are named
+    /// after the query type and the hash of its input.
+    pub fn write_query_graph(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let query_types = self.query_types.read().unwrap();
+        let node = |id: &QueryId| {
+            let name = query_types.get(&id.query_type).map_or("?", |t| t.name);
+            format!("\"{}({:016x})\"", name, id.from.0)
+        };
+
+        let mut lines = Vec::new();
+        for (query_type, queries) in self.queries_metadata.read().unwrap().iter() {
+            for (from, metadata) in queries {
+                let id = QueryId {
+                    query_type: *query_type,
+                    from: *from,
+                };
+                lines.push(format!("    {};", node(&id)));
+                for dependency in &metadata.dependencies {
+                    lines.push(format!("    {} -> {};", node(&id), node(dependency)));
                 }
             }
-        } else {
-            // no cached result, run the query
-            Q::run(self, from)
-        };
+        }
+        // storage is a hash map, sort to get the same file for the same database
+        lines.sort();
+        lines.dedup();
 
-        // 4. remove this query from stack
-        self.stack.pop();
+        writeln!(writer, "digraph queries {{")?;
+        for line in lines {
+            writeln!(writer, "{}", line)?;
+        }
+        writeln!(writer, "}}")
+    }
 
-        Ok(to)
+    fn register<Q: Query>(&self) {
+        if self
+            .query_types
+            .read()
+            .unwrap()
+            .contains_key(&TypeId::of::<Q>())
+        {
+            return;
+        }
+        self.query_types
+            .write()
+            .unwrap()
+            .entry(TypeId::of::<Q>())
+            .or_insert(QueryType {
+                name: std::any::type_name::<Q>(),
+                refresh: refresh_query::<Q>,
+                persist: None,
+            });
+    }
+
+    /// Returns how the cached result of a query can be reused, or `None` if it
+    /// has to be run again.
+    fn verify_query<Q: Query>(&self, query_id: QueryId) -> Option<FetchOutcome> {
+        let (verified_at, dependencies) = self.find_query_metadata(query_id, |metadata| {
+            (metadata.verified_at, metadata.dependencies.clone())
+        })?;
+        if verified_at == self.current_rivision {
+            // verified to have runned in this rivision
+            return Some(FetchOutcome::Hit);
+        }
+        if Q::IS_INPUT {
+            return None;
+        }
+
+        // the cache can be used if no dependency has changed since it was verified
+        for dependency in dependencies {
+            let refresh = self
+                .query_types
+                .read()
+                .unwrap()
+                .get(&dependency.query_type)?
+                .refresh;
+            match refresh(self, dependency) {
+                Some(changed_at) if changed_at <= verified_at => {}
+                _ => return None,
+            }
+        }
+
+        if let Some(metadata) = self
+            .queries_metadata
+            .write()
+            .unwrap()
+            .get_mut(&query_id.query_type)
+            .and_then(|found| found.get_mut(&query_id.from))
+        {
+            metadata.verified_at = self.current_rivision;
+        }
+        Some(FetchOutcome::Verified)
     }
 
-    fn check_query_is_up_to_date(&self, query_id: QueryId) -> bool {
-        todo!()
+    fn touch_lru(&self, query_id: QueryId) {
+        let lrus = self.lrus.read().unwrap();
+        let Some(lru) = lrus.get(&query_id.query_type) else {
+            return;
+        };
+        let evicted = lru.lock().unwrap().touch(query_id.from);
+        drop(lrus);
+        if evicted.is_empty() {
+            return;
+        }
+        if let Some(found) = self
+            .queries_outputs
+            .write()
+            .unwrap()
+            .get_mut(&query_id.query_type)
+        {
+            for from in evicted {
+                found.remove(&from);
+            }
+        }
     }
 
-    fn find_query_output<Q: Query>(&self, query_id: QueryId) -> Option<&Q::To> {
+    fn with_stack<R>(&self, f: impl FnOnce(&mut Vec<ActiveQuery>) -> R) -> R {
+        let mut stacks = self.stacks.lock().unwrap();
+        let stack = stacks.entry(std::thread::current().id()).or_default();
+        let ret = f(stack);
+        if stack.is_empty() {
+            stacks.remove(&std::thread::current().id());
+        }
+        ret
+    }
+
+    fn find_query_output<Q: Query>(&self, query_id: QueryId) -> Option<Q::To> {
         assert_eq!(query_id.query_type, TypeId::of::<Q>());
-        let found = self.queries_outputs.get(&TypeId::of::<Q>())?;
+        let outputs = self.queries_outputs.read().unwrap();
+        let found = outputs.get(&TypeId::of::<Q>())?;
         let storage = found.get(&query_id.from)?;
-        Some(storage.downcast_ref::<Q::To>().unwrap())
+        Some(storage.downcast_ref::<Q::To>().unwrap().clone())
+    }
+
+    fn find_query_metadata<R>(
+        &self,
+        query_id: QueryId,
+        f: impl FnOnce(&QueryMetadata) -> R,
+    ) -> Option<R> {
+        let metadata = self.queries_metadata.read().unwrap();
+        let found = metadata.get(&query_id.query_type)?;
+        found.get(&query_id.from).map(f)
     }
 
-    fn find_query_metadata_mut(&mut self, query_id: QueryId) -> Option<&mut QueryMetadata> {
-        let found = self.queries_metadata.get_mut(&query_id.query_type)?;
-        found.get_mut(&query_id.from)
+    fn store_query_output<Q: Query>(
+        &self,
+        query_id: QueryId,
+        from: Q::From,
+        output: Q::To,
+        dependencies: Vec<QueryId>,
+    ) {
+        let output_hash = hash_of(&output);
+        let changed_at = match self.find_query_metadata(query_id, |metadata| {
+            (metadata.output_hash, metadata.changed_at)
+        }) {
+            // same output as before, so the queries depending on it stay valid
+            Some((old_hash, changed_at)) if old_hash == output_hash => changed_at,
+            _ => self.current_rivision,
+        };
+        self.queries_outputs
+            .write()
+            .unwrap()
+            .entry(query_id.query_type)
+            .or_default()
+            .insert(query_id.from, Arc::new(output));
+        self.queries_metadata
+            .write()
+            .unwrap()
+            .entry(query_id.query_type)
+            .or_default()
+            .insert(
+                query_id.from,
+                QueryMetadata {
+                    verified_at: self.current_rivision,
+                    changed_at,
+                    dependencies,
+                    output_hash,
+                    from: Arc::new(from),
+                },
+            );
     }
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Joins the workers in the order they were spawned.
+fn join_all<T>(handles: Vec<std::thread::ScopedJoinHandle<'_, T>>) -> Vec<T> {
+    handles
+        .into_iter()
+        // resume the panic of a worker, so that cancellation reaches the caller
+        .map(|handle| {
+            handle
+                .join()
+                .unwrap_or_else(|payload| std::panic::resume_unwind(payload))
+        })
+        .collect()
+}
 
-    fn find_query_metadata(&self, query_id: QueryId) -> Option<&QueryMetadata> {
-        let found = self.queries_metadata.get(&query_id.query_type)?;
-        found.get(&query_id.from)
+fn refresh_query<Q: Query>(db: &Database, query_id: QueryId) -> Option<Revision> {
+    let from = db.find_query_metadata(query_id, |metadata| {
+        metadata.from.downcast_ref::<Q::From>().cloned()
+    })??;
+    db.fetch::<Q>(from).ok()?;
+    db.find_query_metadata(query_id, |metadata| metadata.changed_at)
+}
+
+fn save_queries<Q: PersistentQuery>(
+    db: &Database,
+    names: &HashMap<TypeId, &'static str>,
+    encoder: &mut Encoder<'_>,
+) {
+    let outputs = db.queries_outputs.read().unwrap();
+    let metadata = db.queries_metadata.read().unwrap();
+    let (Some(outputs), Some(metadata)) = (
+        outputs.get(&TypeId::of::<Q>()),
+        metadata.get(&TypeId::of::<Q>()),
+    ) else {
+        encoder.write_u32(0);
+        return;
+    };
+
+    let mut saved = metadata
+        .iter()
+        .filter_map(|(from, metadata)| {
+            let output = outputs.get(from)?.downcast_ref::<Q::To>().unwrap();
+            let dependencies = metadata
+                .dependencies
+                .iter()
+                .map(|dependency| Some((names.get(&dependency.query_type)?, dependency.from.0)))
+                .collect::<Option<Vec<_>>>()?;
+            Some((from.0, metadata, output, dependencies))
+        })
+        .collect::<Vec<_>>();
+    saved.sort_by_key(|(from, ..)| *from);
+
+    encoder.write_u32(saved.len() as u32);
+    for (from, metadata, output, dependencies) in saved {
+        encoder.write_u64(from);
+        metadata
+            .from
+            .downcast_ref::<Q::From>()
+            .unwrap()
+            .encode(encoder);
+        output.encode(encoder);
+        encoder.write_u64(metadata.output_hash);
+        encoder.write_u32(metadata.changed_at.0);
+        encoder.write_u32(dependencies.len() as u32);
+        for (name, from) in dependencies {
+            encoder.write_str(name);
+            encoder.write_u64(from);
+        }
+    }
+}
+
+fn load_queries<Q: PersistentQuery>(
+    decoder: &mut Decoder<'_>,
+) -> Result<Vec<LoadedQuery>, CacheErr> {
+    let count = decoder.read_u32()?;
+    let mut loaded = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let saved_from = HashedQueryFrom(decoder.read_u64()?);
+        let from = Q::From::decode(decoder)?;
+        let output = Q::To::decode(decoder)?;
+        let output_hash = decoder.read_u64()?;
+        let changed_at = Revision(decoder.read_u32()?);
+        let dependencies = (0..decoder.read_u32()?)
+            .map(|_| {
+                let name = decoder.read_str()?.to_string();
+                Ok((name, HashedQueryFrom(decoder.read_u64()?)))
+            })
+            .collect::<Result<Vec<_>, CacheErr>>()?;
+        loaded.push(LoadedQuery {
+            name: Q::NAME,
+            id: QueryId::new::<Q>(HashedQueryFrom::new(&from)),
+            saved_from,
+            from: Arc::new(from),
+            output: Arc::new(output),
+            output_hash,
+            changed_at,
+            dependencies,
+        });
+    }
+    if !decoder.is_finished() {
+        return Err(CacheErr::Corrupted);
     }
+    Ok(loaded)
 }
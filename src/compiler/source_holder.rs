@@ -26,12 +26,20 @@ impl<'src> SourceHolder<'src> {
         }
     }
 
+    /// Finds the line starts itself, for when the tokenizer didn't.
+    pub fn from_source(source: &'src str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(at, _)| at as u32 + 1))
+            .collect();
+        Self::new(source, line_starts)
+    }
+
     pub fn get_snippet(&self, span: Span) -> &'src str {
         &self.src[span.begin as usize..span.end as usize]
     }
 
     /// エラー表示時などに (行, 列) を計算する
-    pub fn resolve_position(&mut self, offset: u32) -> (usize, usize) {
+    pub fn resolve_position(&self, offset: u32) -> (usize, usize) {
         let line_idx = match self.line_starts.binary_search(&offset) {
             Ok(idx) => idx,
             Err(idx) => idx - 1,
@@ -40,6 +48,16 @@ impl<'src> SourceHolder<'src> {
         (line_idx + 1, column + 1)
     }
 
+    /// The text of the 1-based line `line`, without its line break.
+    pub fn line(&self, line: usize) -> &'src str {
+        let start = self.line_starts[line - 1] as usize;
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.src.len(), |&next| next as usize);
+        self.src[start..end].trim_end_matches(['\n', '\r'])
+    }
+
     pub fn len(&self) -> usize {
         self.src.len()
     }
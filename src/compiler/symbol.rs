@@ -1,6 +1,6 @@
 //! # Symbol Interning
 //!
-//! This module provides a `SymbolFactory` for interning strings. Symbol interning is a
+//! This module provides an `Interner` for interning strings. Symbol interning is a
 //! technique for storing only one copy of each distinct string. This can save a
 //! significant amount of memory, especially in a compiler where the same
 //! identifiers and keywords are often repeated.
@@ -8,9 +8,30 @@
 //! Instead of passing strings around, the compiler can use `Symbol`s, which are
 //! lightweight numeric IDs. This makes comparisons, hashing, and storage much
 //! more efficient.
+//!
+//! All files of a compilation share the global interner, so a `Symbol` means
+//! the same string no matter which file it was read from, and it stays valid
+//! after the source it was read from is dropped. Well-known names are interned
+//! before anything else and are available as constants in `sym`.
+//!
+//! Workers which run in parallel intern into interners of their own, see
+//! `SymbolFactory::with_interner`, so the ids they hand out don't depend on
+//! how the threads are scheduled. Their interners are merged afterwards with
+//! `Interner::absorb`.
+//!
+//! An interner can be written with `Interner::encode`. Reading it back with
+//! `Interner::decode_into` interns the saved strings into a live interner and
+//! returns a `SymbolRemap` from the saved ids to the live ones, which the
+//! `Decoder` applies to every `Symbol` it reads afterwards.
 
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::sync::{OnceLock, RwLock};
 
+use crate::compiler::context::Mergeble;
+use crate::compiler::runtime::persist::{CacheErr, Decoder, Encoder};
 use crate::compiler::span::Span;
 
 /// A rough estimate used to pre-allocate the symbol table's capacity.
@@ -18,10 +39,14 @@ use crate::compiler::span::Span;
 /// helping to avoid reallocations of the hash map.
 const RECIPROCAL_OF_USUAL_SYMBOL_NUM_PER_LENGTH: usize = 120;
 
+/// The number of independently locked parts of the interning table.
+/// Threads interning different strings rarely wait for each other.
+const SHARD_COUNT: usize = 16;
+
 /// Represents a unique identifier for a string.
 ///
 /// A `Symbol` is a lightweight, copyable handle that can be used to refer to a
-/// string that has been interned by a `SymbolFactory`. It can be cheaply
+/// string that has been interned by an `Interner`. It can be cheaply
 /// compared for equality.
 #[derive(Eq, PartialEq, Copy, Clone, Debug, Hash)]
 pub struct Symbol(u32);
@@ -30,21 +55,261 @@ impl Symbol {
     pub fn raw(&self) -> u32 {
         self.0
     }
+
+    /// Rebuilds a symbol from its raw id, e.g. one read back from the query cache.
+    pub(crate) fn from_raw(raw: u32) -> Self {
+        Symbol(raw)
+    }
+
+    /// Interns `string` into the global interner.
+    pub fn intern(string: &str) -> Self {
+        Interner::global().intern(string)
+    }
+
+    /// Returns the string this symbol was interned from by the global interner.
+    pub fn as_str(&self) -> &'static str {
+        Interner::global().get(*self)
+    }
+
+    /// Returns an adaptor which displays the string of this symbol in `interner`.
+    pub fn display(self, interner: &Interner) -> SymbolDisplay<'_> {
+        SymbolDisplay {
+            symbol: self,
+            interner,
+        }
+    }
+}
+
+/// Displays a `Symbol` as its string, see `Symbol::display`.
+pub struct SymbolDisplay<'a> {
+    symbol: Symbol,
+    interner: &'a Interner,
+}
+
+impl Display for SymbolDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.interner.get(self.symbol))
+    }
+}
+
+macro_rules! predefined_symbols {
+    ($($name:ident: $string:literal),* $(,)?) => {
+        #[allow(non_camel_case_types)]
+        #[repr(u32)]
+        enum Predefined {
+            $($name),*
+        }
+
+        /// Symbols every `Interner` starts with, e.g. `sym::main`.
+        ///
+        /// They can be compared against without looking anything up.
+        #[allow(non_upper_case_globals)]
+        pub mod sym {
+            use super::{Predefined, Symbol};
+
+            $(pub const $name: Symbol = Symbol(Predefined::$name as u32);)*
+        }
+
+        const PREDEFINED: &[&str] = &[$($string),*];
+    };
+}
+
+predefined_symbols! {
+    main: "main",
+    SelfTy: "Self",
+    Option: "Option",
+    Result: "Result",
+    Ok: "Ok",
+    Err: "Err",
+    print: "print",
+    Underscore: "_",
 }
 
-/// A factory for creating and managing symbols.
+/// An owned, thread-safe table of interned strings.
 ///
-/// The `SymbolFactory` is responsible for interning strings from the source
-/// code. It maintains a mapping from string slices to `Symbol`s, ensuring that
-/// each unique string is associated with a unique `Symbol`.
+/// The table is split into shards which are locked separately, and the
+/// strings are never freed, so the `&'static str` returned by `get` stays
+/// valid for the rest of the program.
+pub struct Interner {
+    shards: [RwLock<HashMap<&'static str, Symbol>>; SHARD_COUNT],
+    strings: RwLock<Vec<&'static str>>,
+}
+
+impl Interner {
+    /// Creates an interner holding only the symbols of `sym`.
+    pub fn new() -> Self {
+        let interner = Self {
+            shards: std::array::from_fn(|_| RwLock::new(HashMap::new())),
+            strings: RwLock::new(Vec::new()),
+        };
+        for (id, string) in PREDEFINED.iter().enumerate() {
+            assert_eq!(
+                interner.intern(string).raw() as usize,
+                id,
+                "duplicated predefined symbol {string:?}"
+            );
+        }
+        interner
+    }
+
+    /// The interner shared by every file of the compilation.
+    pub fn global() -> &'static Interner {
+        static GLOBAL: OnceLock<Interner> = OnceLock::new();
+        GLOBAL.get_or_init(Interner::new)
+    }
+
+    /// Returns the symbol of `string`, interning it if it is new.
+    pub fn intern(&self, string: &str) -> Symbol {
+        let shard = &self.shards[shard_of(string)];
+        if let Some(&symbol) = shard.read().unwrap().get(string) {
+            return symbol;
+        }
+
+        let mut shard = shard.write().unwrap();
+        // another thread may have interned it while the lock was released
+        if let Some(&symbol) = shard.get(string) {
+            return symbol;
+        }
+        let string: &'static str = Box::leak(string.into());
+        let symbol = {
+            let mut strings = self.strings.write().unwrap();
+            let id = u32::try_from(strings.len()).expect("too many symbols");
+            strings.push(string);
+            Symbol(id)
+        };
+        shard.insert(string, symbol);
+        symbol
+    }
+
+    /// Returns the string `symbol` was interned from.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `symbol` was not interned by this interner.
+    pub fn get(&self, symbol: Symbol) -> &'static str {
+        self.strings.read().unwrap()[symbol.raw() as usize]
+    }
+
+    /// Returns the number of interned strings, including the predefined ones.
+    pub fn len(&self) -> usize {
+        self.strings.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Interns every string of `other` into this interner.
+    ///
+    /// Strings already known to `self` keep their symbols, and the new ones
+    /// are interned in the order `other` created them, so the result only
+    /// depends on the order of the merges.
+    ///
+    /// # Returns
+    ///
+    /// A `SymbolRemap` translating symbols of `other` into symbols of `self`.
+    pub fn absorb(&self, other: &Interner) -> SymbolRemap {
+        let strings = other.strings.read().unwrap().clone();
+        SymbolRemap(strings.into_iter().map(|s| self.intern(s)).collect())
+    }
+
+    /// Writes every interned string in the order of their symbols.
+    pub fn encode(&self, encoder: &mut Encoder<'_>) {
+        let strings = self.strings.read().unwrap();
+        encoder.write_u32(strings.len() as u32);
+        for string in strings.iter() {
+            encoder.write_str(string);
+        }
+    }
+
+    /// Interns the strings written by `encode` into this interner.
+    ///
+    /// # Returns
+    ///
+    /// A `SymbolRemap` translating the saved symbols into symbols of `self`.
+    pub fn decode_into(&self, decoder: &mut Decoder<'_>) -> Result<SymbolRemap, CacheErr> {
+        let count = decoder.read_u32()?;
+        let mut remap = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            remap.push(self.intern(decoder.read_str()?));
+        }
+        Ok(SymbolRemap(remap))
+    }
+
+    /// Reads the strings written by `encode` into a new interner, in which
+    /// they get the same symbols they had when they were written.
+    pub fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        let interner = Self::new();
+        let remap = interner.decode_into(decoder)?;
+        if !remap.is_identity() || remap.len() < PREDEFINED.len() {
+            return Err(CacheErr::Corrupted);
+        }
+        Ok(interner)
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A translation table from the symbols of one interner to the symbols
+/// of the interner they were merged or loaded into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolRemap(Vec<Symbol>);
+
+impl SymbolRemap {
+    /// Translates `symbol`, or returns `None` if the source table did not contain it.
+    pub fn try_get(&self, symbol: Symbol) -> Option<Symbol> {
+        self.0.get(symbol.raw() as usize).copied()
+    }
+
+    /// Translates `symbol`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the source table did not contain `symbol`.
+    pub fn get(&self, symbol: Symbol) -> Symbol {
+        self.0[symbol.raw() as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns `true` if every symbol is translated to itself.
+    pub fn is_identity(&self) -> bool {
+        self.0
+            .iter()
+            .enumerate()
+            .all(|(id, symbol)| symbol.raw() as usize == id)
+    }
+}
+
+fn shard_of(string: &str) -> usize {
+    let mut hasher = DefaultHasher::new();
+    string.hash(&mut hasher);
+    hasher.finish() as usize % SHARD_COUNT
+}
+
+/// A factory for creating symbols from the spans of one source file.
+///
+/// The `SymbolFactory` interns strings from the source code into an
+/// `Interner`, the global one unless it is made with `with_interner`. It keeps
+/// a cache of the strings it has already seen, so the shared table is only
+/// consulted once per distinct string of the file.
 pub struct SymbolFactory<'src> {
-    /// The next available symbol ID.
-    now_symbol_id: u32,
     /// A reference to the source code holder.
     source: &'src str,
-    /// The interning table, mapping string slices to their corresponding symbols.
-    map: HashMap<&'src str, u32>,
-    reverse: Vec<&'src str>,
+    /// The interner the symbols are interned into.
+    interner: &'src Interner,
+    /// The strings of this file which have already been interned.
+    map: HashMap<&'src str, Symbol>,
 }
 
 /// Implementation block for `SymbolFactory`.
@@ -54,30 +319,38 @@ pub struct SymbolFactory<'src> {
 impl<'src> SymbolFactory<'src> {
     /// Creates a new `SymbolFactory` for a given source holder.
     ///
-    /// The factory is initialized with an empty symbol table. The capacity of the
-    /// table is pre-allocated based on a heuristic to improve performance.
+    /// The factory is initialized with an empty cache. The capacity of the
+    /// cache is pre-allocated based on a heuristic to improve performance.
     ///
     /// # Arguments
     ///
     /// * `src` - A reference to the `SourceHolder` containing the source code.
     pub fn new(src: &'src str) -> Self {
+        Self::with_interner(src, Interner::global())
+    }
+
+    /// Creates a `SymbolFactory` which interns into `interner` instead of the
+    /// global interner.
+    pub fn with_interner(src: &'src str, interner: &'src Interner) -> Self {
         Self {
             map: HashMap::with_capacity(
                 src.len() * 2 / RECIPROCAL_OF_USUAL_SYMBOL_NUM_PER_LENGTH + 1,
             ),
-            reverse: Vec::with_capacity(
-                src.len() * 2 / RECIPROCAL_OF_USUAL_SYMBOL_NUM_PER_LENGTH + 1,
-            ),
             source: src,
-            now_symbol_id: 0,
+            interner,
         }
     }
 
+    /// The interner the symbols of this factory belong to.
+    pub fn interner(&self) -> &'src Interner {
+        self.interner
+    }
+
     /// Interns a string slice represented by a `Span`.
     ///
     /// If the string has already been interned, this method returns the existing
-    /// `Symbol`. Otherwise, it creates a new `Symbol`, adds it to the symbol table,
-    /// and returns it.
+    /// `Symbol`. Otherwise, it creates a new `Symbol` in the interner of the
+    /// factory and returns it.
     ///
     /// # Arguments
     ///
@@ -88,19 +361,11 @@ impl<'src> SymbolFactory<'src> {
     /// The `Symbol` for the given string slice.
     #[inline]
     pub fn from_span(&mut self, span: Span) -> Symbol {
-        // Create a temporary `SpanWithRef` to perform the lookup.
-        let span_with_ref = span.into(self.source);
-        if let Some(&id) = self.map.get(span_with_ref) {
-            Symbol(id)
-        } else {
-            // If the symbol is not found, create a new one.
-            let symbol_id = self.now_symbol_id;
-            let symbol = Symbol(symbol_id);
-            self.map.insert(span.into(self.source), symbol.raw());
-            self.reverse.push(span.into(self.source));
-            self.now_symbol_id += 1;
-            symbol
-        }
+        let string = span.into(self.source);
+        *self
+            .map
+            .entry(string)
+            .or_insert_with(|| self.interner.intern(string))
     }
 
     pub fn from_range(&mut self, begin: usize, end: usize) -> Symbol {
@@ -108,7 +373,32 @@ impl<'src> SymbolFactory<'src> {
         self.from_span(span)
     }
 
-    pub fn get(&self, symbol: &Symbol) -> &'src str {
-        self.reverse.get(symbol.raw() as usize).unwrap()
+    pub fn get(&self, symbol: &Symbol) -> &'static str {
+        self.interner.get(*symbol)
+    }
+
+    /// Adds the strings `other` has seen to the cache of this factory.
+    ///
+    /// If `other` interns into another interner, its strings are interned
+    /// into the one of this factory first, see `Interner::absorb`.
+    pub fn absorb(&mut self, other: &SymbolFactory<'src>) {
+        if std::ptr::eq(self.interner, other.interner) {
+            self.map.extend(other.map.iter());
+            return;
+        }
+        let remap = self.interner.absorb(other.interner);
+        self.map.extend(
+            other
+                .map
+                .iter()
+                .map(|(string, symbol)| (*string, remap.get(*symbol))),
+        );
+    }
+}
+
+impl Mergeble for SymbolFactory<'_> {
+    fn merge(mut self, other: Self) -> Self {
+        self.absorb(&other);
+        self
     }
 }
@@ -1,4 +1,4 @@
-use crate::compiler::arena::{Arena, ArenaBox};
+use crate::compiler::arena::{Arena, ArenaBox, ArenaIter};
 
 #[test]
 fn test_arena_basic_alloc() {
@@ -11,14 +11,14 @@ fn test_arena_basic_alloc() {
 fn test_arena_alloc_slice() {
     let arena = Arena::new();
     let data = [1, 2, 3, 4, 5];
-    let mut iter = arena.alloc_iter(data.into_iter());
+    let iter = arena.alloc_iter(data.into_iter());
     let mut reader = iter.into_ref(&arena);
 
-    assert_eq!(reader.next(), Some(&mut 1));
-    assert_eq!(reader.next(), Some(&mut 2));
-    assert_eq!(reader.next(), Some(&mut 3));
-    assert_eq!(reader.next(), Some(&mut 4));
-    assert_eq!(reader.next(), Some(&mut 5));
+    assert_eq!(reader.next(), Some(&1));
+    assert_eq!(reader.next(), Some(&2));
+    assert_eq!(reader.next(), Some(&3));
+    assert_eq!(reader.next(), Some(&4));
+    assert_eq!(reader.next(), Some(&5));
     assert_eq!(reader.next(), None);
 }
 
@@ -26,12 +26,12 @@ fn test_arena_alloc_slice() {
 fn test_arena_alloc_iter() {
     let arena = Arena::new();
     let data = vec![10, 20, 30];
-    let mut iter = arena.alloc_iter(data.into_iter());
+    let iter = arena.alloc_iter(data.into_iter());
     let mut reader = iter.into_ref(&arena);
 
-    assert_eq!(reader.next(), Some(&mut 10));
-    assert_eq!(reader.next(), Some(&mut 20));
-    assert_eq!(reader.next(), Some(&mut 30));
+    assert_eq!(reader.next(), Some(&10));
+    assert_eq!(reader.next(), Some(&20));
+    assert_eq!(reader.next(), Some(&30));
     assert_eq!(reader.next(), None);
 }
 
@@ -44,7 +44,7 @@ fn test_arena_large_allocation() {
     let mut iter = arena.alloc_iter(0..count).into_ref(&arena);
 
     for i in 0..count {
-        assert_eq!(iter.next().as_deref(), Some(&i));
+        assert_eq!(iter.next(), Some(&i));
     }
     assert_eq!(iter.next(), None);
 }
@@ -58,7 +58,7 @@ fn test_arena_large_allocation() {
     let mut iter = arena.alloc_iter(0..count).into_ref(&arena);
 
     for i in 0..count {
-        assert_eq!(iter.next().as_deref(), Some(&i));
+        assert_eq!(iter.next(), Some(&i));
     }
     assert_eq!(iter.next(), None);
 }
@@ -85,3 +85,217 @@ fn test_mixed_allocations() {
         [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1]
     );
 }
+
+#[test]
+fn test_arena_iter_mut() {
+    let mut arena = Arena::new();
+    let iter = arena.alloc_iter([1, 2, 3].into_iter());
+    for item in iter.iter_mut(&mut arena) {
+        *item *= 10;
+    }
+    assert_eq!(
+        iter.into_ref(&arena).copied().collect::<Vec<_>>(),
+        vec![10, 20, 30]
+    );
+}
+
+#[test]
+fn test_arena_padded_items() {
+    let arena = Arena::new();
+    let items = [(1u8, 10u32), (2u8, 20u32), (3u8, 30u32)];
+    let iter = arena.alloc_iter(items.into_iter());
+    assert_eq!(iter.into_ref(&arena).copied().collect::<Vec<_>>(), items);
+}
+
+#[test]
+fn test_arena_nested_iter_allocation() {
+    let arena = Arena::new();
+    arena.start_iter_allocation::<ArenaIter<u16>>();
+    for i in 0..3u16 {
+        let inner = arena.alloc_iter(0..i);
+        arena.alloc_iter_item(&inner);
+    }
+    let outer = arena.finish_iter_allocation::<ArenaIter<u16>>();
+
+    let lens = outer
+        .into_ref(&arena)
+        .map(|inner| inner.into_ref(&arena).count())
+        .collect::<Vec<_>>();
+    assert_eq!(lens, vec![0, 1, 2]);
+}
+
+#[test]
+fn test_arena_abandoned_iter_allocation() {
+    let arena = Arena::new();
+    arena.start_iter_allocation::<u32>();
+    arena.alloc_iter_item(&1u32);
+    arena.start_iter_allocation::<u64>();
+    arena.alloc_iter_item(&2u64);
+    arena.abandon_iter_allocation();
+    arena.alloc_iter_item(&3u32);
+    let outer = arena.finish_iter_allocation::<u32>();
+    assert_eq!(outer.as_slice(&arena), &[1, 3]);
+}
+
+#[test]
+fn test_arena_zero_sized_values() {
+    let arena = Arena::new();
+    let unit = arena.alloc(());
+    assert_eq!(unit.get(&arena), &());
+    let units = arena.alloc_iter(std::iter::repeat_n((), 3));
+    assert_eq!(units.into_ref(&arena).count(), 3);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "not allocated in")]
+fn test_arena_box_from_other_arena() {
+    let arena = Arena::new();
+    let other = Arena::new();
+    let value = other.alloc(1u32);
+    value.get(&arena);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "not allocated in")]
+fn test_arena_iter_from_other_arena() {
+    let arena = Arena::new();
+    let other = Arena::new();
+    let iter = other.alloc_iter(0..3u32);
+    iter.into_ref(&arena);
+}
+
+#[test]
+fn test_arena_iter_random_access() {
+    let arena = Arena::new();
+    let iter = arena.alloc_iter([3.5f64, 1.0, 2.0].into_iter());
+    assert_eq!(iter.len(), 3);
+    assert_eq!(iter.get(1, &arena), Some(&1.0));
+    assert_eq!(iter.get(3, &arena), None);
+    assert_eq!(iter.as_slice(&arena), &[3.5, 1.0, 2.0]);
+
+    let (first, rest) = iter.split_first().unwrap();
+    assert_eq!(first.get(&arena), &3.5);
+    assert_eq!(rest.as_slice(&arena), &[1.0, 2.0]);
+    let (_, rest) = rest.split_first().unwrap();
+    let (last, rest) = rest.split_first().unwrap();
+    assert_eq!(last.get(&arena), &2.0);
+    assert!(rest.is_empty());
+    assert_eq!(rest.split_first(), None);
+}
+
+#[test]
+fn test_arena_iter_is_contiguous_across_blocks() {
+    // small blocks, so that filling one is cheap under miri as well
+    let arena = Arena::with_block_size(256);
+    // leave less room in the first block than the iterator needs
+    arena.alloc_iter(std::iter::repeat_n(0u8, arena.block_size() - 64));
+    let iter = arena.alloc_iter(0..32u64);
+    assert_eq!(iter.as_slice(&arena), (0..32).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_arena_oversized_allocations() {
+    let arena = Arena::with_block_size(256);
+    let small = arena.alloc(1u64);
+    let large = arena.alloc([7u64; 100]);
+    let after = arena.alloc(2u64);
+    assert_eq!(*small.get(&arena), 1);
+    assert_eq!(*large.get(&arena), [7u64; 100]);
+    assert_eq!(*after.get(&arena), 2);
+
+    let iter = arena.alloc_iter(0..1000u32);
+    assert_eq!(iter.as_slice(&arena), (0..1000).collect::<Vec<_>>());
+    let (_, rest) = iter.split_first().unwrap();
+    assert_eq!(rest.get(998, &arena), Some(&999));
+}
+
+#[test]
+#[should_panic(expected = "positive multiple of 64")]
+fn test_arena_rejects_unaligned_block_size() {
+    Arena::with_block_size(100);
+}
+
+#[test]
+fn test_arena_reset_reuses_pages() {
+    let mut arena = Arena::with_block_size(256);
+    arena.alloc_iter(0..200u32);
+    let pages = arena.stats().pages;
+    assert!(pages > 1);
+
+    arena.reset();
+    assert_eq!(arena.stats().bytes_used, 0);
+    let iter = arena.alloc_iter(0..200u32);
+    assert_eq!(iter.as_slice(&arena), (0..200).collect::<Vec<_>>());
+    assert_eq!(arena.stats().pages, pages);
+}
+
+#[test]
+fn test_arena_reset_keeps_the_page_count_bounded() {
+    let mut arena = Arena::with_block_size(64);
+    let mut pages = Vec::new();
+    for round in 0..8 {
+        arena.reset();
+        // the rounds alternate between needing a small chunk and a large
+        // one first, so the chunk after the first is too small every other round
+        if round % 2 == 1 {
+            arena.alloc([7u8; 256]);
+        }
+        let iter = arena.alloc_iter(0..40u32);
+        assert_eq!(iter.as_slice(&arena), (0..40).collect::<Vec<_>>());
+        pages.push(arena.stats().pages);
+    }
+    assert!(
+        pages[2..].iter().all(|&count| count == pages[1]),
+        "{pages:?}"
+    );
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "not allocated in")]
+fn test_arena_reset_invalidates_handles() {
+    let mut arena = Arena::new();
+    let value = arena.alloc(1u32);
+    arena.reset();
+    value.get(&arena);
+}
+
+#[test]
+fn test_arena_stats() {
+    let arena = Arena::with_block_size(64);
+    arena.alloc(1u8);
+    arena.alloc(2u32);
+    arena.alloc_iter(0..4u64);
+
+    let stats = arena.stats();
+    assert_eq!(stats.bytes_used, 1 + 4 + 32);
+    assert_eq!(stats.alignment_waste, 3);
+    assert_eq!(stats.pages, 1);
+    assert_eq!(stats.bytes_reserved, 64);
+    assert!(stats.types.is_empty());
+}
+
+#[test]
+fn test_arena_type_stats() {
+    let arena = Arena::new().with_type_stats();
+    arena.alloc(1u8);
+    arena.alloc(2u32);
+    arena.alloc(3u32);
+    arena.alloc_iter(0..4u64);
+
+    // the largest node types first
+    let types = arena.stats().types;
+    assert_eq!(
+        types
+            .iter()
+            .map(|stats| (stats.name, stats.size, stats.count, stats.bytes()))
+            .collect::<Vec<_>>(),
+        [("u64", 8, 4, 32), ("u32", 4, 2, 8), ("u8", 1, 1, 1)]
+    );
+    // other arenas don't record theirs
+    let other = Arena::new();
+    other.alloc(1u8);
+    assert!(other.stats().types.is_empty());
+}
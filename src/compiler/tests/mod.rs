@@ -1 +1,3 @@
 mod arena;
+mod query_sys;
+mod symbol;
@@ -1 +1,446 @@
-// TODO: add tests
\ No newline at end of file
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use crate::compiler::arena::{Arena, ArenaBox};
+use crate::compiler::context::frontend::CompilerFrontendContext;
+use crate::compiler::runtime::persist::CacheErr;
+use crate::compiler::runtime::profile::{FetchOutcome, Profiler};
+use crate::compiler::runtime::query_sys::{Database, PersistentQuery, Query, QuerySysFetchErr};
+use crate::compiler::runtime::RuntimeErr;
+use crate::compiler::symbol::{Interner, Symbol, SymbolFactory};
+use crate::diagnostic::stream::StockDiagnosticStream;
+use crate::tokenizer::tokenize::Tokenizer;
+use crate::tokenizer::tokens::Token;
+
+struct Square;
+impl Query for Square {
+    type From = u64;
+    type To = u64;
+    fn run(_: &Database, src: Self::From) -> Self::To {
+        src * src
+    }
+}
+
+struct SumOfSquares;
+impl Query for SumOfSquares {
+    type From = u64;
+    type To = u64;
+    fn run(db: &Database, src: Self::From) -> Self::To {
+        (0..=src).map(|i| db.fetch::<Square>(i).unwrap()).sum()
+    }
+}
+
+struct Cyclic;
+impl Query for Cyclic {
+    type From = ();
+    type To = bool;
+    fn run(db: &Database, src: Self::From) -> Self::To {
+        db.fetch::<Cyclic>(src) == Err(QuerySysFetchErr::DependentCycleDetected)
+    }
+}
+
+#[test]
+fn test_fetch_nested_queries() {
+    let db = Database::new();
+    assert_eq!(db.fetch::<SumOfSquares>(3), Ok(14));
+    assert_eq!(db.fetch::<SumOfSquares>(3), Ok(14));
+}
+
+#[test]
+fn test_fetch_detects_cycle() {
+    let db = Database::new();
+    assert_eq!(db.fetch::<Cyclic>(()), Ok(true));
+}
+
+#[test]
+fn test_profiler_records_outcomes() {
+    let mut db = Database::new();
+    db.enable_profiling(Profiler::new());
+    db.fetch::<SumOfSquares>(1).unwrap();
+    db.fetch::<SumOfSquares>(1).unwrap();
+
+    let outcomes = db
+        .profiler()
+        .unwrap()
+        .events()
+        .iter()
+        .map(|event| (event.name.rsplit("::").next().unwrap(), event.outcome))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        outcomes,
+        vec![
+            ("SumOfSquares", FetchOutcome::Recomputed),
+            ("Square", FetchOutcome::Recomputed),
+            ("Square", FetchOutcome::Recomputed),
+            ("SumOfSquares", FetchOutcome::Hit),
+        ]
+    );
+
+    let mut trace = Vec::new();
+    db.profiler()
+        .unwrap()
+        .write_chrome_trace(&mut trace)
+        .unwrap();
+    let trace = String::from_utf8(trace).unwrap();
+    assert!(trace.starts_with("{\"traceEvents\":["));
+    assert_eq!(trace.matches("\"ph\":\"X\"").count(), 4);
+}
+
+#[test]
+fn test_write_query_graph() {
+    let db = Database::new();
+    db.fetch::<SumOfSquares>(1).unwrap();
+
+    let mut graph = Vec::new();
+    db.write_query_graph(&mut graph).unwrap();
+    let graph = String::from_utf8(graph).unwrap();
+    assert!(graph.starts_with("digraph queries {"));
+    assert_eq!(graph.matches(" -> ").count(), 2);
+    assert_eq!(graph.lines().count(), 2 + 3 + 2);
+}
+
+#[test]
+fn test_fetch_parallel_keeps_input_order() {
+    let db = Database::new();
+    let inputs = (0..64).rev().collect::<Vec<u64>>();
+    let results = db.fetch_parallel::<SumOfSquares>(inputs.clone());
+    for (input, result) in inputs.into_iter().zip(results) {
+        assert_eq!(result, Ok((0..=input).map(|i| i * i).sum()));
+    }
+}
+
+/// Tokenizes `sources` on `workers` threads and returns the raw ids of the
+/// identifiers of every source, the hash of the outputs and the identifiers.
+fn identifiers(
+    db: &Database,
+    sources: &[&str],
+    workers: usize,
+) -> (Vec<Vec<u32>>, u64, Vec<String>) {
+    let interner = Interner::new();
+    let arena = Arena::new();
+    let mut symbols = SymbolFactory::with_interner("", &interner);
+    let outputs = db.fetch_parallel_frontend(
+        CompilerFrontendContext::new("", &arena, &mut symbols),
+        sources,
+        workers,
+        |_, ctx| {
+            let (tokens, _) = Tokenizer::new(ctx.source, ctx.symbol_factory)
+                .tokenize(&mut StockDiagnosticStream::new());
+            ctx.ast_arena
+                .alloc_iter(tokens.into_iter().filter_map(|(token, _)| match token {
+                    Token::Identifier(symbol) => Some(symbol),
+                    _ => None,
+                }))
+        },
+    );
+    let ids = outputs
+        .iter()
+        .map(|identifiers| {
+            identifiers
+                .as_slice(&arena)
+                .iter()
+                .map(Symbol::raw)
+                .collect()
+        })
+        .collect::<Vec<Vec<u32>>>();
+    let mut hasher = DefaultHasher::new();
+    outputs.hash(&mut hasher);
+    let strings = outputs
+        .iter()
+        .flat_map(|identifiers| identifiers.as_slice(&arena))
+        .map(|symbol| interner.get(*symbol).to_string())
+        .collect();
+    (ids, hasher.finish(), strings)
+}
+
+#[test]
+fn test_fetch_parallel_frontend_is_deterministic() {
+    let sources = [
+        "extern fn a(); extern fn b();",
+        "extern fn c(a);",
+        "extern fn d(); extern fn e(b, c); extern fn f();",
+        "extern fn g();",
+        "extern fn h(g); extern fn a();",
+    ];
+    let db = Database::new();
+    let (expected_ids, expected_hash, strings) = identifiers(&db, &sources, 1);
+    assert_eq!(
+        strings,
+        ["a", "b", "c", "a", "d", "e", "b", "c", "f", "g", "h", "g", "a"]
+    );
+    for workers in [1, 2, 3, 8] {
+        let (ids, hash, _) = identifiers(&db, &sources, workers);
+        assert_eq!(ids, expected_ids);
+        assert_eq!(hash, expected_hash);
+    }
+}
+
+struct ParallelSquares;
+impl Query for ParallelSquares {
+    type From = u64;
+    type To = Vec<u64>;
+    fn run(db: &Database, src: Self::From) -> Self::To {
+        let sources = (0..src).map(|i| " ".repeat(i as usize)).collect::<Vec<_>>();
+        let sources = sources.iter().map(String::as_str).collect::<Vec<_>>();
+        let arena = Arena::new();
+        let mut symbols = SymbolFactory::new("");
+        db.fetch_parallel_frontend(
+            CompilerFrontendContext::new("", &arena, &mut symbols),
+            &sources,
+            4,
+            |db, ctx| db.fetch::<Square>(ctx.source.len() as u64).unwrap(),
+        )
+    }
+}
+
+struct ParallelCyclic;
+impl Query for ParallelCyclic {
+    type From = ();
+    type To = Vec<bool>;
+    fn run(db: &Database, src: Self::From) -> Self::To {
+        let arena = Arena::new();
+        let mut symbols = SymbolFactory::new("");
+        db.fetch_parallel_frontend(
+            CompilerFrontendContext::new("", &arena, &mut symbols),
+            &["", ""],
+            2,
+            |db, _| {
+                db.fetch::<ParallelCyclic>(src) == Err(QuerySysFetchErr::DependentCycleDetected)
+            },
+        )
+    }
+}
+
+#[test]
+fn test_fetch_parallel_frontend_records_dependencies() {
+    let db = Database::new();
+    assert_eq!(db.fetch::<ParallelSquares>(4), Ok(vec![0, 1, 4, 9]));
+
+    let mut graph = Vec::new();
+    db.write_query_graph(&mut graph).unwrap();
+    let graph = String::from_utf8(graph).unwrap();
+    assert_eq!(graph.matches(" -> ").count(), 4);
+
+    assert_eq!(db.fetch::<ParallelCyclic>(()), Ok(vec![true, true]));
+}
+
+static SETTING: AtomicU64 = AtomicU64::new(1);
+static DOUBLED_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+struct Setting;
+impl Query for Setting {
+    type From = ();
+    type To = u64;
+    const IS_INPUT: bool = true;
+    fn run(_: &Database, _: Self::From) -> Self::To {
+        SETTING.load(Ordering::SeqCst)
+    }
+}
+impl PersistentQuery for Setting {
+    const NAME: &'static str = "Setting";
+}
+
+struct Doubled;
+impl Query for Doubled {
+    type From = ();
+    type To = u64;
+    fn run(db: &Database, src: Self::From) -> Self::To {
+        DOUBLED_RUNS.fetch_add(1, Ordering::SeqCst);
+        db.fetch::<Setting>(src).unwrap() * 2
+    }
+}
+impl PersistentQuery for Doubled {
+    const NAME: &'static str = "Doubled";
+}
+
+fn database_with_cache(dir: &Path) -> Database {
+    let mut db = Database::new();
+    db.register_persistent::<Setting>();
+    db.register_persistent::<Doubled>();
+    db.load(dir).unwrap();
+    db
+}
+
+#[test]
+fn test_persistent_cache_roundtrip() {
+    let dir = std::env::temp_dir().join(format!("hydent-query-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut db = database_with_cache(&dir);
+    assert_eq!(db.fetch::<Doubled>(()), Ok(2));
+    assert_eq!(DOUBLED_RUNS.load(Ordering::SeqCst), 1);
+
+    // the input did not change, so the derived query is reused
+    db.new_revision();
+    assert_eq!(db.fetch::<Doubled>(()), Ok(2));
+    assert_eq!(DOUBLED_RUNS.load(Ordering::SeqCst), 1);
+    db.save(&dir).unwrap();
+
+    let db = database_with_cache(&dir);
+    assert_eq!(db.fetch::<Doubled>(()), Ok(2));
+    assert_eq!(DOUBLED_RUNS.load(Ordering::SeqCst), 1);
+
+    SETTING.store(5, Ordering::SeqCst);
+    let db = database_with_cache(&dir);
+    assert_eq!(db.fetch::<Doubled>(()), Ok(10));
+    assert_eq!(DOUBLED_RUNS.load(Ordering::SeqCst), 2);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_load_rejects_foreign_file() {
+    let dir = std::env::temp_dir().join(format!("hydent-foreign-cache-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("queries.bin"), b"not a cache").unwrap();
+    let mut db = Database::new();
+    assert_eq!(db.load(&dir), Err(CacheErr::NotACacheFile));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+thread_local! {
+    static BOXES: Arena = Arena::new();
+}
+
+struct Boxed;
+impl Query for Boxed {
+    type From = u64;
+    type To = ArenaBox<u64>;
+    fn run(_: &Database, src: Self::From) -> Self::To {
+        BOXES.with(|arena| arena.alloc(src * 10))
+    }
+}
+impl PersistentQuery for Boxed {
+    const NAME: &'static str = "Boxed";
+}
+
+#[test]
+fn test_cache_of_arena_boxes() {
+    let dir = std::env::temp_dir().join(format!("hydent-arena-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::new();
+    db.register_persistent::<Boxed>();
+    db.fetch::<Boxed>(3).unwrap();
+    assert_eq!(db.save(&dir), Err(CacheErr::MissingArena));
+    assert!(!dir.exists());
+    BOXES.with(|arena| db.save_with_arena(&dir, arena)).unwrap();
+
+    let arena = Arena::new();
+    let mut db = Database::new();
+    db.register_persistent::<Boxed>();
+    db.load_with_arena(&dir, &arena).unwrap();
+    let boxed = db.fetch::<Boxed>(3).unwrap();
+    assert_eq!(*boxed.get(&arena), 30);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+struct Interned;
+impl Query for Interned {
+    type From = String;
+    type To = Symbol;
+    fn run(_: &Database, src: Self::From) -> Self::To {
+        Symbol::intern(&src)
+    }
+}
+impl PersistentQuery for Interned {
+    const NAME: &'static str = "Interned";
+}
+
+#[test]
+fn test_cache_keeps_only_the_reached_symbols() {
+    let dir = std::env::temp_dir().join(format!("hydent-symbol-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    Symbol::intern("left_out_of_the_cache");
+    let db = Database::new();
+    db.register_persistent::<Interned>();
+    let kept = db
+        .fetch::<Interned>("kept_in_the_cache".to_string())
+        .unwrap();
+    db.save(&dir).unwrap();
+
+    let bytes = std::fs::read(dir.join("queries.bin")).unwrap();
+    let contains = |string: &str| bytes.windows(string.len()).any(|w| w == string.as_bytes());
+    assert!(contains("kept_in_the_cache"));
+    assert!(!contains("left_out_of_the_cache"));
+
+    let mut db = Database::new();
+    db.register_persistent::<Interned>();
+    db.load(&dir).unwrap();
+    assert_eq!(
+        db.fetch::<Interned>("kept_in_the_cache".to_string()),
+        Ok(kept)
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+struct CancelInside;
+impl Query for CancelInside {
+    type From = u64;
+    type To = u64;
+    fn run(db: &Database, src: Self::From) -> Self::To {
+        db.cancellation_token().cancel();
+        db.fetch::<Square>(src).unwrap()
+    }
+}
+
+#[test]
+fn test_cancelled_fetch_unwinds() {
+    let mut db = Database::new();
+    let result = db.catch_cancellation(|db| db.fetch::<CancelInside>(3));
+    assert_eq!(result, Err(RuntimeErr::Cancelled));
+
+    let result = db.catch_cancellation(|db| db.fetch_parallel::<Square>((0..16).collect()));
+    assert_eq!(result.unwrap_err(), RuntimeErr::Cancelled);
+
+    // the new revision has a fresh token, and no stale frame is left behind
+    db.new_revision();
+    assert_eq!(db.fetch::<SumOfSquares>(3), Ok(14));
+}
+
+#[test]
+fn test_evicted_outputs_are_recomputed() {
+    let mut db = Database::new();
+    db.set_capacity::<Square>(2);
+    db.enable_profiling(Profiler::new());
+    for i in [1, 2, 3, 1, 3] {
+        assert_eq!(db.fetch::<Square>(i), Ok(i * i));
+    }
+
+    let outcomes = db
+        .profiler()
+        .unwrap()
+        .events()
+        .iter()
+        .map(|event| event.outcome)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        outcomes,
+        vec![
+            FetchOutcome::Recomputed,
+            FetchOutcome::Recomputed,
+            FetchOutcome::Recomputed,
+            FetchOutcome::Recomputed,
+            FetchOutcome::Hit,
+        ]
+    );
+}
+
+#[test]
+fn test_dependents_of_evicted_output_are_verified() {
+    let mut db = Database::new();
+    db.set_capacity::<Square>(1);
+    db.enable_profiling(Profiler::new());
+    assert_eq!(db.fetch::<SumOfSquares>(2), Ok(5));
+    db.new_revision();
+    assert_eq!(db.fetch::<SumOfSquares>(2), Ok(5));
+
+    let events = db.profiler().unwrap().events();
+    let root = events
+        .iter()
+        .rfind(|event| event.name.ends_with("SumOfSquares"))
+        .unwrap();
+    assert_eq!(root.outcome, FetchOutcome::Verified);
+}
@@ -1 +1,179 @@
-// TODO: add tests
\ No newline at end of file
+use std::sync::Arc;
+
+use crate::compiler::context::integrate_all_contexts;
+use crate::compiler::runtime::persist::{CacheErr, Decoder, Encoder, Persist};
+use crate::compiler::symbol::{sym, Interner, Symbol, SymbolFactory};
+use crate::tokenizer::tokens::Token;
+
+#[test]
+fn test_symbol_interning() {
+    let source = "foo bar foo";
+    let mut symbols = SymbolFactory::new(source);
+    let foo = symbols.from_range(0, 3);
+    let bar = symbols.from_range(4, 7);
+    assert_eq!(symbols.from_range(8, 11), foo);
+    assert_ne!(foo, bar);
+    assert_eq!(symbols.get(&bar), "bar");
+}
+
+#[test]
+fn test_symbols_of_different_files_compare_equal() {
+    let first = String::from("alpha beta");
+    let second = String::from("gamma alpha");
+    let alpha = SymbolFactory::new(&first).from_range(0, 5);
+    let other_alpha = SymbolFactory::new(&second).from_range(6, 11);
+    assert_eq!(alpha, other_alpha);
+
+    drop(first);
+    drop(second);
+    assert_eq!(alpha.as_str(), "alpha");
+}
+
+#[test]
+fn test_predefined_symbols() {
+    assert_eq!(Symbol::intern("main"), sym::main);
+    assert_eq!(Symbol::intern("Self"), sym::SelfTy);
+    assert_eq!(sym::Option.as_str(), "Option");
+    assert_eq!(sym::print.as_str(), "print");
+
+    let interner = Interner::new();
+    assert_eq!(interner.intern("Result"), sym::Result);
+    assert_eq!(interner.get(sym::Err), "Err");
+    assert_eq!(interner.len(), 8);
+}
+
+#[test]
+fn test_concurrent_interning() {
+    let interner = Arc::new(Interner::new());
+    let names = (0..64).map(|i| format!("name{i}")).collect::<Vec<_>>();
+    let results = std::thread::scope(|scope| {
+        let workers = (0..4)
+            .map(|worker| {
+                let interner = interner.clone();
+                let names = &names;
+                scope.spawn(move || {
+                    // every worker interns the names in a different order
+                    let mut symbols = names
+                        .iter()
+                        .cycle()
+                        .skip(worker * 16)
+                        .take(names.len())
+                        .map(|name| (name.clone(), interner.intern(name)))
+                        .collect::<Vec<_>>();
+                    symbols.sort_by(|a, b| a.0.cmp(&b.0));
+                    symbols
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    assert!(results.windows(2).all(|pair| pair[0] == pair[1]));
+    assert_eq!(interner.len(), 8 + names.len());
+    for (name, symbol) in &results[0] {
+        assert_eq!(interner.get(*symbol), name);
+    }
+}
+
+#[test]
+fn test_symbol_merge_keeps_symbols() {
+    let source = "a b c d";
+    let parts = (0..4)
+        .map(|i| {
+            let mut symbols = SymbolFactory::new(source);
+            symbols.from_range(i * 2, i * 2 + 1);
+            symbols
+        })
+        .collect::<Vec<_>>();
+    let expected = (0..4)
+        .map(|i| Symbol::intern(&source[i * 2..i * 2 + 1]))
+        .collect::<Vec<_>>();
+    let mut merged = integrate_all_contexts(parts);
+
+    let symbols = (0..4)
+        .map(|i| merged.from_range(i * 2, i * 2 + 1))
+        .collect::<Vec<_>>();
+    assert_eq!(symbols, expected);
+}
+
+#[test]
+fn test_symbol_display() {
+    let interner = Interner::new();
+    let foo = interner.intern("foo");
+    assert_eq!(foo.display(&interner).to_string(), "foo");
+    assert_eq!(
+        Token::Identifier(foo).display(&interner).to_string(),
+        "identifier `foo`"
+    );
+    assert_eq!(
+        Token::Identifier(sym::main).to_string(),
+        "identifier `main`"
+    );
+}
+
+#[test]
+fn test_interner_roundtrip() {
+    let interner = Interner::new();
+    let symbols = ["x", "y", "main", "z"].map(|s| interner.intern(s));
+    let mut encoder = Encoder::new();
+    interner.encode(&mut encoder);
+    let bytes = encoder.into_bytes();
+
+    let decoded = Interner::decode(&mut Decoder::new(&bytes)).unwrap();
+    assert_eq!(decoded.len(), interner.len());
+    for symbol in symbols {
+        assert_eq!(decoded.get(symbol), interner.get(symbol));
+    }
+    assert_eq!(
+        Interner::decode(&mut Decoder::new(&bytes[..bytes.len() - 1])).err(),
+        Some(CacheErr::Corrupted)
+    );
+}
+
+#[test]
+fn test_decoded_symbols_are_remapped() {
+    let saved = Interner::new();
+    let y = saved.intern("y");
+    let x = saved.intern("x");
+    let mut encoder = Encoder::new();
+    saved.encode(&mut encoder);
+    let table = encoder.into_bytes();
+    let mut encoder = Encoder::new();
+    vec![x, y, sym::Ok].encode(&mut encoder);
+    let values = encoder.into_bytes();
+
+    let live = Interner::new();
+    let live_x = live.intern("x");
+    let remap = live.decode_into(&mut Decoder::new(&table)).unwrap();
+    assert_eq!(remap.get(x), live_x);
+    assert_eq!(remap, live.decode_into(&mut Decoder::new(&table)).unwrap());
+
+    let decoded = Vec::<Symbol>::decode(&mut Decoder::new(&values).with_symbols(&remap)).unwrap();
+    let strings = decoded.iter().map(|s| live.get(*s)).collect::<Vec<_>>();
+    assert_eq!(strings, ["x", "y", "Ok"]);
+
+    let mut encoder = Encoder::new();
+    Symbol::from_raw(100).encode(&mut encoder);
+    let bytes = encoder.into_bytes();
+    assert_eq!(
+        Symbol::decode(&mut Decoder::new(&bytes).with_symbols(&remap)),
+        Err(CacheErr::Corrupted)
+    );
+}
+
+#[test]
+fn test_interner_absorb() {
+    let left = Interner::new();
+    let foo = left.intern("foo");
+    let right = Interner::new();
+    let baz = right.intern("baz");
+    let right_foo = right.intern("foo");
+
+    let remap = left.absorb(&right);
+    assert_eq!(remap.get(right_foo), foo);
+    assert_eq!(remap.get(sym::print), sym::print);
+    assert_eq!(left.get(remap.get(baz)), "baz");
+}
@@ -4,3 +4,12 @@ pub trait IntoDiagnostic {
     type Reference;
     fn into_diagnostic(self, reference: &Self::Reference) -> Diagnostic;
 }
+
+/// Lets diagnostics which were collected, like by `StockDiagnosticStream`, be
+/// poured into another stream.
+impl IntoDiagnostic for Diagnostic {
+    type Reference = ();
+    fn into_diagnostic(self, _: &()) -> Diagnostic {
+        self
+    }
+}
@@ -1,5 +1,134 @@
-//! This module defines common diagnostic patterns and their implementations.
+//! The registry of every diagnostic code the compiler reports.
 //!
-//! These patterns provide a structured way to report errors, warnings, and
-//! notes during the compilation process, ensuring consistency and
-//! providing helpful information to the user.
+//! Each code has a `DiagnosticCode` here with its default level, a one-line
+//! message and a long explanation in `explanations/E<id>.md`, which
+//! `hydent explain` prints and the website publishes. Diagnostics take their
+//! id and level from these constants instead of writing numbers themselves.
+//!
+//! A message template marks what the reported message fills in with
+//! `{braces}`, like `annotation is only allowed on {target}`.
+//!
+//! The explanations are Markdown. Their `hydent` code blocks are checked by
+//! the tests: a block tagged `compile_fail,E0005` has to report E0005, an
+//! untagged one has to compile. `expression` parses the block as an
+//! expression instead of a file, and `ignore` skips it.
+
+use crate::diagnostic::DiagnosticLevel;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiagnosticCode {
+    pub id: u32,
+    /// A `snake_case` name of the code, for people rather than tools.
+    pub name: &'static str,
+    pub level: DiagnosticLevel,
+    pub message: &'static str,
+    pub explanation: &'static str,
+}
+
+impl DiagnosticCode {
+    /// The code as it is printed, like `E0005`.
+    pub fn code(&self) -> String {
+        format!("E{:04}", self.id)
+    }
+
+    /// The explanation with the test tags of its code blocks removed.
+    pub fn published_explanation(&self) -> String {
+        self.explanation
+            .lines()
+            .map(|line| match line.strip_prefix("```hydent,") {
+                Some(_) => "```hydent",
+                None => line,
+            })
+            .map(|line| format!("{line}\n"))
+            .collect()
+    }
+}
+
+macro_rules! code {
+    ($id:literal, $name:literal, $level:ident, $message:literal, $file:literal) => {
+        DiagnosticCode {
+            id: $id,
+            name: $name,
+            level: DiagnosticLevel::$level,
+            message: $message,
+            explanation: include_str!(concat!("explanations/", $file)),
+        }
+    };
+}
+
+pub const CHAINED_OPERATORS: DiagnosticCode = code!(
+    1,
+    "chained_operators",
+    Error,
+    "{operators} operators cannot be chained",
+    "E0001.md"
+);
+pub const NESTING_TOO_DEEP: DiagnosticCode = code!(
+    2,
+    "nesting_too_deep",
+    Error,
+    "expression nested too deeply",
+    "E0002.md"
+);
+pub const UNKNOWN_ANNOTATION: DiagnosticCode = code!(
+    3,
+    "unknown_annotation",
+    Warning,
+    "unknown annotation",
+    "E0003.md"
+);
+pub const ANNOTATION_ARGUMENTS: DiagnosticCode = code!(
+    4,
+    "annotation_arguments",
+    Error,
+    "wrong arguments for annotation",
+    "E0004.md"
+);
+pub const ANNOTATION_TARGET: DiagnosticCode = code!(
+    5,
+    "annotation_target",
+    Error,
+    "annotation is only allowed on {target}",
+    "E0005.md"
+);
+pub const DANGLING_ANNOTATION: DiagnosticCode = code!(
+    6,
+    "dangling_annotation",
+    Error,
+    "annotation is not followed by an item",
+    "E0006.md"
+);
+pub const UNEXPECTED_TOKEN: DiagnosticCode =
+    code!(7, "unexpected_token", Error, "unexpected token", "E0007.md");
+pub const UNCLOSED: DiagnosticCode = code!(
+    8,
+    "unclosed",
+    Error,
+    "unclosed {literal or comment}",
+    "E0008.md"
+);
+pub const INVALID_LITERAL: DiagnosticCode =
+    code!(9, "invalid_literal", Error, "invalid {literal}", "E0009.md");
+pub const UNKNOWN_TOKEN: DiagnosticCode =
+    code!(10, "unknown_token", Error, "unknown token", "E0010.md");
+
+/// Every code, ordered by id.
+pub const DIAGNOSTIC_CODES: &[DiagnosticCode] = &[
+    CHAINED_OPERATORS,
+    NESTING_TOO_DEEP,
+    UNKNOWN_ANNOTATION,
+    ANNOTATION_ARGUMENTS,
+    ANNOTATION_TARGET,
+    DANGLING_ANNOTATION,
+    UNEXPECTED_TOKEN,
+    UNCLOSED,
+    INVALID_LITERAL,
+    UNKNOWN_TOKEN,
+];
+
+/// Looks a code up by how people write it: `E0005`, `e0005` or `5`.
+pub fn lookup(code: &str) -> Option<&'static DiagnosticCode> {
+    let digits = code.strip_prefix(['E', 'e']).unwrap_or(code);
+    let id = digits.parse::<u32>().ok()?;
+    DIAGNOSTIC_CODES.iter().find(|code| code.id == id)
+}
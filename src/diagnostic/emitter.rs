@@ -0,0 +1,517 @@
+//! A `DiagnosticStream` which writes the diagnostics of a file as they are
+//! reported, for people or for tools:
+//!
+//! - `human` renders them like `render::render` does,
+//! - `json` writes one JSON object per line,
+//! - `sarif` writes a single SARIF 2.1.0 log for code scanning once the
+//!   stream is finished.
+//!
+//! Lines are 1-based and columns are 1-based and count characters, in both
+//! JSON and SARIF. Spans also carry their byte offsets.
+//!
+//! What `--fix` did is reported through the same stream as `FixNote`s, so
+//! that JSON and SARIF stay machine-readable with it.
+
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crate::compiler::runtime::persist::{CacheErr, Decoder, Encoder, Persist};
+use crate::compiler::runtime::profile::escape_json;
+use crate::compiler::source_holder::SourceHolder;
+use crate::compiler::span::Span;
+use crate::diagnostic::converter::IntoDiagnostic;
+use crate::diagnostic::diagnotice_patterns::DIAGNOSTIC_CODES;
+use crate::diagnostic::fixer::Fix;
+use crate::diagnostic::render::render;
+use crate::diagnostic::stream::DiagnosticStream;
+use crate::diagnostic::{Applicability, Diagnostic, DiagnosticLevel};
+
+pub const SARIF_VERSION: &str = "2.1.0";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const TOOL_NAME: &str = "hydent";
+const TOOL_URI: &str = "https://github.com/sonneko/hydent-lang";
+/// Where the website publishes the explanation of a code.
+const EXPLANATION_URI: &str = "https://sonneko.github.io/hydent-lang/reference/diagnostics";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, clap::ValueEnum)]
+pub enum ErrorFormat {
+    #[default]
+    Human,
+    Json,
+    Sarif,
+}
+
+impl Persist for ErrorFormat {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_u8(match self {
+            ErrorFormat::Human => 0,
+            ErrorFormat::Json => 1,
+            ErrorFormat::Sarif => 2,
+        });
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        match decoder.read_u8()? {
+            0 => Ok(ErrorFormat::Human),
+            1 => Ok(ErrorFormat::Json),
+            2 => Ok(ErrorFormat::Sarif),
+            _ => Err(CacheErr::Corrupted),
+        }
+    }
+}
+
+/// What `--fix` did to the file, with positions in the file before it was
+/// fixed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FixNote {
+    /// A fix which was applied, or skipped since it overlaps another one.
+    Fix {
+        message: String,
+        replacement: String,
+        line: usize,
+        column: usize,
+        applied: bool,
+    },
+    /// The fixes were not applied, since the file had more errors with them.
+    Rejected {
+        errors_before: usize,
+        errors_after: usize,
+    },
+}
+
+impl FixNote {
+    pub fn new(source: &SourceHolder<'_>, fix: &Fix, applied: bool) -> Self {
+        let (line, column) = position(source, fix.span.begin);
+        FixNote::Fix {
+            message: fix.message.to_string(),
+            replacement: fix.replacement.to_string(),
+            line,
+            column,
+            applied,
+        }
+    }
+}
+
+impl Persist for FixNote {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        match self {
+            FixNote::Fix {
+                message,
+                replacement,
+                line,
+                column,
+                applied,
+            } => {
+                encoder.write_u8(0);
+                message.encode(encoder);
+                replacement.encode(encoder);
+                line.encode(encoder);
+                column.encode(encoder);
+                applied.encode(encoder);
+            }
+            FixNote::Rejected {
+                errors_before,
+                errors_after,
+            } => {
+                encoder.write_u8(1);
+                errors_before.encode(encoder);
+                errors_after.encode(encoder);
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        match decoder.read_u8()? {
+            0 => Ok(FixNote::Fix {
+                message: String::decode(decoder)?,
+                replacement: String::decode(decoder)?,
+                line: usize::decode(decoder)?,
+                column: usize::decode(decoder)?,
+                applied: bool::decode(decoder)?,
+            }),
+            1 => Ok(FixNote::Rejected {
+                errors_before: usize::decode(decoder)?,
+                errors_after: usize::decode(decoder)?,
+            }),
+            _ => Err(CacheErr::Corrupted),
+        }
+    }
+}
+
+pub struct EmitterDiagnosticStream<'src, W: Write> {
+    format: ErrorFormat,
+    /// The path of the file as it is printed.
+    path: String,
+    source: SourceHolder<'src>,
+    out: W,
+    /// The diagnostics for the SARIF log, which is written at the end.
+    results: Vec<Diagnostic>,
+    /// The notes for the SARIF log.
+    notes: Vec<FixNote>,
+    errors: usize,
+}
+
+impl<'src, W: Write> EmitterDiagnosticStream<'src, W> {
+    pub fn new(format: ErrorFormat, path: &str, source: &'src str, out: W) -> Self {
+        Self {
+            format,
+            path: path.to_string(),
+            source: SourceHolder::from_source(source),
+            out,
+            results: Vec::new(),
+            notes: Vec::new(),
+            errors: 0,
+        }
+    }
+
+    /// How many errors were reported, not counting warnings and notes.
+    pub fn error_count(&self) -> usize {
+        self.errors
+    }
+
+    pub fn note(&mut self, note: &FixNote) {
+        let path = &self.path;
+        let written = match (self.format, note) {
+            (
+                ErrorFormat::Human,
+                FixNote::Fix {
+                    message,
+                    line,
+                    column,
+                    applied,
+                    ..
+                },
+            ) => {
+                if *applied {
+                    writeln!(self.out, "fixed {path}:{line}:{column}: {message}")
+                } else {
+                    writeln!(
+                        self.out,
+                        "skipped {path}:{line}:{column}: {message}, since it overlaps another fix"
+                    )
+                }
+            }
+            (
+                ErrorFormat::Human,
+                FixNote::Rejected {
+                    errors_before,
+                    errors_after,
+                },
+            ) => writeln!(
+                self.out,
+                "warning: the fixes for {path} were not applied, since the file had {errors_after} errors with them instead of {errors_before}"
+            ),
+            (ErrorFormat::Json, note) => writeln!(self.out, "{}", json_note(path, note)),
+            (ErrorFormat::Sarif, note) => {
+                self.notes.push(note.clone());
+                Ok(())
+            }
+        };
+        written.expect("We failed to write the diagnostics.");
+    }
+
+    /// Writes what is left to write and returns the writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        if self.format == ErrorFormat::Sarif {
+            let log = sarif_log(&self.path, &self.source, &self.results, &self.notes);
+            writeln!(self.out, "{log}")?;
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl<W: Write> DiagnosticStream for EmitterDiagnosticStream<'_, W> {
+    fn pour<D: IntoDiagnostic>(&mut self, diagnostic: D, reference: &D::Reference) {
+        let diagnostic = diagnostic.into_diagnostic(reference);
+        if diagnostic.level == DiagnosticLevel::Error {
+            self.errors += 1;
+        }
+        let written = match self.format {
+            ErrorFormat::Human => write!(
+                self.out,
+                "{}",
+                render(&diagnostic, &self.path, &self.source)
+            ),
+            ErrorFormat::Json => writeln!(
+                self.out,
+                "{}",
+                json_line(&self.path, &self.source, &diagnostic)
+            ),
+            ErrorFormat::Sarif => {
+                self.results.push(diagnostic);
+                Ok(())
+            }
+        };
+        written.expect("We failed to write the diagnostics.");
+    }
+}
+
+/// The 1-based line and character column of `offset`.
+fn position(source: &SourceHolder<'_>, offset: u32) -> (usize, usize) {
+    let (line, byte_column) = source.resolve_position(offset);
+    let text = source.line(line);
+    let column = text
+        .get(..byte_column - 1)
+        .map_or(text.chars().count(), |before| before.chars().count());
+    (line, column + 1)
+}
+
+fn json_string(string: &str) -> String {
+    format!("\"{}\"", escape_json(string))
+}
+
+fn json_option(string: Option<&str>) -> String {
+    string.map_or("null".to_string(), json_string)
+}
+
+fn json_span(path: &str, source: &SourceHolder<'_>, span: Span) -> String {
+    let (line_start, column_start) = position(source, span.begin);
+    let (line_end, column_end) = position(source, span.end);
+    format!(
+        r#"{{"file":{},"byte_start":{},"byte_end":{},"line_start":{line_start},"column_start":{column_start},"line_end":{line_end},"column_end":{column_end}}}"#,
+        json_string(path),
+        span.begin,
+        span.end,
+    )
+}
+
+/// A diagnostic as one line of JSON.
+pub fn json_line(path: &str, source: &SourceHolder<'_>, diagnostic: &Diagnostic) -> String {
+    let highlights = diagnostic
+        .highlights
+        .iter()
+        .map(|highlight| {
+            format!(
+                r#"{{"span":{},"label":{},"is_primary":{}}}"#,
+                json_span(path, source, highlight.span),
+                json_option(highlight.label),
+                highlight.is_primary
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let notes = diagnostic
+        .notes
+        .iter()
+        .map(|note| json_string(note))
+        .collect::<Vec<_>>()
+        .join(",");
+    let suggestions = diagnostic
+        .suggestions
+        .iter()
+        .map(|suggestion| {
+            let span = suggestion
+                .replacement_span
+                .map_or("null".to_string(), |span| json_span(path, source, span));
+            format!(
+                r#"{{"message":{},"span":{span},"replacement":{},"applicability":"{}"}}"#,
+                json_string(suggestion.message),
+                json_option(suggestion.replacement_text),
+                suggestion.applicability
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        r#"{{"code":"E{:04}","level":"{}","message":{},"file":{},"span":{},"highlights":[{highlights}],"notes":[{notes}],"suggestions":[{suggestions}]}}"#,
+        diagnostic.id,
+        diagnostic.level,
+        json_string(diagnostic.message),
+        json_string(path),
+        json_span(path, source, diagnostic.primary),
+    )
+}
+
+/// A `FixNote` as one line of JSON, which has a `fix` or `fix_rejected`
+/// field instead of the `code` of a diagnostic.
+pub fn json_note(path: &str, note: &FixNote) -> String {
+    match note {
+        FixNote::Fix {
+            message,
+            replacement,
+            line,
+            column,
+            applied,
+        } => format!(
+            r#"{{"fix":{{"file":{},"message":{},"replacement":{},"line":{line},"column":{column},"applied":{applied}}}}}"#,
+            json_string(path),
+            json_string(message),
+            json_string(replacement)
+        ),
+        FixNote::Rejected {
+            errors_before,
+            errors_after,
+        } => format!(
+            r#"{{"fix_rejected":{{"file":{},"errors_before":{errors_before},"errors_after":{errors_after}}}}}"#,
+            json_string(path)
+        ),
+    }
+}
+
+/// A `FixNote` as a SARIF notification of the invocation.
+fn sarif_notification(path: &str, note: &FixNote) -> String {
+    match note {
+        FixNote::Fix {
+            message,
+            line,
+            column,
+            applied,
+            ..
+        } => format!(
+            r#"{{"level":"note","message":{{"text":{}}},"locations":[{{"physicalLocation":{{"artifactLocation":{{"uri":{}}},"region":{{"startLine":{line},"startColumn":{column}}}}}}}]}}"#,
+            json_string(&if *applied {
+                format!("fixed: {message}")
+            } else {
+                format!("skipped, since it overlaps another fix: {message}")
+            }),
+            json_string(path)
+        ),
+        FixNote::Rejected {
+            errors_before,
+            errors_after,
+        } => format!(
+            r#"{{"level":"warning","message":{{"text":{}}}}}"#,
+            json_string(&format!(
+                "the fixes for {path} were not applied, since the file had {errors_after} errors with them instead of {errors_before}"
+            ))
+        ),
+    }
+}
+
+fn sarif_level(level: DiagnosticLevel) -> &'static str {
+    match level {
+        DiagnosticLevel::Error => "error",
+        DiagnosticLevel::Warning => "warning",
+        DiagnosticLevel::Note => "note",
+    }
+}
+
+fn sarif_region(source: &SourceHolder<'_>, span: Span) -> String {
+    let (start_line, start_column) = position(source, span.begin);
+    let (end_line, end_column) = position(source, span.end);
+    format!(
+        r#"{{"startLine":{start_line},"startColumn":{start_column},"endLine":{end_line},"endColumn":{end_column},"byteOffset":{},"byteLength":{}}}"#,
+        span.begin,
+        span.end - span.begin
+    )
+}
+
+fn sarif_location(path: &str, source: &SourceHolder<'_>, span: Span) -> String {
+    format!(
+        r#"{{"artifactLocation":{{"uri":{}}},"region":{}}}"#,
+        json_string(path),
+        sarif_region(source, span)
+    )
+}
+
+fn sarif_result(path: &str, source: &SourceHolder<'_>, diagnostic: &Diagnostic) -> String {
+    let mut result = String::new();
+    let rule_index = DIAGNOSTIC_CODES
+        .iter()
+        .position(|code| code.id == diagnostic.id);
+    write!(result, r#"{{"ruleId":"E{:04}","#, diagnostic.id).unwrap();
+    if let Some(rule_index) = rule_index {
+        write!(result, r#""ruleIndex":{rule_index},"#).unwrap();
+    }
+    write!(
+        result,
+        r#""level":"{}","message":{{"text":{}}},"locations":[{{"physicalLocation":{}}}]"#,
+        sarif_level(diagnostic.level),
+        // SARIF has no notes, so they go on the lines after the message
+        json_string(
+            &std::iter::once(diagnostic.message)
+                .chain(diagnostic.notes.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+        sarif_location(path, source, diagnostic.primary)
+    )
+    .unwrap();
+
+    let related = diagnostic
+        .highlights
+        .iter()
+        .filter(|highlight| !highlight.is_primary)
+        .enumerate()
+        .map(|(id, highlight)| {
+            format!(
+                r#"{{"id":{id},"physicalLocation":{},"message":{{"text":{}}}}}"#,
+                sarif_location(path, source, highlight.span),
+                json_string(highlight.label.unwrap_or(diagnostic.message))
+            )
+        })
+        .collect::<Vec<_>>();
+    if !related.is_empty() {
+        write!(result, r#","relatedLocations":[{}]"#, related.join(",")).unwrap();
+    }
+
+    let fixes = diagnostic
+        .suggestions
+        .iter()
+        .filter_map(|suggestion| {
+            let span = suggestion.replacement_span?;
+            if suggestion.applicability == Applicability::Placeholder {
+                return None;
+            }
+            Some(format!(
+                r#"{{"description":{{"text":{}}},"artifactChanges":[{{"artifactLocation":{{"uri":{}}},"replacements":[{{"deletedRegion":{},"insertedContent":{{"text":{}}}}}]}}]}}"#,
+                json_string(suggestion.message),
+                json_string(path),
+                sarif_region(source, span),
+                json_string(suggestion.replacement_text.unwrap_or_default())
+            ))
+        })
+        .collect::<Vec<_>>();
+    if !fixes.is_empty() {
+        write!(result, r#","fixes":[{}]"#, fixes.join(",")).unwrap();
+    }
+    result.push('}');
+    result
+}
+
+/// A SARIF log of one run over the file `path`. Every code is listed as a
+/// rule, so that code scanning can show the explanations. The notes become
+/// notifications of the invocation.
+pub fn sarif_log(
+    path: &str,
+    source: &SourceHolder<'_>,
+    diagnostics: &[Diagnostic],
+    notes: &[FixNote],
+) -> String {
+    let rules = DIAGNOSTIC_CODES
+        .iter()
+        .map(|code| {
+            format!(
+                r#"{{"id":"{}","name":{},"shortDescription":{{"text":{}}},"helpUri":"{EXPLANATION_URI}/{}/","defaultConfiguration":{{"level":"{}"}}}}"#,
+                code.code(),
+                json_string(code.name),
+                json_string(code.message),
+                code.code().to_lowercase(),
+                sarif_level(code.level)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let results = diagnostics
+        .iter()
+        .map(|diagnostic| sarif_result(path, source, diagnostic))
+        .collect::<Vec<_>>()
+        .join(",");
+    let invocations = if notes.is_empty() {
+        String::new()
+    } else {
+        let notifications = notes
+            .iter()
+            .map(|note| sarif_notification(path, note))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            r#""invocations":[{{"executionSuccessful":true,"toolExecutionNotifications":[{notifications}]}}],"#
+        )
+    };
+    format!(
+        r#"{{"$schema":"{SARIF_SCHEMA}","version":"{SARIF_VERSION}","runs":[{{"tool":{{"driver":{{"name":"{TOOL_NAME}","informationUri":"{TOOL_URI}","version":"{}","rules":[{rules}]}}}},{invocations}"columnKind":"unicodeCodePoints","results":[{results}]}}]}}"#,
+        env!("CARGO_PKG_VERSION")
+    )
+}
@@ -0,0 +1,115 @@
+//! Applies the machine-applicable suggestions of a file, for `--fix`.
+//!
+//! The fixes are applied all at once to the source they were made for, so
+//! fixes which touch the same text are left out. The fixed source is checked
+//! again, and it is given up if it has more errors than before.
+
+use crate::compiler::span::Span;
+use crate::diagnostic::{Applicability, Diagnostic, DiagnosticLevel};
+
+/// A machine-applicable suggestion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fix {
+    /// The id of the diagnostic the suggestion is from.
+    pub id: u32,
+    pub message: &'static str,
+    pub span: Span,
+    pub replacement: &'static str,
+}
+
+#[derive(Debug)]
+pub struct Fixed {
+    pub source: String,
+    /// The fixes which were applied, in the order of the source.
+    pub applied: Vec<Fix>,
+    /// The fixes which were left out since they overlap an applied one.
+    pub conflicting: Vec<Fix>,
+}
+
+/// The fixed source had more errors than the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixErr {
+    pub errors_before: usize,
+    pub errors_after: usize,
+}
+
+fn error_count(diagnostics: &[Diagnostic]) -> usize {
+    diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.level == DiagnosticLevel::Error)
+        .count()
+}
+
+/// The suggestions of `diagnostics` which can be applied without asking.
+pub fn machine_applicable_fixes(diagnostics: &[Diagnostic]) -> Vec<Fix> {
+    diagnostics
+        .iter()
+        .flat_map(|diagnostic| {
+            diagnostic.suggestions.iter().filter_map(|suggestion| {
+                if suggestion.applicability != Applicability::MachineApplicable {
+                    return None;
+                }
+                Some(Fix {
+                    id: diagnostic.id,
+                    message: suggestion.message,
+                    span: suggestion.replacement_span?,
+                    replacement: suggestion.replacement_text?,
+                })
+            })
+        })
+        .collect()
+}
+
+/// Whether `later`, which doesn't start before `earlier`, touches its text.
+/// Two insertions at the same place overlap too, since either order could
+/// be meant.
+fn overlaps(earlier: Fix, later: Fix) -> bool {
+    later.span.begin < earlier.span.end || later.span.begin == earlier.span.begin
+}
+
+/// Applies every fix which doesn't overlap one before it. The same fix
+/// suggested twice is applied once.
+pub fn apply_fixes(source: &str, mut fixes: Vec<Fix>) -> Fixed {
+    fixes.sort_by_key(|fix| (fix.span.begin, fix.span.end));
+    fixes.dedup();
+    let mut applied: Vec<Fix> = Vec::new();
+    let mut conflicting = Vec::new();
+    for fix in fixes {
+        match applied.last() {
+            Some(&earlier) if overlaps(earlier, fix) => conflicting.push(fix),
+            _ => applied.push(fix),
+        }
+    }
+
+    let mut fixed = String::with_capacity(source.len());
+    let mut copied = 0;
+    for fix in &applied {
+        fixed.push_str(&source[copied..fix.span.begin as usize]);
+        fixed.push_str(fix.replacement);
+        copied = fix.span.end as usize;
+    }
+    fixed.push_str(&source[copied..]);
+    Fixed {
+        source: fixed,
+        applied,
+        conflicting,
+    }
+}
+
+/// Fixes `source`, whose diagnostics `check` reports.
+pub fn fix(source: &str, check: impl Fn(&str) -> Vec<Diagnostic>) -> Result<Fixed, FixErr> {
+    let diagnostics = check(source);
+    let fixed = apply_fixes(source, machine_applicable_fixes(&diagnostics));
+    if fixed.applied.is_empty() {
+        return Ok(fixed);
+    }
+    let errors_before = error_count(&diagnostics);
+    let errors_after = error_count(&check(&fixed.source));
+    if errors_after > errors_before {
+        return Err(FixErr {
+            errors_before,
+            errors_after,
+        });
+    }
+    Ok(fixed)
+}
@@ -1,7 +1,13 @@
 pub mod converter;
 pub mod diagnotice_patterns;
+pub mod emitter;
+pub mod fixer;
+pub mod render;
 pub mod stream;
 
+#[cfg(test)]
+mod tests;
+
 use std::fmt::Display;
 
 use crate::compiler::span::Span;
@@ -18,8 +24,31 @@ pub struct Suggestion<'ms> {
     pub message: &'ms str,
     pub replacement_span: Option<Span>,
     pub replacement_text: Option<&'ms str>,
+    pub applicability: Applicability,
+}
+
+/// How sure a suggestion is, which decides whether `--fix` applies it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Applicability {
+    /// The replacement is what was meant, so it can be applied without asking.
+    MachineApplicable,
+    /// The replacement may not be what was meant, or there is no replacement.
+    MaybeIncorrect,
+    /// The replacement has parts which have to be filled in, like `<name>`.
+    Placeholder,
+}
+
+impl Display for Applicability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Applicability::MachineApplicable => write!(f, "machine-applicable"),
+            Applicability::MaybeIncorrect => write!(f, "maybe-incorrect"),
+            Applicability::Placeholder => write!(f, "placeholder"),
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DiagnosticLevel {
     Error,
     Warning,
@@ -42,6 +71,8 @@ pub struct Diagnostic {
     pub primary: Span,
     pub level: DiagnosticLevel,
     pub highlights: Vec<Highlight<'static>>,
+    /// Further details, like which tokens were expected.
+    pub notes: Vec<String>,
     pub suggestions: Vec<Suggestion<'static>>,
 }
 
@@ -64,6 +95,7 @@ impl Default for Diagnostic {
             primary: Span::new(0, 0),
             level: DiagnosticLevel::Error,
             highlights: Vec::new(),
+            notes: Vec::new(),
             suggestions: Vec::new(),
         }
     }
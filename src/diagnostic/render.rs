@@ -0,0 +1,102 @@
+//! Renders diagnostics for people, with the lines they point at:
+//!
+//! ```text
+//! error[E0005]: annotation is only allowed on functions
+//!  --> src/main.hyt:3:2
+//!   |
+//! 3 | @MockNetwork;
+//!   |  ^^^^^^^^^^^
+//!   |
+//!   = help: move the annotation to an item it is allowed on
+//! ```
+
+use std::fmt::Write;
+
+use crate::compiler::source_holder::SourceHolder;
+use crate::diagnostic::{Diagnostic, Highlight};
+
+/// Renders `diagnostic`, which was reported in the file `path` with the
+/// source `source`. The result ends with an empty line, which separates it
+/// from the next diagnostic.
+pub fn render(diagnostic: &Diagnostic, path: &str, source: &SourceHolder<'_>) -> String {
+    let mut out = String::new();
+    let (line, column) = source.resolve_position(diagnostic.primary.begin);
+    writeln!(
+        out,
+        "{}[E{:04}]: {}",
+        diagnostic.level, diagnostic.id, diagnostic.message
+    )
+    .unwrap();
+
+    let primary = [Highlight {
+        span: diagnostic.primary,
+        label: None,
+        is_primary: true,
+    }];
+    let highlights = if diagnostic.highlights.is_empty() {
+        &primary[..]
+    } else {
+        &diagnostic.highlights[..]
+    };
+    let mut lines = highlights
+        .iter()
+        .map(|highlight| (source.resolve_position(highlight.span.begin).0, highlight))
+        .collect::<Vec<_>>();
+    lines.sort_by_key(|(line, highlight)| (*line, highlight.span.begin));
+    let last_line = lines.last().map_or(line, |(line, _)| *line).max(line);
+    let gutter = " ".repeat(last_line.to_string().len());
+
+    writeln!(out, "{gutter}--> {path}:{line}:{column}").unwrap();
+    writeln!(out, "{gutter} |").unwrap();
+    let mut previous = None;
+    for (line, highlight) in lines {
+        let text = source.line(line);
+        if previous != Some(line) {
+            if previous.is_some_and(|previous| previous + 1 < line) {
+                writeln!(out, "...").unwrap();
+            }
+            writeln!(out, "{line:>width$} | {text}", width = gutter.len()).unwrap();
+            previous = Some(line);
+        }
+        write_underline(&mut out, &gutter, text, highlight, source);
+    }
+    writeln!(out, "{gutter} |").unwrap();
+
+    for note in &diagnostic.notes {
+        writeln!(out, "{gutter} = note: {note}").unwrap();
+    }
+    for suggestion in &diagnostic.suggestions {
+        write!(out, "{gutter} = help: {}", suggestion.message).unwrap();
+        if let Some(replacement) = suggestion.replacement_text {
+            write!(out, ": `{replacement}`").unwrap();
+        }
+        out.push('\n');
+    }
+    out.push('\n');
+    out
+}
+
+/// Underlines `highlight` on its first line, which is `text`. Spans over
+/// several lines are underlined up to the end of the first one.
+fn write_underline(
+    out: &mut String,
+    gutter: &str,
+    text: &str,
+    highlight: &Highlight<'_>,
+    source: &SourceHolder<'_>,
+) {
+    let (_, column) = source.resolve_position(highlight.span.begin);
+    // columns count bytes, the underline is indented by characters
+    let start = text
+        .get(..column - 1)
+        .map_or(text.chars().count(), |before| before.chars().count());
+    let span_len = source.get_snippet(highlight.span).chars().count();
+    let rest = text.chars().count().saturating_sub(start);
+    let marker = if highlight.is_primary { "^" } else { "-" };
+    let underline = marker.repeat(span_len.min(rest).max(1));
+    write!(out, "{gutter} | {}{underline}", " ".repeat(start)).unwrap();
+    if let Some(label) = highlight.label {
+        write!(out, " {label}").unwrap();
+    }
+    out.push('\n');
+}
@@ -0,0 +1,209 @@
+use crate::compiler::source_holder::SourceHolder;
+use crate::compiler::span::Span;
+use crate::diagnostic::emitter::{
+    json_line, json_note, sarif_log, EmitterDiagnosticStream, ErrorFormat, FixNote, SARIF_VERSION,
+};
+use crate::diagnostic::fixer::Fix;
+use crate::diagnostic::stream::DiagnosticStream;
+use crate::diagnostic::{Applicability, Diagnostic, DiagnosticLevel, Highlight, Suggestion};
+use crate::parser::parse_module;
+
+const SOURCE: &str = "extern fn puts();\n@MockNetwork;\nextern fn main();\n";
+
+fn emit(format: ErrorFormat, source: &str) -> (String, usize) {
+    let (_, diagnostics) = parse_module(source);
+    let mut emitter = EmitterDiagnosticStream::new(format, "main.hyt", source, Vec::new());
+    for diagnostic in diagnostics {
+        emitter.pour(diagnostic, &());
+    }
+    let errors = emitter.error_count();
+    (
+        String::from_utf8(emitter.finish().unwrap()).unwrap(),
+        errors,
+    )
+}
+
+#[test]
+fn test_json_lines() {
+    let (emitted, errors) = emit(ErrorFormat::Json, SOURCE);
+    assert_eq!(errors, 1);
+    assert_eq!(emitted.lines().count(), 1);
+    assert_eq!(
+        emitted,
+        concat!(
+            r#"{"code":"E0005","level":"error","message":"annotation is only allowed on functions","file":"main.hyt","#,
+            r#""span":{"file":"main.hyt","byte_start":18,"byte_end":31,"line_start":2,"column_start":1,"line_end":2,"column_end":14},"#,
+            r#""highlights":[{"span":{"file":"main.hyt","byte_start":18,"byte_end":31,"line_start":2,"column_start":1,"line_end":2,"column_end":14},"label":null,"is_primary":true}],"#,
+            r#""notes":[],"suggestions":[{"message":"move the annotation to an item it is allowed on","span":null,"replacement":null,"applicability":"maybe-incorrect"}]}"#,
+            "\n"
+        )
+    );
+}
+
+#[test]
+fn test_json_line_counts_characters() {
+    let source = "fn ü()\n\"ab\ncd\"\n";
+    let holder = SourceHolder::from_source(source);
+    let diagnostic = Diagnostic {
+        id: 12,
+        message: "a \"quoted\" message",
+        primary: Span::new(3, 5),
+        level: DiagnosticLevel::Warning,
+        highlights: vec![Highlight {
+            span: Span::new(8, 15),
+            label: Some("over two lines"),
+            is_primary: false,
+        }],
+        notes: Vec::new(),
+        suggestions: vec![Suggestion {
+            message: "rename it",
+            replacement_span: Some(Span::new(3, 5)),
+            replacement_text: Some("u"),
+            applicability: Applicability::MachineApplicable,
+        }],
+    };
+    assert_eq!(
+        json_line("a\\b.hyt", &holder, &diagnostic),
+        concat!(
+            r#"{"code":"E0012","level":"warning","message":"a \"quoted\" message","file":"a\\b.hyt","#,
+            r#""span":{"file":"a\\b.hyt","byte_start":3,"byte_end":5,"line_start":1,"column_start":4,"line_end":1,"column_end":5},"#,
+            r#""highlights":[{"span":{"file":"a\\b.hyt","byte_start":8,"byte_end":15,"line_start":2,"column_start":1,"line_end":3,"column_end":4},"label":"over two lines","is_primary":false}],"#,
+            r#""notes":[],"suggestions":[{"message":"rename it","span":{"file":"a\\b.hyt","byte_start":3,"byte_end":5,"line_start":1,"column_start":4,"line_end":1,"column_end":5},"replacement":"u","applicability":"machine-applicable"}]}"#
+        )
+    );
+}
+
+#[test]
+fn test_human_is_rendered() {
+    let (emitted, _) = emit(ErrorFormat::Human, SOURCE);
+    assert!(emitted
+        .starts_with("error[E0005]: annotation is only allowed on functions\n --> main.hyt:2:1\n"));
+}
+
+#[test]
+fn test_sarif_log() {
+    let (emitted, errors) = emit(ErrorFormat::Sarif, SOURCE);
+    assert_eq!(errors, 1);
+    assert_eq!(emitted.lines().count(), 1);
+    assert!(emitted.contains(&format!(r#""version":"{SARIF_VERSION}""#)));
+    assert!(emitted.contains(r#""id":"E0005","name":"annotation_target""#));
+    assert!(emitted.contains(
+        r#""helpUri":"https://sonneko.github.io/hydent-lang/reference/diagnostics/e0005/""#
+    ));
+    assert!(emitted.contains(concat!(
+        r#""results":[{"ruleId":"E0005","ruleIndex":4,"level":"error","#,
+        r#""message":{"text":"annotation is only allowed on functions"},"#,
+        r#""locations":[{"physicalLocation":{"artifactLocation":{"uri":"main.hyt"},"#,
+        r#""region":{"startLine":2,"startColumn":1,"endLine":2,"endColumn":14,"byteOffset":18,"byteLength":13}}}]}]"#
+    )));
+}
+
+#[test]
+fn test_sarif_log_without_results() {
+    let (emitted, errors) = emit(ErrorFormat::Sarif, "fn main() {}\n");
+    assert_eq!(errors, 0);
+    assert!(emitted.ends_with("\"results\":[]}]}\n"));
+}
+
+#[test]
+fn test_sarif_fixes_and_related_locations() {
+    let source = "fn a()\nfn a()\n";
+    let holder = SourceHolder::from_source(source);
+    let diagnostic = Diagnostic {
+        id: 7,
+        message: "example",
+        primary: Span::new(10, 11),
+        level: DiagnosticLevel::Error,
+        highlights: vec![
+            Highlight {
+                span: Span::new(3, 4),
+                label: Some("first here"),
+                is_primary: false,
+            },
+            Highlight {
+                span: Span::new(10, 11),
+                label: None,
+                is_primary: true,
+            },
+        ],
+        notes: Vec::new(),
+        suggestions: vec![
+            Suggestion {
+                message: "rename it",
+                replacement_span: Some(Span::new(10, 11)),
+                replacement_text: Some("b"),
+                applicability: Applicability::MachineApplicable,
+            },
+            Suggestion {
+                message: "think about it",
+                replacement_span: None,
+                replacement_text: None,
+                applicability: Applicability::MaybeIncorrect,
+            },
+        ],
+    };
+    let log = sarif_log("main.hyt", &holder, &[diagnostic], &[]);
+    assert!(!log.contains("invocations"));
+    assert!(log.contains(concat!(
+        r#""relatedLocations":[{"id":0,"physicalLocation":{"artifactLocation":{"uri":"main.hyt"},"#,
+        r#""region":{"startLine":1,"startColumn":4,"endLine":1,"endColumn":5,"byteOffset":3,"byteLength":1}},"#,
+        r#""message":{"text":"first here"}}]"#
+    )));
+    assert!(log.contains(concat!(
+        r#""fixes":[{"description":{"text":"rename it"},"artifactChanges":[{"artifactLocation":{"uri":"main.hyt"},"#,
+        r#""replacements":[{"deletedRegion":{"startLine":2,"startColumn":4,"endLine":2,"endColumn":5,"byteOffset":10,"byteLength":1},"#,
+        r#""insertedContent":{"text":"b"}}]}]}]"#
+    )));
+}
+
+fn semicolon_fix() -> Fix {
+    Fix {
+        id: 0,
+        message: "add a semicolon",
+        span: Span::new(26, 26),
+        replacement: ";",
+    }
+}
+
+#[test]
+fn test_json_fix_note() {
+    let holder = SourceHolder::from_source(SOURCE);
+    let note = FixNote::new(&holder, &semicolon_fix(), true);
+    assert_eq!(
+        json_note("main.hyt", &note),
+        r#"{"fix":{"file":"main.hyt","message":"add a semicolon","replacement":";","line":2,"column":9,"applied":true}}"#
+    );
+    let rejected = FixNote::Rejected {
+        errors_before: 1,
+        errors_after: 2,
+    };
+    assert_eq!(
+        json_note("main.hyt", &rejected),
+        r#"{"fix_rejected":{"file":"main.hyt","errors_before":1,"errors_after":2}}"#
+    );
+}
+
+#[test]
+fn test_fix_notes_follow_the_format() {
+    let holder = SourceHolder::from_source(SOURCE);
+    let note = FixNote::new(&holder, &semicolon_fix(), false);
+
+    let mut human =
+        EmitterDiagnosticStream::new(ErrorFormat::Human, "main.hyt", SOURCE, Vec::new());
+    human.note(&note);
+    let human = String::from_utf8(human.finish().unwrap()).unwrap();
+    assert_eq!(
+        human,
+        "skipped main.hyt:2:9: add a semicolon, since it overlaps another fix\n"
+    );
+
+    let mut sarif =
+        EmitterDiagnosticStream::new(ErrorFormat::Sarif, "main.hyt", SOURCE, Vec::new());
+    sarif.note(&note);
+    let sarif = String::from_utf8(sarif.finish().unwrap()).unwrap();
+    assert!(sarif.contains(concat!(
+        r#""invocations":[{"executionSuccessful":true,"toolExecutionNotifications":[{"level":"note","#,
+        r#""message":{"text":"skipped, since it overlaps another fix: add a semicolon"},"#,
+        r#""locations":[{"physicalLocation":{"artifactLocation":{"uri":"main.hyt"},"region":{"startLine":2,"startColumn":9}}}]}]}]"#
+    )));
+}
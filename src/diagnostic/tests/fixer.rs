@@ -0,0 +1,105 @@
+use crate::compiler::span::Span;
+use crate::diagnostic::fixer::{apply_fixes, fix, machine_applicable_fixes, Fix, FixErr};
+use crate::diagnostic::{Applicability, Diagnostic, Suggestion};
+use crate::parser::parse_module;
+
+fn fix_at(begin: usize, end: usize, replacement: &'static str) -> Fix {
+    Fix {
+        id: 0,
+        message: "example",
+        span: Span::new(begin, end),
+        replacement,
+    }
+}
+
+#[test]
+fn test_fix_adds_missing_semicolons() {
+    let source = "extern fn puts()\nfn main() {}\n";
+    let fixed = fix(source, |source| parse_module(source).1).unwrap();
+    assert_eq!(fixed.source, "extern fn puts();\nfn main() {}\n");
+    assert_eq!(fixed.applied.len(), 1);
+    assert_eq!(fixed.applied[0].span, Span::new(16, 16));
+    assert!(fixed.conflicting.is_empty());
+    assert!(parse_module(&fixed.source).1.is_empty());
+}
+
+#[test]
+fn test_fix_leaves_valid_source() {
+    let source = "extern fn puts();\nfn main() {}\n";
+    let fixed = fix(source, |source| parse_module(source).1).unwrap();
+    assert_eq!(fixed.source, source);
+    assert!(fixed.applied.is_empty());
+}
+
+#[test]
+fn test_only_machine_applicable_suggestions_are_fixes() {
+    let suggestion = |applicability, replacement_text| Suggestion {
+        message: "example",
+        replacement_span: Some(Span::new(0, 1)),
+        replacement_text,
+        applicability,
+    };
+    let diagnostic = Diagnostic {
+        suggestions: vec![
+            suggestion(Applicability::MaybeIncorrect, Some("a")),
+            suggestion(Applicability::Placeholder, Some("<name>")),
+            suggestion(Applicability::MachineApplicable, None),
+            suggestion(Applicability::MachineApplicable, Some("b")),
+        ],
+        ..Diagnostic::default()
+    };
+    assert_eq!(
+        machine_applicable_fixes(&[diagnostic]),
+        vec![fix_at(0, 1, "b")]
+    );
+}
+
+#[test]
+fn test_overlapping_fixes_are_left_out() {
+    let fixed = apply_fixes(
+        "0123456789",
+        vec![
+            fix_at(6, 6, "|"),
+            fix_at(2, 4, "ab"),
+            fix_at(3, 5, "cd"),
+            fix_at(6, 6, "|"),
+            fix_at(6, 6, "&"),
+            fix_at(4, 4, "e"),
+        ],
+    );
+    assert_eq!(fixed.source, "01abe45|6789");
+    assert_eq!(
+        fixed.applied,
+        vec![fix_at(2, 4, "ab"), fix_at(4, 4, "e"), fix_at(6, 6, "|")]
+    );
+    assert_eq!(
+        fixed.conflicting,
+        vec![fix_at(3, 5, "cd"), fix_at(6, 6, "&")]
+    );
+}
+
+#[test]
+fn test_fix_is_given_up_when_it_adds_errors() {
+    let check = |source: &str| {
+        let mut diagnostics = vec![Diagnostic {
+            suggestions: vec![Suggestion {
+                message: "example",
+                replacement_span: Some(Span::new(0, 0)),
+                replacement_text: Some("x"),
+                applicability: Applicability::MachineApplicable,
+            }],
+            ..Diagnostic::default()
+        }];
+        if source.starts_with('x') {
+            diagnostics.extend([Diagnostic::default(), Diagnostic::default()]);
+        }
+        diagnostics
+    };
+    assert_eq!(
+        fix("a", check).unwrap_err(),
+        FixErr {
+            errors_before: 1,
+            errors_after: 3,
+        }
+    );
+}
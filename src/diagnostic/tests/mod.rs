@@ -0,0 +1,3 @@
+mod emitter;
+mod fixer;
+mod render;
@@ -0,0 +1,77 @@
+use crate::compiler::source_holder::SourceHolder;
+use crate::compiler::span::Span;
+use crate::diagnostic::render::render;
+use crate::diagnostic::{Applicability, Diagnostic, DiagnosticLevel, Highlight, Suggestion};
+use crate::parser::parse_module;
+
+#[test]
+fn test_render_points_at_the_annotation() {
+    let source = "extern fn puts();\n@MockNetwork;\nextern fn main();\n";
+    let (ast, diagnostics) = parse_module(source);
+    let rendered = diagnostics
+        .iter()
+        .map(|diagnostic| render(diagnostic, "main.hyt", ast.source_holder()))
+        .collect::<String>();
+    assert_eq!(
+        rendered,
+        "error[E0005]: annotation is only allowed on functions
+ --> main.hyt:2:1
+  |
+2 | @MockNetwork;
+  | ^^^^^^^^^^^^^
+  |
+  = help: move the annotation to an item it is allowed on
+
+"
+    );
+}
+
+#[test]
+fn test_render_labels_and_replacements() {
+    let source = "fn a() {}\n\n\n\n\n\n\n\n\nfn ü() {}\n";
+    let line_starts = source
+        .match_indices('\n')
+        .map(|(at, _)| at as u32 + 1)
+        .filter(|&at| (at as usize) < source.len());
+    let holder = SourceHolder::new(source, [0].into_iter().chain(line_starts).collect());
+    let diagnostic = Diagnostic {
+        id: 12,
+        message: "example",
+        primary: Span::new(21, 23),
+        level: DiagnosticLevel::Warning,
+        highlights: vec![
+            Highlight {
+                span: Span::new(3, 4),
+                label: Some("first"),
+                is_primary: false,
+            },
+            Highlight {
+                span: Span::new(21, 23),
+                label: Some("second"),
+                is_primary: true,
+            },
+        ],
+        notes: Vec::new(),
+        suggestions: vec![Suggestion {
+            message: "rename it",
+            replacement_span: Some(Span::new(21, 23)),
+            replacement_text: Some("u"),
+            applicability: Applicability::MachineApplicable,
+        }],
+    };
+    assert_eq!(
+        render(&diagnostic, "a.hyt", &holder),
+        "warning[E0012]: example
+  --> a.hyt:10:4
+   |
+ 1 | fn a() {}
+   |    - first
+...
+10 | fn ü() {}
+   |    ^ second
+   |
+   = help: rename it: `u`
+
+"
+    );
+}
@@ -0,0 +1,181 @@
+//! Annotation parser.
+//!
+//! An annotation is written before an item, like `@Link "math_lib";`, and
+//! takes a list of arguments separated by spaces:
+//!
+//! ```text
+//! Annotation := "@" Identifier { Argument } ";"
+//! Argument   := Value | Identifier "=" Value
+//! Value      := Literal | Identifier
+//! ```
+//!
+//! The annotations of an item are kept in `Item::annotations`. Items are
+//! parsed here instead of by the generated parser, which would report the
+//! annotations at the end of a module as a missing item. Which annotations
+//! exist and where they may be used is checked afterwards by
+//! `parser::annotation_registry`.
+
+use crate::compiler::arena::ArenaIter;
+use crate::compiler::span::Span;
+use crate::diagnostic::stream::DiagnosticStream;
+use crate::parser::ast::{Identifier, Item, TopLevelStatement};
+use crate::parser::ast_node::ASTNode;
+use crate::parser::base_parser::BaseParser;
+use crate::parser::errors::{AnnotationErr, AnnotationErrKind, IParseErr, ParseErr};
+use crate::parser::generated_parser::GeneratedParser;
+use crate::parser::parse::Parser;
+use crate::parser::tracer::Tracer;
+use crate::tokenizer::tokens::{Delimiter, Literal, Operator, Token};
+
+#[derive(Debug, Copy, Clone, std::hash::Hash, PartialEq, Eq)]
+pub enum Annotation {
+    Parsed {
+        name: Identifier,
+        arguments: ArenaIter<AnnotationArgument>,
+        /// From the `@` to the `;`.
+        span: Span,
+    },
+    Invalid,
+}
+
+#[derive(Debug, Copy, Clone, std::hash::Hash, PartialEq, Eq)]
+pub enum AnnotationArgument {
+    Positional(AnnotationValue),
+    /// `key=value`
+    Named {
+        key: Identifier,
+        value: AnnotationValue,
+    },
+}
+
+#[derive(Debug, Copy, Clone, std::hash::Hash, PartialEq, Eq)]
+pub enum AnnotationValue {
+    Literal(Literal),
+    Identifier(Identifier),
+}
+
+impl<S: DiagnosticStream, TR: Tracer> Parser<'_, '_, '_, S, TR> {
+    /// Parses an item and the annotations before it, see `parse_annotations`.
+    pub(crate) fn parse_item(&mut self) -> Result<Item, ParseErr> {
+        let _guard = TR::trace(Item::ast_name());
+        let annotations = self.parse_annotations()?;
+        let declaration = self.parse_TopLevelStatement()?;
+        Ok(Item {
+            annotations,
+            declaration,
+        })
+    }
+
+    /// Parses the annotations before an item. An annotation which fails to
+    /// parse is reported and skipped up to its `;`. The annotations at the
+    /// end of a module annotate nothing, which is reported instead of the
+    /// missing item.
+    fn parse_annotations(&mut self) -> Result<ArenaIter<Annotation>, ParseErr> {
+        let mut annotations = Vec::new();
+        while self.peek::<0>() == Some(Token::Operator(Operator::At)) {
+            match self.parse_annotation() {
+                Ok(annotation) => annotations.push(annotation),
+                // a backtracked item has to fail instead of recovering,
+                // since its errors aren't reported
+                Err(err) if self.is_panic_or_backtrack_mode() => return Err(err),
+                Err(err) => {
+                    self.report_error(err);
+                    self.skip_annotation();
+                }
+            }
+        }
+        let dangling = matches!(
+            self.peek::<0>(),
+            None | Some(Token::EndOfFile | Token::Delimiter(Delimiter::RightBrace))
+        );
+        if dangling && !annotations.is_empty() {
+            if !self.is_panic_or_backtrack_mode() {
+                for annotation in annotations {
+                    if let Annotation::Parsed { span, .. } = annotation {
+                        let err = AnnotationErr {
+                            span,
+                            kind: AnnotationErrKind::Dangling,
+                        };
+                        self.diagnostic_stream.pour(err, &());
+                    }
+                }
+            }
+            return Err(ParseErr {
+                reported: true,
+                ..ParseErr::build(false, &[], self.enviroment())
+            });
+        }
+        Ok(self.ctx.ast_arena.alloc_iter(annotations.into_iter()))
+    }
+
+    pub fn parse_annotation(&mut self) -> Result<Annotation, ParseErr> {
+        let _guard = TR::trace("Annotation");
+        let begin = self.now_span().begin;
+        self.expect(&Token::Operator(Operator::At))?;
+        let name = self.parse_name()?;
+        let mut arguments = Vec::new();
+        while self.peek::<0>() != Some(Token::Delimiter(Delimiter::Semicolon)) {
+            arguments.push(self.parse_annotation_argument()?);
+        }
+        let end = self.now_span().end;
+        self.consume_token();
+        Ok(Annotation::Parsed {
+            name,
+            arguments: self.ctx.ast_arena.alloc_iter(arguments.into_iter()),
+            span: Span { begin, end },
+        })
+    }
+
+    fn parse_annotation_argument(&mut self) -> Result<AnnotationArgument, ParseErr> {
+        if let (Some(Token::Identifier(symbol)), Some(Token::Operator(Operator::Assignment))) =
+            (self.peek::<0>(), self.peek::<1>())
+        {
+            self.consume_token();
+            self.consume_token();
+            return Ok(AnnotationArgument::Named {
+                key: Identifier { symbol },
+                value: self.parse_annotation_value()?,
+            });
+        }
+        Ok(AnnotationArgument::Positional(
+            self.parse_annotation_value()?,
+        ))
+    }
+
+    fn parse_annotation_value(&mut self) -> Result<AnnotationValue, ParseErr> {
+        match self.peek::<0>() {
+            Some(Token::Literal(literal)) => {
+                self.consume_token();
+                Ok(AnnotationValue::Literal(literal))
+            }
+            Some(Token::Identifier(symbol)) => {
+                self.consume_token();
+                Ok(AnnotationValue::Identifier(Identifier { symbol }))
+            }
+            _ => Err(ParseErr::build(
+                true,
+                &[Token::Delimiter(Delimiter::Semicolon)],
+                self.enviroment(),
+            )),
+        }
+    }
+
+    /// Skips to after the `;` of a broken annotation, or to the annotation or
+    /// item after it if the `;` is missing.
+    fn skip_annotation(&mut self) {
+        loop {
+            let next = self.peek::<0>();
+            if next.is_none()
+                || next == Some(Token::EndOfFile)
+                || next == Some(Token::Operator(Operator::At))
+                || TopLevelStatement::is_first1_sets(&next)
+            {
+                break;
+            }
+            self.consume_token();
+            if next == Some(Token::Delimiter(Delimiter::Semicolon)) {
+                break;
+            }
+        }
+    }
+}
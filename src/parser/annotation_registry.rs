@@ -0,0 +1,201 @@
+//! The annotations the compiler knows.
+//!
+//! Every known annotation is described by an `AnnotationSpec`: the arguments
+//! it takes and the items it may annotate. `AnnotationRegistry::validate`
+//! checks the annotations of a module, and of the modules in it, against
+//! them. Unknown annotations are only warned about, so code written for a
+//! newer compiler still builds.
+
+use std::collections::HashMap;
+
+use crate::compiler::arena::{Arena, ArenaIter};
+use crate::compiler::symbol::Symbol;
+use crate::diagnostic::stream::DiagnosticStream;
+use crate::parser::annotation::{Annotation, AnnotationArgument, AnnotationValue};
+use crate::parser::ast::{Item, TopLevelStatement};
+use crate::parser::errors::{AnnotationErr, AnnotationErrKind};
+use crate::parser::Ast;
+use crate::tokenizer::tokens::Literal;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArgumentKind {
+    String,
+    Int,
+    Identifier,
+}
+
+impl ArgumentKind {
+    fn matches(self, argument: &AnnotationArgument) -> bool {
+        let AnnotationArgument::Positional(value) = argument else {
+            return false;
+        };
+        matches!(
+            (self, value),
+            (
+                ArgumentKind::String,
+                AnnotationValue::Literal(Literal::StringLiteral(_))
+            ) | (
+                ArgumentKind::Int,
+                AnnotationValue::Literal(
+                    Literal::IntegerLiteral(_) | Literal::DoubleIntegerLiteral(_)
+                )
+            ) | (ArgumentKind::Identifier, AnnotationValue::Identifier(_))
+        )
+    }
+}
+
+/// The items an annotation may be written before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnnotationTarget {
+    Any,
+    Function,
+    /// A function without parameters.
+    NullaryFunction,
+    /// A module with an `extern fn` directly in it.
+    ExternModule,
+}
+
+impl AnnotationTarget {
+    fn matches(self, item: &TopLevelStatement, arena: &Arena) -> bool {
+        match (self, item) {
+            (AnnotationTarget::Any, _) => true,
+            (AnnotationTarget::Function, TopLevelStatement::FunctionDeclaration(_)) => true,
+            (
+                AnnotationTarget::NullaryFunction,
+                TopLevelStatement::FunctionDeclaration(function),
+            ) => function.params().is_none(),
+            (AnnotationTarget::ExternModule, TopLevelStatement::ModuleDeclaration(module)) => {
+                module
+                    .declarations()
+                    .as_slice(arena)
+                    .iter()
+                    .any(|item| matches!(item.declaration(), TopLevelStatement::ExternFunction(_)))
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AnnotationSpec {
+    pub name: &'static str,
+    /// The kinds of the arguments, in order. Named arguments are not taken by
+    /// any known annotation.
+    pub arguments: &'static [ArgumentKind],
+    pub target: AnnotationTarget,
+}
+
+const fn annotation(
+    name: &'static str,
+    arguments: &'static [ArgumentKind],
+    target: AnnotationTarget,
+) -> AnnotationSpec {
+    AnnotationSpec {
+        name,
+        arguments,
+        target,
+    }
+}
+
+#[rustfmt::skip]
+pub const BUILTIN_ANNOTATIONS: &[AnnotationSpec] = &[
+    annotation("ModuleConfig", &[ArgumentKind::String, ArgumentKind::String], AnnotationTarget::Any),
+    annotation("Link", &[ArgumentKind::String], AnnotationTarget::ExternModule),
+    annotation("EntryPoint", &[], AnnotationTarget::NullaryFunction),
+    annotation("MockNetwork", &[], AnnotationTarget::Function),
+];
+
+pub struct AnnotationRegistry {
+    specs: HashMap<Symbol, AnnotationSpec>,
+}
+
+impl AnnotationRegistry {
+    /// A registry without any annotations.
+    pub fn empty() -> Self {
+        Self {
+            specs: HashMap::new(),
+        }
+    }
+
+    /// A registry of `BUILTIN_ANNOTATIONS`.
+    pub fn builtin() -> Self {
+        let mut registry = Self::empty();
+        for spec in BUILTIN_ANNOTATIONS {
+            registry.register(*spec);
+        }
+        registry
+    }
+
+    /// Adds `spec`, replacing a spec of the same name.
+    pub fn register(&mut self, spec: AnnotationSpec) {
+        self.specs.insert(Symbol::intern(spec.name), spec);
+    }
+
+    pub fn get(&self, name: Symbol) -> Option<&AnnotationSpec> {
+        self.specs.get(&name)
+    }
+
+    /// Reports every annotation of `ast` which is unknown, has the wrong
+    /// arguments or annotates the wrong item.
+    pub fn validate<S: DiagnosticStream>(&self, ast: &Ast<'_>, diagnostic_stream: &mut S) {
+        self.validate_items(*ast.root().declarations(), ast.arena(), diagnostic_stream);
+    }
+
+    fn validate_items<S: DiagnosticStream>(
+        &self,
+        items: ArenaIter<Item>,
+        arena: &Arena,
+        diagnostic_stream: &mut S,
+    ) {
+        for item in items.as_slice(arena) {
+            for annotation in item.annotations().as_slice(arena) {
+                let Annotation::Parsed {
+                    name,
+                    arguments,
+                    span,
+                } = *annotation
+                else {
+                    continue;
+                };
+                if let Some(kind) = self.check(name.symbol, arguments, item.declaration(), arena) {
+                    diagnostic_stream.pour(AnnotationErr { span, kind }, &());
+                }
+            }
+            if let TopLevelStatement::ModuleDeclaration(module) = item.declaration() {
+                self.validate_items(*module.declarations(), arena, diagnostic_stream);
+            }
+        }
+    }
+
+    fn check(
+        &self,
+        name: Symbol,
+        arguments: ArenaIter<AnnotationArgument>,
+        item: &TopLevelStatement,
+        arena: &Arena,
+    ) -> Option<AnnotationErrKind> {
+        let Some(spec) = self.get(name) else {
+            return Some(AnnotationErrKind::Unknown);
+        };
+        let arguments = arguments.as_slice(arena);
+        if arguments.len() != spec.arguments.len()
+            || !spec
+                .arguments
+                .iter()
+                .zip(arguments)
+                .all(|(kind, argument)| kind.matches(argument))
+        {
+            return Some(AnnotationErrKind::Arguments);
+        }
+        if !spec.target.matches(item, arena) {
+            return Some(AnnotationErrKind::Target(spec.target));
+        }
+        None
+    }
+}
+
+impl Default for AnnotationRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
@@ -1,6 +1,6 @@
-use crate::compiler::arena::ArenaBox;
+use crate::compiler::arena::{Arena, ArenaBox, ArenaIter};
 use crate::parser::errors::ParseErr;
-use crate::parser::generated_ast::ASTVisitor;
+use crate::parser::generated_ast::{ASTFolder, ASTVisitor, ASTVisitorMut};
 use crate::tokenizer::tokens::Token;
 
 pub trait ASTNode:
@@ -34,6 +34,50 @@ pub trait ASTNode:
 
 pub trait Node {
     fn accept<V: ASTVisitor>(&self, visitor: &mut V) -> V::ReturnType;
+
+    /// Visits this node in place. Its children live in `arena`.
+    fn accept_mut<V: ASTVisitorMut>(&mut self, visitor: &mut V, arena: &mut Arena);
+
+    /// Rebuilds this node with its children from `from` allocated in `to`.
+    fn fold<F: ASTFolder>(&self, folder: &mut F, from: &Arena, to: &Arena) -> Self;
+}
+
+/// Visits a copy of the node `node` points to and writes it back, so the
+/// visitor can use `arena` for the children of the node meanwhile.
+pub fn visit_boxed_mut<T: Copy>(
+    mut node: ArenaBox<T>,
+    arena: &mut Arena,
+    visit: impl FnOnce(&mut T, &mut Arena),
+) {
+    let mut value = *node.get(arena);
+    visit(&mut value, arena);
+    *node.get_mut(arena) = value;
+}
+
+/// Like `visit_boxed_mut`, for every node of `nodes`.
+pub fn visit_iter_mut<T: Copy>(
+    nodes: ArenaIter<T>,
+    arena: &mut Arena,
+    mut visit: impl FnMut(&mut T, &mut Arena),
+) {
+    for i in 0..nodes.len() {
+        let mut value = nodes.as_slice(arena)[i];
+        visit(&mut value, arena);
+        nodes.as_mut_slice(arena)[i] = value;
+    }
+}
+
+/// Folds every node of `nodes` from `from` and allocates the results in `to`.
+pub fn fold_iter<T: Copy>(
+    nodes: ArenaIter<T>,
+    from: &Arena,
+    to: &Arena,
+    fold: impl FnMut(&T) -> T,
+) -> ArenaIter<T> {
+    // folding a node can allocate its own children, so the items are only
+    // allocated once all of them are folded
+    let folded = nodes.as_slice(from).iter().map(fold).collect::<Vec<_>>();
+    to.alloc_iter(folded.into_iter())
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -58,6 +102,19 @@ impl TokenSet {
         }
     }
 
+    /// Every token, for the tokens which can follow the first one of a hand
+    /// written rule.
+    pub const fn all() -> Self {
+        Self {
+            keywords: u64::MAX,
+            operators: u64::MAX,
+            delimiter: u64::MAX,
+            literals: true,
+            identifier: true,
+            eof: true,
+        }
+    }
+
     pub const fn build_map(identifier: bool, literals: bool, eof: bool, tokens: &[Token]) -> Self {
         let mut keywords_bits = 0u64;
         let mut operators_bits = 0u64;
@@ -140,4 +197,4 @@ where
         N::get_error_situation(err)
     }
     type Target = N;
-}
\ No newline at end of file
+}
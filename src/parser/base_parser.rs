@@ -4,7 +4,7 @@ use crate::diagnostic::stream::DiagnosticStream;
 use crate::parser::ast_node::ASTNode;
 use crate::parser::errors::{IParseErr, ParseErr};
 use crate::parser::parse::Parser;
-use crate::parser::recovery::recover;
+use crate::parser::recovery::{recover, unexpected};
 use crate::parser::tracer::Tracer;
 use crate::tokenizer::tokens::Token;
 
@@ -36,6 +36,15 @@ pub trait BaseParser: Sized {
         parser_fn: impl FnMut(&mut Self) -> Result<T, Self::Error>,
     ) -> Result<T, Self::Error>;
 
+    /// Parses rule `T` at most once per token position.
+    ///
+    /// The generator uses it for the rules it has to backtrack over, see
+    /// `parser::memo`.
+    fn memoize<T: ASTNode>(
+        &mut self,
+        parser_fn: impl FnOnce(&mut Self) -> Result<T, Self::Error>,
+    ) -> Result<T, Self::Error>;
+
     fn enviroment(&self) -> Enviroment;
 
     fn now_span(&self) -> Span;
@@ -59,6 +68,10 @@ pub trait BaseParser: Sized {
 pub struct Enviroment {
     pub current: Token,
     pub span: Span,
+    /// The span of the token before `current`, if there is one.
+    pub previous: Option<Span>,
+    /// Whether `current` is on a later line than the token before it.
+    pub line_break_before: bool,
 }
 
 impl<S: DiagnosticStream, TR: Tracer> BaseParser for Parser<'_, '_, '_, S, TR> {
@@ -67,7 +80,8 @@ impl<S: DiagnosticStream, TR: Tracer> BaseParser for Parser<'_, '_, '_, S, TR> {
         &mut self,
         parser_fn: impl FnOnce(&mut Self) -> Result<T, Self::Error>,
     ) -> Result<ArenaBox<T>, Self::Error> {
-        Ok(self.ctx.ast_arena.alloc(parser_fn(self)?))
+        let node = self.nested(parser_fn)?;
+        Ok(self.ctx.ast_arena.alloc(node))
     }
 
     fn alloc<T: ASTNode>(&mut self, node: T) -> ArenaBox<T> {
@@ -79,7 +93,9 @@ impl<S: DiagnosticStream, TR: Tracer> BaseParser for Parser<'_, '_, '_, S, TR> {
     }
 
     fn consume_token(&mut self) -> Option<Token> {
-        self.tokens.next().map(|(token, _)| token)
+        let token = self.tokens.next();
+        TR::advance(self.tokens.position());
+        token.map(|(token, _)| token)
     }
 
     fn repeat<T: ASTNode>(
@@ -101,30 +117,58 @@ impl<S: DiagnosticStream, TR: Tracer> BaseParser for Parser<'_, '_, '_, S, TR> {
                     if !T::is_first2_sets(&self.peek::<1>()) {
                         break Ok(self.ctx.ast_arena.finish_iter_allocation::<T>());
                     }
-                    match self.backtrack(&mut parser_fn) {
+                    let backtracking = self.is_panic_or_backtrack_mode();
+                    match self.backtrack(|this| this.memoize(&mut parser_fn)) {
                         Ok(node) => {
                             self.ctx.ast_arena.alloc_iter_item(&node);
+                            continue;
                         }
-                        Err(err) => {
+                        Err(_) if backtracking => {
                             break Ok(self.ctx.ast_arena.finish_iter_allocation::<T>());
                         }
+                        // both lookahead tokens fit the node, so it is parsed
+                        // again below, to report why it failed and recover
+                        // from there
+                        Err(_) => {}
                     }
                 }
-                (true, false) => match parser_fn(self) {
-                    Ok(node) => {
-                        self.ctx.ast_arena.alloc_iter_item(&node);
-                    }
-                    Err(err) => {
-                        self.report_error(err);
-                        recover::<T, S, TR>(self);
-                    }
-                },
+                (true, false) => {}
                 (false, true) => {
                     break Ok(self.ctx.ast_arena.finish_iter_allocation::<T>());
                 }
+                // while backtracking the list just ends, so that the error is
+                // about what the rule after it expects, which it fails with
+                // at the same token
+                (false, false) if self.is_panic_or_backtrack_mode() => {
+                    break Ok(self.ctx.ast_arena.finish_iter_allocation::<T>());
+                }
                 (false, false) => {
-                    //TODO:  build error and report
+                    let err = unexpected(self, &mut parser_fn);
+                    self.report_error(err);
                     recover::<T, S, TR>(self);
+                    continue;
+                }
+            }
+
+            let start = self.tokens.position();
+            match parser_fn(self) {
+                Ok(node) => {
+                    self.ctx.ast_arena.alloc_iter_item(&node);
+                }
+                // a backtracked node has to fail instead of recovering,
+                // since its errors aren't reported
+                Err(err) if self.is_panic_or_backtrack_mode() => {
+                    self.ctx.ast_arena.abandon_iter_allocation();
+                    break Err(err);
+                }
+                Err(err) => {
+                    self.report_error(err);
+                    recover::<T, S, TR>(self);
+                    // a node which fails at its first token would otherwise
+                    // be tried again forever
+                    if self.tokens.position() == start {
+                        self.consume_token();
+                    }
                 }
             }
         }
@@ -153,7 +197,7 @@ impl<S: DiagnosticStream, TR: Tracer> BaseParser for Parser<'_, '_, '_, S, TR> {
     }
 
     fn report_error(&mut self, err: Self::Error) {
-        if !self.is_panic_or_backtrack_mode() {
+        if !self.is_panic_or_backtrack_mode() && !err.reported {
             self.diagnostic_stream.pour(err, &self.enviroment());
         }
     }
@@ -162,27 +206,56 @@ impl<S: DiagnosticStream, TR: Tracer> BaseParser for Parser<'_, '_, '_, S, TR> {
         &mut self,
         mut parser_fn: impl FnMut(&mut Self) -> Result<T, Self::Error>,
     ) -> Result<T, Self::Error> {
+        // a backtrack inside another one has to leave it backtracking
+        let backtracking = self.is_panic_or_backtrack_mode;
         self.is_panic_or_backtrack_mode = true;
         self.tokens.checkpoint();
+        TR::backtrack(self.tokens.position());
         let node = parser_fn(self);
         match node {
             Ok(_) => {
                 self.tokens.commit();
             }
             Err(_) => {
+                let from = self.tokens.position();
                 self.tokens.rollback();
+                TR::rollback(from, self.tokens.position());
             }
         }
-        self.is_panic_or_backtrack_mode = false;
+        self.is_panic_or_backtrack_mode = backtracking;
         node
     }
 
+    fn memoize<T: ASTNode>(
+        &mut self,
+        parser_fn: impl FnOnce(&mut Self) -> Result<T, Self::Error>,
+    ) -> Result<T, Self::Error> {
+        if !self.memo.is_enabled() {
+            return parser_fn(self);
+        }
+        let start = self.tokens.position();
+        if let Some((result, end)) = self.memo.get::<T>(start) {
+            TR::memo_hit(start);
+            self.tokens.seek(end);
+            TR::advance(end);
+            return result;
+        }
+        let cut_offs = self.nesting_cut_offs();
+        let result = parser_fn(self);
+        // a rule cut off by the nesting limit could parse from a shallower
+        // depth
+        if self.nesting_cut_offs() == cut_offs {
+            self.memo.insert(start, result, self.tokens.position());
+        }
+        result
+    }
+
     fn optional<T: ASTNode>(
         &mut self,
         mut parser_fn: impl FnMut(&mut Self) -> Result<T, Self::Error>,
     ) -> Option<T> {
         if T::is_first1_sets(&self.peek::<0>()) {
-            self.backtrack(parser_fn).ok()
+            self.backtrack(|this| this.memoize(&mut parser_fn)).ok()
         } else {
             None
         }
@@ -202,9 +275,19 @@ impl<S: DiagnosticStream, TR: Tracer> BaseParser for Parser<'_, '_, '_, S, TR> {
 
     fn enviroment(&self) -> Enviroment {
         // TODO: consider Environment structure
+        let span = self.now_span();
+        let previous = self.tokens.get_previous_span();
+        let line_break_before = previous.is_some_and(|previous| {
+            self.ctx
+                .source
+                .get(previous.end as usize..span.begin as usize)
+                .is_some_and(|between| between.contains('\n'))
+        });
         Enviroment {
             current: self.peek::<0>().unwrap_or(Token::EndOfFile),
-            span: self.now_span(),
+            span,
+            previous,
+            line_break_before,
         }
     }
 
@@ -1,21 +1,249 @@
 use crate::{
-    diagnostic::{converter::IntoDiagnostic, Diagnostic},
-    parser::errors::ParseErr,
-    tokenizer::errors::TokenizeErr,
+    compiler::span::Span,
+    diagnostic::diagnotice_patterns::{
+        ANNOTATION_ARGUMENTS, ANNOTATION_TARGET, CHAINED_OPERATORS, DANGLING_ANNOTATION,
+        INVALID_LITERAL, NESTING_TOO_DEEP, UNCLOSED, UNEXPECTED_TOKEN, UNKNOWN_ANNOTATION,
+        UNKNOWN_TOKEN,
+    },
+    diagnostic::{converter::IntoDiagnostic, Applicability, Diagnostic, Highlight, Suggestion},
+    parser::annotation_registry::AnnotationTarget,
+    parser::ast_node::ASTNode,
+    parser::base_parser::Enviroment,
+    parser::errors::{
+        AnnotationErr, AnnotationErrKind, ChainedOperatorErr, NestingTooDeepErr, ParseErr,
+    },
+    parser::generated_ast::TopLevelStatement,
+    tokenizer::errors::{TokenizeErr, TokenizeErrKind},
+    tokenizer::tokens::{Delimiter, Token},
 };
 
 impl IntoDiagnostic for ParseErr {
     type Reference = crate::parser::base_parser::Enviroment;
-    fn into_diagnostic(self, reference: &Self::Reference) -> crate::diagnostic::Diagnostic {
-        println!("{:?}", self);
-        Diagnostic::default()
+    fn into_diagnostic(self, _: &Self::Reference) -> crate::diagnostic::Diagnostic {
+        let label = (self.found.current == Token::EndOfFile).then_some("the file ends here");
+        // the `;` belongs right after the token before, not before the token
+        // found, which may be on the next line
+        let suggestions = match (self.expected, self.found.previous) {
+            ([Token::Delimiter(Delimiter::Semicolon)], Some(previous)) => vec![Suggestion {
+                message: "add a semicolon",
+                replacement_span: Some(Span {
+                    begin: previous.end,
+                    end: previous.end,
+                }),
+                replacement_text: Some(";"),
+                applicability: semicolon_applicability(&self.found),
+            }],
+            _ => Vec::new(),
+        };
+        Diagnostic {
+            id: UNEXPECTED_TOKEN.id,
+            message: UNEXPECTED_TOKEN.message,
+            primary: self.found.span,
+            level: UNEXPECTED_TOKEN.level,
+            highlights: vec![Highlight {
+                span: self.found.span,
+                label,
+                is_primary: true,
+            }],
+            notes: expected_note(self.expected, self.identifier)
+                .into_iter()
+                .collect(),
+            suggestions,
+        }
+    }
+}
+
+/// Lists what the parser expected instead of the token it found, like
+/// "expected one of `extern`, `fn`". `None` if it is not known.
+fn expected_note(expected: &[Token], identifier: bool) -> Option<String> {
+    let mut items = expected
+        .iter()
+        .map(|token| match token {
+            Token::Delimiter(delimiter) => format!("`{delimiter}`"),
+            Token::Keyword(keyword) => format!("`{keyword}`"),
+            Token::Operator(operator) => format!("`{operator}`"),
+            Token::EndOfFile => "the end of the file".to_string(),
+            token => token.to_string(),
+        })
+        .collect::<Vec<_>>();
+    if identifier {
+        items.push("an identifier".to_string());
+    }
+    match items.as_slice() {
+        [] => None,
+        [item] => Some(format!("expected {item}")),
+        items => Some(format!("expected one of {}", items.join(", "))),
+    }
+}
+
+/// A `;` is only certainly missing when the declaration ended, so when the
+/// next token is on a later line, starts the next item or ends the file.
+/// Otherwise the tokens around it are more likely wrong, like the `: T {}`
+/// of `extern fn f() : T {}` which a `;` after `)` wouldn't make right.
+fn semicolon_applicability(found: &Enviroment) -> Applicability {
+    let next = Some(found.current);
+    if found.line_break_before
+        || found.current == Token::EndOfFile
+        || TopLevelStatement::is_first1_sets(&next)
+    {
+        Applicability::MachineApplicable
+    } else {
+        Applicability::MaybeIncorrect
     }
 }
 
 impl IntoDiagnostic for TokenizeErr {
     type Reference = ();
     fn into_diagnostic(self, _: &()) -> crate::diagnostic::Diagnostic {
-        eprintln!("tokenize error: {:?}", self);
-        Diagnostic::default()
+        let (code, message) = match self.kind {
+            TokenizeErrKind::StringLiteralNotClosed => (UNCLOSED, "unclosed string literal"),
+            TokenizeErrKind::CharLiteralNotClosed => (UNCLOSED, "unclosed character literal"),
+            TokenizeErrKind::BlockCommentNotClosed => (UNCLOSED, "unclosed block comment"),
+            TokenizeErrKind::InvalidCharLiteral => (INVALID_LITERAL, "invalid character literal"),
+            TokenizeErrKind::InvalidIntegerLiteral => (INVALID_LITERAL, "invalid integer literal"),
+            TokenizeErrKind::InvalidFloatLiteral => (INVALID_LITERAL, "invalid float literal"),
+            TokenizeErrKind::UnknownToken => (UNKNOWN_TOKEN, UNKNOWN_TOKEN.message),
+        };
+        Diagnostic {
+            id: code.id,
+            message,
+            primary: self.span,
+            level: code.level,
+            highlights: vec![Highlight {
+                span: self.span,
+                label: None,
+                is_primary: true,
+            }],
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+}
+
+impl IntoDiagnostic for ChainedOperatorErr {
+    type Reference = ();
+    fn into_diagnostic(self, _: &()) -> crate::diagnostic::Diagnostic {
+        let (message, suggestion) = if self.comparison {
+            (
+                "comparison operators cannot be chained",
+                "split the comparison into two and join them with `&&`",
+            )
+        } else {
+            (
+                "range operators cannot be chained",
+                "wrap one of the ranges in parentheses",
+            )
+        };
+        Diagnostic {
+            id: CHAINED_OPERATORS.id,
+            message,
+            primary: self.second,
+            level: CHAINED_OPERATORS.level,
+            highlights: vec![
+                Highlight {
+                    span: self.first,
+                    label: None,
+                    is_primary: false,
+                },
+                Highlight {
+                    span: self.second,
+                    label: Some("cannot follow the operator before it"),
+                    is_primary: true,
+                },
+            ],
+            notes: Vec::new(),
+            suggestions: vec![Suggestion {
+                message: suggestion,
+                replacement_span: None,
+                replacement_text: None,
+                applicability: Applicability::MaybeIncorrect,
+            }],
+        }
+    }
+}
+
+impl IntoDiagnostic for NestingTooDeepErr {
+    type Reference = ();
+    fn into_diagnostic(self, _: &()) -> crate::diagnostic::Diagnostic {
+        Diagnostic {
+            id: NESTING_TOO_DEEP.id,
+            message: NESTING_TOO_DEEP.message,
+            primary: self.outermost,
+            level: NESTING_TOO_DEEP.level,
+            highlights: vec![
+                Highlight {
+                    span: self.outermost,
+                    label: None,
+                    is_primary: true,
+                },
+                Highlight {
+                    span: self.innermost,
+                    label: Some("nesting limit reached here"),
+                    is_primary: false,
+                },
+            ],
+            notes: Vec::new(),
+            suggestions: vec![Suggestion {
+                message: "move some of the inner parts into `let` bindings",
+                replacement_span: None,
+                replacement_text: None,
+                applicability: Applicability::MaybeIncorrect,
+            }],
+        }
+    }
+}
+
+impl IntoDiagnostic for AnnotationErr {
+    type Reference = ();
+    fn into_diagnostic(self, _: &()) -> crate::diagnostic::Diagnostic {
+        let (code, message, suggestion) = match self.kind {
+            AnnotationErrKind::Unknown => (
+                UNKNOWN_ANNOTATION,
+                UNKNOWN_ANNOTATION.message,
+                "check the spelling of the annotation",
+            ),
+            AnnotationErrKind::Arguments => (
+                ANNOTATION_ARGUMENTS,
+                ANNOTATION_ARGUMENTS.message,
+                "check the arguments the annotation takes",
+            ),
+            AnnotationErrKind::Target(target) => (
+                ANNOTATION_TARGET,
+                match target {
+                    AnnotationTarget::Any => unreachable!("annotations on any item are allowed"),
+                    AnnotationTarget::Function => "annotation is only allowed on functions",
+                    AnnotationTarget::NullaryFunction => {
+                        "annotation is only allowed on functions without parameters"
+                    }
+                    AnnotationTarget::ExternModule => {
+                        "annotation is only allowed on modules with extern functions"
+                    }
+                },
+                "move the annotation to an item it is allowed on",
+            ),
+            AnnotationErrKind::Dangling => (
+                DANGLING_ANNOTATION,
+                DANGLING_ANNOTATION.message,
+                "remove the annotation or add an item after it",
+            ),
+        };
+        Diagnostic {
+            id: code.id,
+            message,
+            primary: self.span,
+            level: code.level,
+            highlights: vec![Highlight {
+                span: self.span,
+                label: None,
+                is_primary: true,
+            }],
+            notes: Vec::new(),
+            suggestions: vec![Suggestion {
+                message: suggestion,
+                replacement_span: None,
+                replacement_text: None,
+                applicability: Applicability::MaybeIncorrect,
+            }],
+        }
     }
 }
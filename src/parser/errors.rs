@@ -1,10 +1,17 @@
+use crate::compiler::span::Span;
+use crate::parser::annotation_registry::AnnotationTarget;
 use crate::parser::base_parser::Enviroment;
 use crate::tokenizer::tokens::Token;
 
 #[derive(Clone, Copy, Debug)]
 pub struct ParseErr {
-    expected: &'static [Token],
-    found: Enviroment,
+    pub(crate) expected: &'static [Token],
+    /// Whether an identifier was expected besides `expected`.
+    pub(crate) identifier: bool,
+    pub(crate) found: Enviroment,
+    /// Whether a more specific error was reported for it already, like
+    /// `NestingTooDeepErr`, so that `BaseParser::report_error` skips it.
+    pub(crate) reported: bool,
 }
 
 pub trait IParseErr {
@@ -14,11 +21,53 @@ pub trait IParseErr {
 
 impl IParseErr for ParseErr {
     fn build(identifier: bool, expected: &'static [Token], found: Enviroment) -> Self {
-        // WARNING: identifier is ignored
-        Self { expected, found }
+        Self {
+            expected,
+            identifier,
+            found,
+            reported: false,
+        }
     }
 
     fn is_endoffile_error(&self) -> bool {
         self.found.current == Token::EndOfFile
     }
 }
+
+/// Two non-associative operators of the same precedence in a row, like
+/// `a < b < c` or `a..b..c`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainedOperatorErr {
+    pub first: Span,
+    pub second: Span,
+    /// `true` for comparisons, `false` for ranges.
+    pub comparison: bool,
+}
+
+/// Boxed nodes or expressions nested deeper than `Parser::max_nesting_depth`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NestingTooDeepErr {
+    /// From the start of the outermost construct to `innermost`.
+    pub outermost: Span,
+    /// The token at which the limit was reached.
+    pub innermost: Span,
+}
+
+/// An annotation the `AnnotationRegistry` rejects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnnotationErr {
+    /// From the `@` to the `;`.
+    pub span: Span,
+    pub kind: AnnotationErrKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnnotationErrKind {
+    Unknown,
+    /// The arguments do not match the spec of the annotation.
+    Arguments,
+    /// The annotation is not allowed on the item after it.
+    Target(AnnotationTarget),
+    /// No item follows the annotation.
+    Dangling,
+}
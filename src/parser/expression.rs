@@ -0,0 +1,561 @@
+//! Expression parser.
+//!
+//! Expressions are parsed by precedence climbing (a Pratt parser) instead of
+//! one grammar rule per precedence level. Which tokens are operators, how
+//! tightly they bind and how they associate is looked up in the table of
+//! `parser::operators`.
+//!
+//! The grammar refers to expressions and types as the hooks `Expression` and
+//! `Type`, which list the tokens they start with. The generated parser calls
+//! `parse_expression` and `parse_type` for them, see `manual_parser`. The other
+//! way around, blocks, match arms and the fields of struct literals are rules
+//! of the grammar, which are parsed by the generated parser from here.
+
+use crate::compiler::arena::{ArenaBox, ArenaIter};
+use crate::compiler::span::Span;
+use crate::diagnostic::stream::DiagnosticStream;
+use crate::parser::ast::{BlockExpression, FieldInit, Identifier, MatchArm};
+use crate::parser::ast_node::ASTNode;
+use crate::parser::base_parser::BaseParser;
+use crate::parser::errors::{ChainedOperatorErr, IParseErr, ParseErr};
+use crate::parser::generated_parser::GeneratedParser;
+use crate::parser::operators::{self, Associativity, Fixity, Operation, OperatorEntry};
+use crate::parser::parse::Parser;
+use crate::parser::pattern::Pattern;
+use crate::parser::tracer::Tracer;
+use crate::tokenizer::tokens::{Delimiter, Keyword, Literal, Operator, Token};
+
+#[derive(Debug, Copy, Clone, std::hash::Hash, PartialEq, Eq)]
+pub enum Expression {
+    Identifier(Identifier),
+    Literal(Literal),
+    This,
+    /// `a::b`, with at least two segments.
+    Path(ArenaIter<Identifier>),
+    /// `[a, b]`
+    Array(ArenaIter<Expression>),
+    /// `Name { field: value }`
+    Struct {
+        name: Identifier,
+        fields: ArenaIter<FieldInit>,
+    },
+    Block(BlockExpression),
+    If {
+        condition: ArenaBox<Expression>,
+        then: BlockExpression,
+        /// A block, or another `If` for `else if`.
+        otherwise: Option<ArenaBox<Expression>>,
+    },
+    Match {
+        scrutinee: ArenaBox<Expression>,
+        arms: ArenaIter<MatchArm>,
+    },
+    For {
+        pattern: Pattern,
+        iterable: ArenaBox<Expression>,
+        body: BlockExpression,
+    },
+    /// `return` or `return value`
+    Return(Option<ArenaBox<Expression>>),
+    Parenthesized(ArenaBox<Expression>),
+    /// `()` or `(a, b)`. A single element is only a tuple with a trailing comma.
+    Tuple(ArenaIter<Expression>),
+    Prefix {
+        operation: Operation,
+        operand: ArenaBox<Expression>,
+    },
+    Infix {
+        operation: Operation,
+        lhs: ArenaBox<Expression>,
+        rhs: ArenaBox<Expression>,
+    },
+    /// `try value else fallback`
+    TryElse {
+        value: ArenaBox<Expression>,
+        fallback: ArenaBox<Expression>,
+    },
+    /// `value?`
+    Propagate(ArenaBox<Expression>),
+    Cast {
+        value: ArenaBox<Expression>,
+        ty: Type,
+    },
+    Call {
+        callee: ArenaBox<Expression>,
+        arguments: ArenaIter<Expression>,
+    },
+    MethodCall {
+        receiver: ArenaBox<Expression>,
+        method: Identifier,
+        arguments: ArenaIter<Expression>,
+    },
+    Field {
+        receiver: ArenaBox<Expression>,
+        field: Identifier,
+    },
+    Index {
+        receiver: ArenaBox<Expression>,
+        index: ArenaBox<Expression>,
+    },
+    Invalid,
+}
+
+impl Expression {
+    /// Whether the expression ends with a block, so that it needs no `;` as
+    /// a statement and no `,` as the value of a match arm.
+    pub fn is_block_like(&self) -> bool {
+        matches!(
+            self,
+            Expression::Block(_)
+                | Expression::If { .. }
+                | Expression::Match { .. }
+                | Expression::For { .. }
+        )
+    }
+}
+
+/// A type, like the one an expression is cast to with `as`.
+#[derive(Debug, Copy, Clone, std::hash::Hash, PartialEq, Eq)]
+pub enum Type {
+    Primitive(Keyword),
+    Named(Identifier),
+    /// `Name<A, B>`
+    Generic {
+        name: Identifier,
+        arguments: ArenaIter<Type>,
+    },
+    Invalid,
+}
+
+const PRIMITIVE_TYPES: [Keyword; 10] = [
+    Keyword::Int,
+    Keyword::DoubleInt,
+    Keyword::Float,
+    Keyword::DoubleFloat,
+    Keyword::Usize,
+    Keyword::Bool,
+    Keyword::Char,
+    Keyword::Void,
+    Keyword::Never,
+    Keyword::Any,
+];
+
+impl<S: DiagnosticStream, TR: Tracer> Parser<'_, '_, '_, S, TR> {
+    pub fn parse_expression(&mut self) -> Result<Expression, ParseErr> {
+        self.parse_expression_with_binding_power(0, true)
+    }
+
+    /// Parses the condition of `if`, the scrutinee of `match` or the iterable
+    /// of `for`, which are followed by a block. A struct literal is not
+    /// parsed there, so that in `if a { b }` the block is not taken for the
+    /// fields of `a`. It can still be written in parentheses.
+    fn parse_condition(&mut self) -> Result<Expression, ParseErr> {
+        self.parse_expression_with_binding_power(0, false)
+    }
+
+    /// Parses an expression whose operators all bind at least as tightly as
+    /// `min_binding_power`. See `OperatorEntry::binding_power`.
+    fn parse_expression_with_binding_power(
+        &mut self,
+        min_binding_power: u8,
+        struct_literal: bool,
+    ) -> Result<Expression, ParseErr> {
+        let _guard = TR::trace("Expression");
+        self.nested(|this| this.parse_operators(min_binding_power, struct_literal))
+    }
+
+    fn parse_operators(
+        &mut self,
+        min_binding_power: u8,
+        struct_literal: bool,
+    ) -> Result<Expression, ParseErr> {
+        let mut lhs = match operators::prefix(self.peek::<0>()) {
+            Some(entry) => {
+                self.consume_token();
+                let power = entry.binding_power().1;
+                let operand = self.parse_expression_with_binding_power(power, struct_literal)?;
+                let operand = self.alloc(operand);
+                if entry.operation == Operation::Try
+                    && self.peek::<0>() == Some(Token::Keyword(Keyword::Else))
+                {
+                    self.consume_token();
+                    let fallback =
+                        self.parse_expression_with_binding_power(power, struct_literal)?;
+                    Expression::TryElse {
+                        value: operand,
+                        fallback: self.alloc(fallback),
+                    }
+                } else {
+                    Expression::Prefix {
+                        operation: entry.operation,
+                        operand,
+                    }
+                }
+            }
+            None => self.parse_primary_expression(struct_literal)?,
+        };
+        // the last non-associative operator applied to `lhs`, to reject `a < b < c`
+        let mut non_associative: Option<(&OperatorEntry, Span)> = None;
+
+        loop {
+            let next = self.peek::<0>();
+            if let Some(entry) = operators::postfix(next) {
+                if entry.binding_power().0 < min_binding_power {
+                    break;
+                }
+                lhs = self.parse_postfix_operator(entry, lhs)?;
+                continue;
+            }
+
+            let Some(entry) = operators::infix(next) else {
+                break;
+            };
+            let (left, right) = entry.binding_power();
+            if left < min_binding_power {
+                break;
+            }
+            let span = self.now_span();
+            if let Some((previous, previous_span)) = non_associative {
+                if previous.precedence == entry.precedence {
+                    self.report_chained(previous, previous_span, span);
+                }
+            }
+            self.consume_token();
+
+            lhs = if entry.operation == Operation::Cast {
+                Expression::Cast {
+                    value: self.alloc(lhs),
+                    ty: self.parse_type()?,
+                }
+            } else {
+                let rhs = self.parse_expression_with_binding_power(right, struct_literal)?;
+                Expression::Infix {
+                    operation: entry.operation,
+                    lhs: self.alloc(lhs),
+                    rhs: self.alloc(rhs),
+                }
+            };
+            non_associative = match entry.fixity {
+                Fixity::Infix(Associativity::None) => Some((entry, span)),
+                _ => None,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn parse_postfix_operator(
+        &mut self,
+        entry: &OperatorEntry,
+        lhs: Expression,
+    ) -> Result<Expression, ParseErr> {
+        self.consume_token();
+        let receiver = self.alloc(lhs);
+        Ok(match entry.operation {
+            Operation::Propagate => Expression::Propagate(receiver),
+            Operation::Call => Expression::Call {
+                callee: receiver,
+                arguments: self.parse_arguments()?,
+            },
+            Operation::Index => {
+                let index = self.alloc_box(Self::parse_expression)?;
+                self.expect(&Token::Delimiter(Delimiter::RightBracket))?;
+                Expression::Index { receiver, index }
+            }
+            Operation::Member => {
+                let name = self.parse_name()?;
+                if self.peek::<0>() == Some(Token::Delimiter(Delimiter::LeftParen)) {
+                    self.consume_token();
+                    Expression::MethodCall {
+                        receiver,
+                        method: name,
+                        arguments: self.parse_arguments()?,
+                    }
+                } else {
+                    Expression::Field {
+                        receiver,
+                        field: name,
+                    }
+                }
+            }
+            operation => unreachable!("{operation:?} is not a postfix operation"),
+        })
+    }
+
+    fn parse_primary_expression(&mut self, struct_literal: bool) -> Result<Expression, ParseErr> {
+        match self.peek::<0>() {
+            Some(Token::Identifier(_)) => self.parse_named_expression(struct_literal),
+            Some(Token::Literal(literal)) => {
+                self.consume_token();
+                Ok(Expression::Literal(literal))
+            }
+            Some(Token::Keyword(Keyword::This)) => {
+                self.consume_token();
+                Ok(Expression::This)
+            }
+            Some(Token::Delimiter(Delimiter::LeftParen)) => {
+                self.consume_token();
+                self.parse_parenthesized_or_tuple()
+            }
+            Some(Token::Delimiter(Delimiter::LeftBracket)) => {
+                self.consume_token();
+                Ok(Expression::Array(self.parse_comma_separated(
+                    None,
+                    &Token::Delimiter(Delimiter::RightBracket),
+                )?))
+            }
+            Some(Token::Keyword(Keyword::Return)) => {
+                self.consume_token();
+                let value = if Expression::is_first1_sets(&self.peek::<0>()) {
+                    Some(self.alloc_box(Self::parse_expression)?)
+                } else {
+                    None
+                };
+                Ok(Expression::Return(value))
+            }
+            _ => self.parse_block_like_expression(),
+        }
+    }
+
+    /// Parses an identifier, a path or a struct literal.
+    fn parse_named_expression(&mut self, struct_literal: bool) -> Result<Expression, ParseErr> {
+        let name = self.parse_name()?;
+        if self.peek::<0>() == Some(Token::Operator(Operator::NamespaceResolver)) {
+            return Ok(Expression::Path(self.parse_path(name)?));
+        }
+        if struct_literal && self.peek::<0>() == Some(Token::Delimiter(Delimiter::LeftBrace)) {
+            self.consume_token();
+            let mut fields = Vec::new();
+            while self.peek::<0>() != Some(Token::Delimiter(Delimiter::RightBrace)) {
+                fields.push(self.parse_FieldInit()?);
+                if self.peek::<0>() != Some(Token::Delimiter(Delimiter::Comma)) {
+                    break;
+                }
+                self.consume_token();
+            }
+            self.expect(&Token::Delimiter(Delimiter::RightBrace))?;
+            return Ok(Expression::Struct {
+                name,
+                fields: self.ctx.ast_arena.alloc_iter(fields.into_iter()),
+            });
+        }
+        Ok(Expression::Identifier(name))
+    }
+
+    /// Parses the rest of a path after its first segment, which is followed
+    /// by `::`.
+    pub(crate) fn parse_path(
+        &mut self,
+        first: Identifier,
+    ) -> Result<ArenaIter<Identifier>, ParseErr> {
+        let mut segments = vec![first];
+        while self.peek::<0>() == Some(Token::Operator(Operator::NamespaceResolver)) {
+            self.consume_token();
+            segments.push(self.parse_name()?);
+        }
+        Ok(self.ctx.ast_arena.alloc_iter(segments.into_iter()))
+    }
+
+    /// Parses an expression where a statement could start, like the value of
+    /// a match arm. One which starts with a block ends with it, so that the
+    /// `(` in `if a { b } (c, d)` starts something new instead of a call.
+    pub(crate) fn parse_statement_expression(&mut self) -> Result<Expression, ParseErr> {
+        match self.peek::<0>() {
+            Some(
+                Token::Delimiter(Delimiter::LeftBrace)
+                | Token::Keyword(Keyword::If | Keyword::Match | Keyword::For),
+            ) => self.nested(Self::parse_block_like_expression),
+            _ => self.parse_expression(),
+        }
+    }
+
+    /// Parses the expressions which end with a block: blocks, `if`, `match`
+    /// and `for`.
+    fn parse_block_like_expression(&mut self) -> Result<Expression, ParseErr> {
+        match self.peek::<0>() {
+            Some(Token::Delimiter(Delimiter::LeftBrace)) => {
+                Ok(Expression::Block(self.parse_BlockExpression()?))
+            }
+            Some(Token::Keyword(Keyword::If)) => self.parse_if(),
+            Some(Token::Keyword(Keyword::Match)) => {
+                self.consume_token();
+                let scrutinee = self.alloc_box(Self::parse_condition)?;
+                Ok(Expression::Match {
+                    scrutinee,
+                    arms: self.parse_match_arms()?,
+                })
+            }
+            Some(Token::Keyword(Keyword::For)) => {
+                self.consume_token();
+                let pattern = self.parse_pattern()?;
+                self.expect(&Token::Keyword(Keyword::In))?;
+                let iterable = self.alloc_box(Self::parse_condition)?;
+                Ok(Expression::For {
+                    pattern,
+                    iterable,
+                    body: self.parse_BlockExpression()?,
+                })
+            }
+            _ => Err(ParseErr::build(
+                true,
+                &[
+                    Token::Delimiter(Delimiter::LeftParen),
+                    Token::Delimiter(Delimiter::LeftBracket),
+                    Token::Delimiter(Delimiter::LeftBrace),
+                    Token::Keyword(Keyword::If),
+                    Token::Keyword(Keyword::Match),
+                    Token::Keyword(Keyword::For),
+                ],
+                self.enviroment(),
+            )),
+        }
+    }
+
+    fn parse_if(&mut self) -> Result<Expression, ParseErr> {
+        self.expect(&Token::Keyword(Keyword::If))?;
+        let condition = self.alloc_box(Self::parse_condition)?;
+        let then = self.parse_BlockExpression()?;
+        let otherwise = if self.peek::<0>() == Some(Token::Keyword(Keyword::Else)) {
+            self.consume_token();
+            Some(self.alloc_box(|this| match this.peek::<0>() {
+                Some(Token::Keyword(Keyword::If)) => this.parse_if(),
+                _ => Ok(Expression::Block(this.parse_BlockExpression()?)),
+            })?)
+        } else {
+            None
+        };
+        Ok(Expression::If {
+            condition,
+            then,
+            otherwise,
+        })
+    }
+
+    /// Parses `{ arm, arm }`. An arm whose value ends with a block needs no
+    /// `,` after it, and the last one needs none at all.
+    fn parse_match_arms(&mut self) -> Result<ArenaIter<MatchArm>, ParseErr> {
+        self.expect(&Token::Delimiter(Delimiter::LeftBrace))?;
+        let mut arms = Vec::new();
+        while self.peek::<0>() != Some(Token::Delimiter(Delimiter::RightBrace)) {
+            let _guard = TR::trace("MatchArm");
+            let pattern = self.parse_pattern()?;
+            let guard = self.optional(Self::parse_Guard);
+            self.expect(&Token::Operator(Operator::FatArrow))?;
+            let value = self.parse_statement_expression()?;
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                value,
+            });
+            match self.peek::<0>() {
+                Some(Token::Delimiter(Delimiter::Comma)) => {
+                    self.consume_token();
+                }
+                Some(Token::Delimiter(Delimiter::RightBrace)) => break,
+                _ if value.is_block_like() => {}
+                _ => self.expect(&Token::Delimiter(Delimiter::Comma))?,
+            }
+        }
+        self.expect(&Token::Delimiter(Delimiter::RightBrace))?;
+        Ok(self.ctx.ast_arena.alloc_iter(arms.into_iter()))
+    }
+
+    /// Parses the rest of `(a)`, `()` or `(a, b)` after the `(`.
+    fn parse_parenthesized_or_tuple(&mut self) -> Result<Expression, ParseErr> {
+        if self.peek::<0>() == Some(Token::Delimiter(Delimiter::RightParen)) {
+            self.consume_token();
+            return Ok(Expression::Tuple(
+                self.ctx.ast_arena.alloc_iter(std::iter::empty()),
+            ));
+        }
+        let first = self.parse_expression()?;
+        if self.peek::<0>() == Some(Token::Delimiter(Delimiter::RightParen)) {
+            self.consume_token();
+            return Ok(Expression::Parenthesized(self.alloc(first)));
+        }
+        self.expect(&Token::Delimiter(Delimiter::Comma))?;
+        Ok(Expression::Tuple(self.parse_comma_separated(
+            Some(first),
+            &Token::Delimiter(Delimiter::RightParen),
+        )?))
+    }
+
+    /// Parses the rest of an argument list after the `(`.
+    fn parse_arguments(&mut self) -> Result<ArenaIter<Expression>, ParseErr> {
+        self.parse_comma_separated(None, &Token::Delimiter(Delimiter::RightParen))
+    }
+
+    /// Parses comma separated expressions up to and including `close`. A
+    /// trailing comma is allowed.
+    fn parse_comma_separated(
+        &mut self,
+        first: Option<Expression>,
+        close: &'static Token,
+    ) -> Result<ArenaIter<Expression>, ParseErr> {
+        let mut items = Vec::from_iter(first);
+        loop {
+            if self.peek::<0>().as_ref() == Some(close) {
+                break;
+            }
+            items.push(self.parse_expression()?);
+            if self.peek::<0>() != Some(Token::Delimiter(Delimiter::Comma)) {
+                break;
+            }
+            self.consume_token();
+        }
+        self.expect(close)?;
+        Ok(self.ctx.ast_arena.alloc_iter(items.into_iter()))
+    }
+
+    pub fn parse_type(&mut self) -> Result<Type, ParseErr> {
+        let _guard = TR::trace("Type");
+        match self.peek::<0>() {
+            Some(Token::Keyword(keyword)) if PRIMITIVE_TYPES.contains(&keyword) => {
+                self.consume_token();
+                Ok(Type::Primitive(keyword))
+            }
+            _ => {
+                let name = self.parse_name()?;
+                if self.peek::<0>() != Some(Token::Operator(Operator::LessThan)) {
+                    return Ok(Type::Named(name));
+                }
+                self.consume_token();
+                let mut arguments = vec![self.nested(Self::parse_type)?];
+                while self.peek::<0>() == Some(Token::Delimiter(Delimiter::Comma)) {
+                    self.consume_token();
+                    arguments.push(self.nested(Self::parse_type)?);
+                }
+                self.expect(&Token::Operator(Operator::GreaterThan))?;
+                Ok(Type::Generic {
+                    name,
+                    arguments: self.ctx.ast_arena.alloc_iter(arguments.into_iter()),
+                })
+            }
+        }
+    }
+
+    pub(crate) fn parse_name(&mut self) -> Result<Identifier, ParseErr> {
+        match self.peek::<0>() {
+            Some(Token::Identifier(symbol)) => {
+                self.consume_token();
+                Ok(Identifier { symbol })
+            }
+            _ => Err(ParseErr::build(true, &[], self.enviroment())),
+        }
+    }
+
+    /// Reports `a < b < c`. The chain is still parsed left to right, so
+    /// parsing can go on.
+    fn report_chained(&mut self, first: &OperatorEntry, first_span: Span, second_span: Span) {
+        if self.is_panic_or_backtrack_mode() {
+            return;
+        }
+        self.diagnostic_stream.pour(
+            ChainedOperatorErr {
+                first: first_span,
+                second: second_span,
+                comparison: first.operation.is_comparison(),
+            },
+            &(),
+        );
+    }
+}
@@ -0,0 +1,624 @@
+//! Random programs for fuzzing the parser.
+//!
+//! `ProgramGenerator` walks a `Grammar` from a rule and writes out a random
+//! derivation of it, spelling the terminals like `assets/tokenmap.csv` does.
+//! The programs are valid by construction. `ProgramGenerator::mutate` then
+//! breaks valid programs token by token, with the `GrammarInterpreter`
+//! telling when they are broken, to make the parser recover from errors.
+//! Hooks, which the grammar leaves to the hand written parsers, are written
+//! out the way those parsers read them, and `ProgramGenerator::nest` nests
+//! them past the nesting limit of the parser.
+//! All of it is deterministic for a seed, so a failing program can be
+//! generated again from the seed alone.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::parser::grammar::{
+    Grammar, Member, Modifier, Rule, RuleKind, Terminal, TokenClass, TokenMap,
+};
+use crate::parser::interpreter::GrammarInterpreter;
+use crate::parser::operators::{Fixity, Operation, OperatorEntry, OPERATORS};
+
+pub const DEFAULT_MAX_DEPTH: usize = 8;
+pub const DEFAULT_MAX_SIZE: usize = 256;
+
+/// How often `mutate` tries to break a program before it gives up.
+const MAX_MUTATION_ATTEMPTS: usize = 64;
+
+const IDENTIFIERS: [&str; 6] = ["a", "b", "value", "count", "item", "main"];
+
+const PRIMITIVE_TYPES: [&str; 5] = ["Int", "Float", "Bool", "Char", "Void"];
+
+/// Programs of the hooks which are not written out like their parsers read
+/// them, as the spellings of their tokens separated by spaces.
+const HOOK_SAMPLES: &[(&str, &[&str])] = &[("Annotation", &["@ ModuleConfig \"app\" \"1.0\" ;"])];
+
+/// What `ProgramGenerator::nest` wraps a program of each rule in, as the
+/// spellings of the tokens before and after it. Every wrapper is a level of
+/// the nesting limit at least.
+const NESTING_WRAPPERS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Expression",
+        &[
+            ("(", ")"),
+            ("[", "]"),
+            ("- (", ")"),
+            ("f (", ")"),
+            ("{", "}"),
+            ("if a {", "}"),
+            ("match a { _ =>", "}"),
+        ],
+    ),
+    ("Pattern", &[("(", ", )"), ("a (", ")"), ("a { b :", "}")]),
+];
+
+/// A SplitMix64 generator. Fuzzing needs reproducible, not good, randomness.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    pub fn coin(&mut self) -> bool {
+        self.next_u64() & 1 == 1
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+/// A generated program, as the spellings of its tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub tokens: Vec<String>,
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.tokens.join(" "))
+    }
+}
+
+pub struct ProgramGenerator<'g> {
+    grammar: &'g Grammar,
+    token_map: &'g TokenMap,
+    rng: Rng,
+    /// How deep a derivation of each rule has to be at least.
+    min_depths: HashMap<&'g str, usize>,
+    /// Rules nested deeper than this only take their shallowest derivation.
+    pub max_depth: usize,
+    /// Once this many tokens are written, lists and options are left empty.
+    pub max_size: usize,
+    size: usize,
+}
+
+impl<'g> ProgramGenerator<'g> {
+    pub fn new(grammar: &'g Grammar, token_map: &'g TokenMap, seed: u64) -> Self {
+        Self {
+            grammar,
+            token_map,
+            rng: Rng::new(seed),
+            min_depths: min_depths(grammar),
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_size: DEFAULT_MAX_SIZE,
+            size: 0,
+        }
+    }
+
+    pub fn with_limits(mut self, max_depth: usize, max_size: usize) -> Self {
+        self.max_depth = max_depth;
+        self.max_size = max_size;
+        self
+    }
+
+    /// Writes a random program matching the rule `rule`.
+    pub fn generate(&mut self, rule: &str) -> Program {
+        let rule = self.rule(rule);
+        let mut tokens = Vec::new();
+        self.size = 0;
+        self.generate_rule(rule, 0, &mut tokens);
+        Program { tokens }
+    }
+
+    /// Changes one to three tokens of `program`, which matches the rule
+    /// `rule`, until it doesn't match it any more. Tokens are deleted,
+    /// duplicated, swapped or replaced by or preceded with a random token.
+    ///
+    /// Returns `None` if every attempt left the program unchanged or still
+    /// matching, which only happens for tiny programs and grammars which
+    /// accept almost anything.
+    pub fn mutate(&mut self, rule: &str, program: &Program) -> Option<Program> {
+        let interpreter = GrammarInterpreter::new(self.grammar);
+        for _ in 0..MAX_MUTATION_ATTEMPTS {
+            let mut mutated = program.clone();
+            for _ in 0..=self.rng.below(3) {
+                self.mutate_once(&mut mutated.tokens);
+            }
+            // swapping a token with itself or with an equal one changes nothing
+            if mutated == *program {
+                continue;
+            }
+            if interpreter.parse(rule, &mutated.to_string()).is_err() {
+                return Some(mutated);
+            }
+        }
+        None
+    }
+
+    /// Wraps `program`, which matches the rule `rule`, in `levels` random
+    /// wrappers which match `rule` again, like `( a )` does for `a`. Enough
+    /// levels run into the nesting limit of the parser.
+    ///
+    /// Only expressions and patterns can be nested.
+    pub fn nest(&mut self, rule: &str, program: &Program, levels: usize) -> Program {
+        let wrappers = NESTING_WRAPPERS
+            .iter()
+            .find(|(nested, _)| *nested == rule)
+            .unwrap_or_else(|| panic!("`{rule}` can't be nested"))
+            .1;
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+        for _ in 0..levels {
+            let (open, close) = self.rng.pick(wrappers);
+            before.extend(open.split(' ').map(str::to_string));
+            after.extend(close.split(' ').rev().map(str::to_string));
+        }
+        after.reverse();
+        before.extend(program.tokens.iter().cloned());
+        before.extend(after);
+        Program { tokens: before }
+    }
+
+    fn mutate_once(&mut self, tokens: &mut Vec<String>) {
+        let spellings = self.token_map.spellings();
+        let random = self.rng.pick(spellings).0.clone();
+        if tokens.is_empty() {
+            tokens.push(random);
+            return;
+        }
+        let at = self.rng.below(tokens.len());
+        match self.rng.below(5) {
+            0 => {
+                tokens.remove(at);
+            }
+            1 => tokens.insert(at, tokens[at].clone()),
+            2 => {
+                let other = self.rng.below(tokens.len());
+                tokens.swap(at, other);
+            }
+            3 => tokens[at] = random,
+            _ => tokens.insert(at, random),
+        }
+    }
+
+    fn rule(&self, name: &str) -> &'g Rule {
+        self.grammar
+            .get(name)
+            .unwrap_or_else(|| panic!("rule `{name}` is not defined"))
+    }
+
+    /// Whether `rule` can still be derived at `depth` without going deeper
+    /// than `max_depth`.
+    fn fits(&self, rule: &str, depth: usize) -> bool {
+        depth.saturating_add(self.min_depths[rule]) <= self.max_depth
+    }
+
+    fn is_exhausted(&self, depth: usize) -> bool {
+        depth >= self.max_depth || self.size >= self.max_size
+    }
+
+    fn generate_rule(&mut self, rule: &'g Rule, depth: usize, tokens: &mut Vec<String>) {
+        match &rule.kind {
+            RuleKind::Branch(variants) => {
+                let fitting = variants
+                    .iter()
+                    .filter(|variant| self.fits(variant, depth + 1))
+                    .collect::<Vec<_>>();
+                let variant = if fitting.is_empty() || self.is_exhausted(depth) {
+                    variants
+                        .iter()
+                        .min_by_key(|variant| self.min_depths[variant.as_str()])
+                        .expect("branches have variants")
+                } else {
+                    *self.rng.pick(&fitting)
+                };
+                self.generate_rule(self.rule(variant), depth + 1, tokens);
+            }
+            RuleKind::Product(members) => {
+                for member in members {
+                    self.generate_member(member, depth, tokens);
+                }
+            }
+            RuleKind::Hook(_) => match rule.name.as_str() {
+                "Expression" => self.generate_expression(depth, tokens),
+                "Statement" => self.generate_statement(depth, tokens),
+                "Pattern" => self.generate_pattern(depth, tokens),
+                "Type" => self.generate_type(depth, tokens),
+                hook => {
+                    let samples = HOOK_SAMPLES
+                        .iter()
+                        .find(|(sampled, _)| *sampled == hook)
+                        .unwrap_or_else(|| panic!("the hook `{hook}` has no samples"))
+                        .1;
+                    let sample = *self.rng.pick(samples);
+                    self.write(sample, tokens);
+                }
+            },
+        }
+    }
+
+    /// Writes `spellings`, separated by spaces, as tokens.
+    fn write(&mut self, spellings: &str, tokens: &mut Vec<String>) {
+        for spelling in spellings.split(' ') {
+            tokens.push(spelling.to_string());
+            self.size += 1;
+        }
+    }
+
+    fn write_terminal(&mut self, terminal: &Terminal, tokens: &mut Vec<String>) {
+        let spelling = self.spell(terminal);
+        tokens.push(spelling);
+        self.size += 1;
+    }
+
+    /// Writes up to three programs of the rule `rule`, separated by commas.
+    fn generate_comma_separated(&mut self, rule: &str, depth: usize, tokens: &mut Vec<String>) {
+        let count = if self.is_exhausted(depth) {
+            0
+        } else {
+            self.rng.below(4)
+        };
+        for i in 0..count {
+            if i > 0 {
+                self.write(",", tokens);
+            }
+            self.generate_rule(self.rule(rule), depth + 1, tokens);
+        }
+    }
+
+    /// Writes an expression like `parser::expression` reads it. Operands of
+    /// operators are atoms or parenthesized, so that neither precedence nor
+    /// the chaining of comparisons gets in the way, and so are conditions,
+    /// which can't be struct literals otherwise.
+    fn generate_expression(&mut self, depth: usize, tokens: &mut Vec<String>) {
+        if self.is_exhausted(depth) {
+            return self.generate_atom(tokens);
+        }
+        let depth = depth + 1;
+        match self.rng.below(13) {
+            0 => {
+                let prefix = self.pick_operator(|fixity| fixity == Fixity::Prefix);
+                self.write_terminal(&Terminal::Token(prefix.token), tokens);
+                self.generate_operand(depth, tokens);
+            }
+            1 => {
+                let infix = self.pick_operator(|fixity| matches!(fixity, Fixity::Infix(_)));
+                self.generate_operand(depth, tokens);
+                self.write_terminal(&Terminal::Token(infix.token), tokens);
+                if infix.operation == Operation::Cast {
+                    self.generate_type(depth, tokens);
+                } else {
+                    self.generate_operand(depth, tokens);
+                }
+            }
+            2 => {
+                self.generate_operand(depth, tokens);
+                match self.rng.below(4) {
+                    0 => self.write("?", tokens),
+                    1 => {
+                        self.write("[", tokens);
+                        self.generate_expression(depth, tokens);
+                        self.write("]", tokens);
+                    }
+                    2 => self.generate_arguments(depth, tokens),
+                    _ => {
+                        self.write(".", tokens);
+                        self.write_terminal(&Terminal::Class(TokenClass::Identifier), tokens);
+                        if self.rng.coin() {
+                            self.generate_arguments(depth, tokens);
+                        }
+                    }
+                }
+            }
+            3 => {
+                self.write("(", tokens);
+                self.generate_expression(depth, tokens);
+                self.write(")", tokens);
+            }
+            4 => {
+                self.write("(", tokens);
+                if self.rng.coin() {
+                    self.generate_expression(depth, tokens);
+                    self.write(",", tokens);
+                    self.generate_comma_separated("Expression", depth, tokens);
+                }
+                self.write(")", tokens);
+            }
+            5 => {
+                self.write("[", tokens);
+                self.generate_comma_separated("Expression", depth, tokens);
+                self.write("]", tokens);
+            }
+            6 => {
+                self.write_terminal(&Terminal::Class(TokenClass::Identifier), tokens);
+                self.write("{", tokens);
+                self.generate_comma_separated("FieldInit", depth, tokens);
+                self.write("}", tokens);
+            }
+            7 => {
+                self.write_terminal(&Terminal::Class(TokenClass::Identifier), tokens);
+                self.write("::", tokens);
+                self.write_terminal(&Terminal::Class(TokenClass::Identifier), tokens);
+            }
+            8 => self.generate_rule(self.rule("BlockExpression"), depth, tokens),
+            9 => {
+                self.write("if", tokens);
+                self.generate_operand(depth, tokens);
+                self.generate_rule(self.rule("BlockExpression"), depth, tokens);
+                if self.rng.coin() {
+                    self.write("else", tokens);
+                    self.generate_rule(self.rule("BlockExpression"), depth, tokens);
+                }
+            }
+            10 => {
+                self.write("match", tokens);
+                self.generate_operand(depth, tokens);
+                self.write("{", tokens);
+                for _ in 0..self.rng.below(3) {
+                    self.generate_rule(self.rule("MatchArm"), depth, tokens);
+                    self.write(",", tokens);
+                }
+                self.write("}", tokens);
+            }
+            11 => {
+                self.write("for", tokens);
+                self.generate_pattern(depth, tokens);
+                self.write("in", tokens);
+                self.generate_operand(depth, tokens);
+                self.generate_rule(self.rule("BlockExpression"), depth, tokens);
+            }
+            _ => {
+                self.write("return", tokens);
+                if self.rng.coin() {
+                    self.generate_operand(depth, tokens);
+                }
+            }
+        }
+    }
+
+    /// Writes an atom, or an expression in parentheses.
+    fn generate_operand(&mut self, depth: usize, tokens: &mut Vec<String>) {
+        if self.is_exhausted(depth) || self.rng.coin() {
+            self.generate_atom(tokens);
+        } else {
+            self.write("(", tokens);
+            self.generate_expression(depth, tokens);
+            self.write(")", tokens);
+        }
+    }
+
+    fn generate_atom(&mut self, tokens: &mut Vec<String>) {
+        let class = *self.rng.pick(&[
+            TokenClass::Identifier,
+            TokenClass::Identifier,
+            TokenClass::Int,
+            TokenClass::String,
+            TokenClass::Bool,
+        ]);
+        self.write_terminal(&Terminal::Class(class), tokens);
+    }
+
+    fn generate_arguments(&mut self, depth: usize, tokens: &mut Vec<String>) {
+        self.write("(", tokens);
+        self.generate_comma_separated("Expression", depth, tokens);
+        self.write(")", tokens);
+    }
+
+    fn pick_operator(&mut self, fixity: impl Fn(Fixity) -> bool) -> OperatorEntry {
+        let operators = OPERATORS
+            .iter()
+            .filter(|entry| fixity(entry.fixity))
+            .copied()
+            .collect::<Vec<_>>();
+        *self.rng.pick(&operators)
+    }
+
+    /// Writes a statement like `parser::statement` reads it. Expression
+    /// statements always end with a `;`, which is allowed after blocks too.
+    fn generate_statement(&mut self, depth: usize, tokens: &mut Vec<String>) {
+        let depth = depth + 1;
+        match self.rng.below(4) {
+            0 => {
+                self.write("let", tokens);
+                self.generate_pattern(depth, tokens);
+                if self.rng.coin() {
+                    self.write(":", tokens);
+                    self.generate_type(depth, tokens);
+                }
+                if self.rng.coin() {
+                    self.write("=", tokens);
+                    self.generate_expression(depth, tokens);
+                }
+            }
+            1 => {
+                self.write("ignore", tokens);
+                self.generate_expression(depth, tokens);
+            }
+            _ => self.generate_expression(depth, tokens),
+        }
+        self.write(";", tokens);
+    }
+
+    /// Writes a pattern like `parser::pattern` reads it.
+    fn generate_pattern(&mut self, depth: usize, tokens: &mut Vec<String>) {
+        let identifier = Terminal::Class(TokenClass::Identifier);
+        let kind = if self.is_exhausted(depth) {
+            self.rng.below(4)
+        } else {
+            self.rng.below(8)
+        };
+        let depth = depth + 1;
+        match kind {
+            0 => self.write("_", tokens),
+            1 => {
+                self.write("mut", tokens);
+                self.write_terminal(&identifier, tokens);
+            }
+            2 => self.write_terminal(&identifier, tokens),
+            3 => self.write_terminal(&Terminal::Class(TokenClass::Int), tokens),
+            4 => {
+                self.write("(", tokens);
+                self.generate_comma_separated("Pattern", depth, tokens);
+                self.write(")", tokens);
+            }
+            5 => {
+                self.write_terminal(&identifier, tokens);
+                self.write("::", tokens);
+                self.write_terminal(&identifier, tokens);
+            }
+            6 => {
+                self.write_terminal(&identifier, tokens);
+                self.write("(", tokens);
+                self.generate_comma_separated("Pattern", depth, tokens);
+                self.write(")", tokens);
+            }
+            _ => {
+                self.write_terminal(&identifier, tokens);
+                self.write("{", tokens);
+                self.generate_comma_separated("FieldPattern", depth, tokens);
+                self.write("}", tokens);
+            }
+        }
+    }
+
+    /// Writes a type like `Parser::parse_type` reads it.
+    fn generate_type(&mut self, depth: usize, tokens: &mut Vec<String>) {
+        match self.rng.below(3) {
+            0 => {
+                let primitive = *self.rng.pick(&PRIMITIVE_TYPES);
+                self.write(primitive, tokens);
+            }
+            1 if !self.is_exhausted(depth) => {
+                self.write_terminal(&Terminal::Class(TokenClass::Identifier), tokens);
+                self.write("<", tokens);
+                self.generate_type(depth + 1, tokens);
+                for _ in 0..self.rng.below(2) {
+                    self.write(",", tokens);
+                    self.generate_type(depth + 1, tokens);
+                }
+                self.write(">", tokens);
+            }
+            _ => self.write_terminal(&Terminal::Class(TokenClass::Identifier), tokens),
+        }
+    }
+
+    fn generate_member(&mut self, member: &'g Member, depth: usize, tokens: &mut Vec<String>) {
+        match member {
+            Member::Terminal(terminal) => {
+                tokens.push(self.spell(terminal));
+                self.size += 1;
+            }
+            Member::Field { rule, modifier, .. } => {
+                let count = match modifier {
+                    Modifier::None => 1,
+                    _ if self.is_exhausted(depth) || !self.fits(rule, depth + 1) => 0,
+                    Modifier::Option => usize::from(self.rng.coin()),
+                    Modifier::List => self.rng.below(4),
+                };
+                for _ in 0..count {
+                    self.generate_rule(self.rule(rule), depth + 1, tokens);
+                }
+            }
+        }
+    }
+
+    fn spell(&mut self, terminal: &Terminal) -> String {
+        match terminal {
+            Terminal::Token(token) => self
+                .token_map
+                .spelling(token)
+                .unwrap_or_else(|| panic!("the token map has no spelling for {token:?}"))
+                .to_string(),
+            Terminal::Class(TokenClass::Identifier) => {
+                let name = self.rng.pick(&IDENTIFIERS);
+                match self.rng.below(3) {
+                    0 => format!("{name}{}", self.rng.below(100)),
+                    _ => name.to_string(),
+                }
+            }
+            Terminal::Class(TokenClass::Int) => self.rng.below(1000).to_string(),
+            Terminal::Class(TokenClass::Float) => format!("{}.5", self.rng.below(1000)),
+            Terminal::Class(TokenClass::String) => format!("\"text{}\"", self.rng.below(100)),
+            Terminal::Class(TokenClass::Char) => "'c'".to_string(),
+            Terminal::Class(TokenClass::Bool) => self.rng.pick(&["true", "false"]).to_string(),
+            Terminal::Class(TokenClass::DocComment) => "/// doc\n".to_string(),
+            Terminal::Class(TokenClass::LineComment) => "// comment\n".to_string(),
+            Terminal::Class(TokenClass::BlockComment) => "/* comment */".to_string(),
+            Terminal::Class(class @ (TokenClass::DoubleInt | TokenClass::DoubleFloat)) => {
+                panic!("the tokenizer has no spelling for {class:?}")
+            }
+        }
+    }
+}
+
+/// How deep the shallowest derivation of every rule is. Rules which can't
+/// be derived at all, like `product A { a: A }`, are left at `usize::MAX`.
+fn min_depths(grammar: &Grammar) -> HashMap<&str, usize> {
+    let mut depths = grammar
+        .rules()
+        .iter()
+        .map(|rule| (rule.name.as_str(), usize::MAX))
+        .collect::<HashMap<_, _>>();
+    loop {
+        let mut changed = false;
+        for rule in grammar.rules() {
+            let children = match &rule.kind {
+                RuleKind::Branch(variants) => variants
+                    .iter()
+                    .map(|variant| depths[variant.as_str()])
+                    .min()
+                    .unwrap_or(usize::MAX),
+                RuleKind::Product(members) => members
+                    .iter()
+                    .filter_map(|member| match member {
+                        Member::Field {
+                            rule,
+                            modifier: Modifier::None,
+                            ..
+                        } => Some(depths[rule.as_str()]),
+                        _ => None,
+                    })
+                    .max()
+                    .unwrap_or(0),
+                RuleKind::Hook(_) => 0,
+            };
+            let depth = children.saturating_add(1);
+            if depth < depths[rule.name.as_str()] {
+                depths.insert(&rule.name, depth);
+                changed = true;
+            }
+        }
+        if !changed {
+            break depths;
+        }
+    }
+}
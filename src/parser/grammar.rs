@@ -0,0 +1,497 @@
+//! The grammar the parser is generated from, read at runtime.
+//!
+//! `script/src/parser_gen` reads `assets/grammer.tibineko` to generate
+//! `GeneratedParser`. This module reads the same file into a `Grammar` for
+//! `parser::interpreter`, which parses source by interpreting the rules
+//! directly, so that bugs of the generator show up as differences between the
+//! two parsers.
+//!
+//! ```text
+//! Grammar     := { Rule }
+//! Rule        := "branch" Name "{" { Name [ "with" String ] } "}"
+//!              | "product" Name ( "{" { Member [ "with" String ] } "}" | "with" String )
+//!              | "hook" Name "{" { String } "}"
+//! Member      := Name ":" [ "*" | "?" ] Name | String
+//! ```
+//!
+//! A quoted terminal is the token it is tokenized to, like `"fn"` or `"("`,
+//! except for the `#` terminals like `"#identifier"`, which stand for a whole
+//! class of tokens, see `TokenClass`.
+//!
+//! A hook is a rule which is parsed by hand, like expressions. It only lists
+//! the terminals it can start with.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::compiler::symbol::SymbolFactory;
+use crate::diagnostic::stream::StockDiagnosticStream;
+use crate::tokenizer::tokenize::Tokenizer;
+use crate::tokenizer::tokens::{Comment, Literal, Token};
+
+/// Where the grammar the parser is generated from lives.
+pub const GRAMMAR_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/grammer.tibineko");
+/// Where the spellings of the tokens live, see `TokenMap`.
+pub const TOKEN_MAP_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/tokenmap.csv");
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grammar {
+    rules: Vec<Rule>,
+    indices: HashMap<String, usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub kind: RuleKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleKind {
+    /// The names of the rules of the variants, in order.
+    Branch(Vec<String>),
+    Product(Vec<Member>),
+    /// The terminals the hand written rule can start with.
+    Hook(Vec<Terminal>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Member {
+    Terminal(Terminal),
+    Field {
+        name: String,
+        rule: String,
+        modifier: Modifier,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Modifier {
+    None,
+    /// `*Rule`
+    List,
+    /// `?Rule`
+    Option,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Terminal {
+    Token(Token),
+    Class(TokenClass),
+}
+
+/// The tokens a `#` terminal stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenClass {
+    Identifier,
+    Int,
+    Float,
+    DoubleInt,
+    DoubleFloat,
+    String,
+    Char,
+    Bool,
+    DocComment,
+    LineComment,
+    BlockComment,
+}
+
+impl TokenClass {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "#identifier" => TokenClass::Identifier,
+            "#int" => TokenClass::Int,
+            "#float" => TokenClass::Float,
+            "#double_int" => TokenClass::DoubleInt,
+            "#double_float" => TokenClass::DoubleFloat,
+            "#string" => TokenClass::String,
+            "#char" => TokenClass::Char,
+            "#bool" => TokenClass::Bool,
+            "#doc_comment" => TokenClass::DocComment,
+            "#line_comment" => TokenClass::LineComment,
+            "#block_comment" => TokenClass::BlockComment,
+            _ => return None,
+        })
+    }
+
+    pub fn matches(self, token: &Token) -> bool {
+        matches!(
+            (self, token),
+            (TokenClass::Identifier, Token::Identifier(_))
+                | (TokenClass::Int, Token::Literal(Literal::IntegerLiteral(_)))
+                | (TokenClass::Float, Token::Literal(Literal::FloatLiteral(_)))
+                | (
+                    TokenClass::DoubleInt,
+                    Token::Literal(Literal::DoubleIntegerLiteral(_))
+                )
+                | (
+                    TokenClass::DoubleFloat,
+                    Token::Literal(Literal::DoubleFloatLiteral(_))
+                )
+                | (
+                    TokenClass::String,
+                    Token::Literal(Literal::StringLiteral(_))
+                )
+                | (TokenClass::Char, Token::Literal(Literal::CharLiteral(_)))
+                | (TokenClass::Bool, Token::Literal(Literal::BoolLiteral(_)))
+                | (
+                    TokenClass::DocComment,
+                    Token::Comment(Comment::DocComment(_))
+                )
+                | (
+                    TokenClass::LineComment,
+                    Token::Comment(Comment::LineComment)
+                )
+                | (
+                    TokenClass::BlockComment,
+                    Token::Comment(Comment::BlockComment)
+                )
+        )
+    }
+
+    /// Whether the tokens carry a value, like the name of an identifier.
+    /// Products of such a terminal are parsed by hand in `manual_parser`.
+    pub fn has_value(self) -> bool {
+        !matches!(self, TokenClass::LineComment | TokenClass::BlockComment)
+    }
+}
+
+/// A grammar which can't be read, with the byte offset of the problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrammarErr {
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for GrammarErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.position)
+    }
+}
+
+impl Grammar {
+    /// Reads a grammar. Every rule a branch or a field refers to has to be
+    /// defined.
+    pub fn parse(source: &str) -> Result<Self, GrammarErr> {
+        let mut reader = GrammarReader {
+            source,
+            position: 0,
+            references: Vec::new(),
+        };
+        let mut grammar = Grammar {
+            rules: Vec::new(),
+            indices: HashMap::new(),
+        };
+        while let Some((position, word)) = reader.next_word()? {
+            let rule = match word {
+                GrammarToken::Word("branch") => reader.read_branch()?,
+                GrammarToken::Word("product") => reader.read_product()?,
+                GrammarToken::Word("hook") => reader.read_hook()?,
+                _ => {
+                    let message = "expected `branch`, `product` or `hook`";
+                    return Err(reader.error(position, message));
+                }
+            };
+            if grammar.indices.contains_key(&rule.name) {
+                let message = format!("rule `{}` is defined twice", rule.name);
+                return Err(GrammarErr { position, message });
+            }
+            grammar
+                .indices
+                .insert(rule.name.clone(), grammar.rules.len());
+            grammar.rules.push(rule);
+        }
+        for (position, name) in reader.references {
+            if !grammar.indices.contains_key(name) {
+                let message = format!("rule `{name}` is not defined");
+                return Err(GrammarErr { position, message });
+            }
+        }
+        Ok(grammar)
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Rule> {
+        self.indices.get(name).map(|&index| &self.rules[index])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GrammarToken<'src> {
+    Word(&'src str),
+    /// A quoted string, without the quotes.
+    String(&'src str),
+    Punct(char),
+}
+
+struct GrammarReader<'src> {
+    source: &'src str,
+    position: usize,
+    /// The rules referred to so far, checked once all rules are read.
+    references: Vec<(usize, &'src str)>,
+}
+
+impl<'src> GrammarReader<'src> {
+    fn error(&self, position: usize, message: &str) -> GrammarErr {
+        GrammarErr {
+            position,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_trivia(&mut self) {
+        loop {
+            let rest = &self.source[self.position..];
+            let trimmed = rest.trim_start();
+            self.position += rest.len() - trimmed.len();
+            if trimmed.starts_with("//") {
+                self.position += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if trimmed.starts_with("/*") {
+                self.position += trimmed.find("*/").map_or(trimmed.len(), |end| end + 2);
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<(usize, GrammarToken<'src>)>, GrammarErr> {
+        let position = self.position;
+        let token = self.next_word();
+        self.position = position;
+        token
+    }
+
+    fn next_word(&mut self) -> Result<Option<(usize, GrammarToken<'src>)>, GrammarErr> {
+        self.skip_trivia();
+        let begin = self.position;
+        let rest = &self.source[begin..];
+        let Some(first) = rest.chars().next() else {
+            return Ok(None);
+        };
+        let token = match first {
+            '{' | '}' | ':' | '*' | '?' => {
+                self.position += 1;
+                GrammarToken::Punct(first)
+            }
+            '"' => {
+                let Some(len) = rest[1..].find('"') else {
+                    return Err(self.error(begin, "unterminated string"));
+                };
+                self.position += len + 2;
+                GrammarToken::String(&rest[1..len + 1])
+            }
+            first if first.is_ascii_alphabetic() || first == '_' => {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                self.position += len;
+                GrammarToken::Word(&rest[..len])
+            }
+            _ => return Err(self.error(begin, &format!("unexpected character `{first}`"))),
+        };
+        Ok(Some((begin, token)))
+    }
+
+    fn expect_punct(&mut self, punct: char) -> Result<(), GrammarErr> {
+        match self.next_word()? {
+            Some((_, GrammarToken::Punct(found))) if found == punct => Ok(()),
+            _ => Err(self.error(self.position, &format!("expected `{punct}`"))),
+        }
+    }
+
+    fn expect_name(&mut self) -> Result<(usize, &'src str), GrammarErr> {
+        match self.next_word()? {
+            Some((position, GrammarToken::Word(name))) if !is_keyword(name) => Ok((position, name)),
+            _ => Err(self.error(self.position, "expected a name")),
+        }
+    }
+
+    fn expect_string(&mut self) -> Result<(usize, &'src str), GrammarErr> {
+        match self.next_word()? {
+            Some((position, GrammarToken::String(string))) => Ok((position, string)),
+            _ => Err(self.error(self.position, "expected a string")),
+        }
+    }
+
+    /// Skips the `with "note"` after a variant or member. Notes only tell the
+    /// generator how to lay out the AST, like boxing a node.
+    fn skip_note(&mut self) -> Result<(), GrammarErr> {
+        if let Some((_, GrammarToken::Word("with"))) = self.peek()? {
+            self.next_word()?;
+            self.expect_string()?;
+        }
+        Ok(())
+    }
+
+    fn at_closing_brace(&mut self) -> Result<bool, GrammarErr> {
+        match self.peek()? {
+            Some((_, GrammarToken::Punct('}'))) => {
+                self.next_word()?;
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => Err(self.error(self.position, "expected `}`")),
+        }
+    }
+
+    fn read_branch(&mut self) -> Result<Rule, GrammarErr> {
+        let (_, name) = self.expect_name()?;
+        self.expect_punct('{')?;
+        let mut variants = Vec::new();
+        while !self.at_closing_brace()? {
+            let (position, variant) = self.expect_name()?;
+            self.references.push((position, variant));
+            variants.push(variant.to_string());
+            self.skip_note()?;
+        }
+        Ok(Rule {
+            name: name.to_string(),
+            kind: RuleKind::Branch(variants),
+        })
+    }
+
+    fn read_product(&mut self) -> Result<Rule, GrammarErr> {
+        let (_, name) = self.expect_name()?;
+        let mut members = Vec::new();
+        if let Some((_, GrammarToken::Word("with"))) = self.peek()? {
+            self.next_word()?;
+            let (position, terminal) = self.expect_string()?;
+            members.push(Member::Terminal(resolve_terminal(position, terminal)?));
+        } else {
+            self.expect_punct('{')?;
+            while !self.at_closing_brace()? {
+                members.push(self.read_member()?);
+                self.skip_note()?;
+            }
+        }
+        Ok(Rule {
+            name: name.to_string(),
+            kind: RuleKind::Product(members),
+        })
+    }
+
+    fn read_hook(&mut self) -> Result<Rule, GrammarErr> {
+        let (_, name) = self.expect_name()?;
+        self.expect_punct('{')?;
+        let mut terminals = Vec::new();
+        while !self.at_closing_brace()? {
+            let (position, terminal) = self.expect_string()?;
+            terminals.push(resolve_terminal(position, terminal)?);
+        }
+        Ok(Rule {
+            name: name.to_string(),
+            kind: RuleKind::Hook(terminals),
+        })
+    }
+
+    fn read_member(&mut self) -> Result<Member, GrammarErr> {
+        if let Some((position, GrammarToken::String(terminal))) = self.peek()? {
+            self.next_word()?;
+            return Ok(Member::Terminal(resolve_terminal(position, terminal)?));
+        }
+        let (_, name) = self.expect_name()?;
+        self.expect_punct(':')?;
+        let modifier = match self.peek()? {
+            Some((_, GrammarToken::Punct('*'))) => Modifier::List,
+            Some((_, GrammarToken::Punct('?'))) => Modifier::Option,
+            _ => Modifier::None,
+        };
+        if modifier != Modifier::None {
+            self.next_word()?;
+        }
+        let (position, rule) = self.expect_name()?;
+        self.references.push((position, rule));
+        Ok(Member::Field {
+            name: name.to_string(),
+            rule: rule.to_string(),
+            modifier,
+        })
+    }
+}
+
+fn is_keyword(word: &str) -> bool {
+    matches!(word, "branch" | "product" | "hook" | "with")
+}
+
+/// Finds the token a quoted terminal of the grammar stands for.
+fn resolve_terminal(position: usize, terminal: &str) -> Result<Terminal, GrammarErr> {
+    if let Some(class) = TokenClass::from_name(terminal) {
+        return Ok(Terminal::Class(class));
+    }
+    match tokenize_one(terminal) {
+        Some(token) if !matches!(token, Token::Identifier(_)) => Ok(Terminal::Token(token)),
+        _ => Err(GrammarErr {
+            position,
+            message: format!("`{terminal}` is not a token"),
+        }),
+    }
+}
+
+/// The token `text` is tokenized to, if it is exactly one token.
+fn tokenize_one(text: &str) -> Option<Token> {
+    let mut symbols = SymbolFactory::new(text);
+    let mut diagnostic_stream = StockDiagnosticStream::new();
+    let (tokens, _) = Tokenizer::new(text, &mut symbols).tokenize(&mut diagnostic_stream);
+    if !diagnostic_stream.into_vec().is_empty() {
+        return None;
+    }
+    match tokens.as_slice() {
+        [(token, _), (Token::EndOfFile, _)] => Some(*token),
+        _ => None,
+    }
+}
+
+/// The spellings of the tokens, read from `assets/tokenmap.csv`, which the
+/// tokenizer is generated from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenMap {
+    spellings: Vec<(String, Token)>,
+}
+
+impl TokenMap {
+    /// Reads the lines `"spelling", "Token::..."` of the token map. The `#`
+    /// entries stand for classes of tokens and have no single spelling, so
+    /// they are left out.
+    pub fn parse(csv: &str) -> Result<Self, GrammarErr> {
+        let mut spellings = Vec::new();
+        let mut position = 0;
+        for line in csv.split_inclusive('\n') {
+            let fields = line.split('"').collect::<Vec<_>>();
+            match fields.as_slice() {
+                [_, spelling, _, _, ..] if spelling.starts_with('#') && spelling.len() > 1 => {}
+                [_, spelling, _, _, ..] => {
+                    let Some(token) = tokenize_one(spelling) else {
+                        let message = format!("`{spelling}` is not a token");
+                        return Err(GrammarErr { position, message });
+                    };
+                    spellings.push((spelling.to_string(), token));
+                }
+                _ if line.trim().is_empty() => {}
+                _ => {
+                    return Err(GrammarErr {
+                        position,
+                        message: "expected `\"spelling\", \"token\"`".to_string(),
+                    })
+                }
+            }
+            position += line.len();
+        }
+        Ok(Self { spellings })
+    }
+
+    /// Every spelling with the token it is tokenized to.
+    pub fn spellings(&self) -> &[(String, Token)] {
+        &self.spellings
+    }
+
+    /// How `token` is written.
+    pub fn spelling(&self, token: &Token) -> Option<&str> {
+        self.spellings
+            .iter()
+            .find(|(_, spelled)| spelled == token)
+            .map(|(spelling, _)| spelling.as_str())
+    }
+}
@@ -0,0 +1,398 @@
+//! A parser which interprets a `Grammar` instead of being generated from it.
+//!
+//! The interpreter is the reference `GeneratedParser` is tested against: it
+//! is slow and recovers from no errors, but it is simple enough to be
+//! obviously right. Where the generated parser decides by peeking at the next
+//! tokens, the interpreter tries every alternative in order and backtracks:
+//!
+//! - a branch is the first of its variants which parses,
+//! - `*Rule` parses `Rule` until it fails,
+//! - `?Rule` parses `Rule` if it can.
+//!
+//! A `*` or `?` rule which matches no tokens counts as absent, like the
+//! generated parser, which never enters a rule the next token can't start.
+//!
+//! Hooks are parsed by the hand written parser, from where the interpreter
+//! got to, and fail if it reports a diagnostic.
+//!
+//! The result is a `Tree`, which prints like `ASTPrinter` prints the AST, so
+//! the two parsers can be compared by their output.
+
+use std::fmt::Display;
+
+use crate::compiler::arena::Arena;
+use crate::compiler::context::frontend::CompilerFrontendContext;
+use crate::compiler::source_holder::SourceHolder;
+use crate::compiler::span::Span;
+use crate::compiler::symbol::{Interner, SymbolFactory};
+use crate::diagnostic::stream::StockDiagnosticStream;
+use crate::parser::ast_node::Node;
+use crate::parser::generated_ast_printer::ASTPrinter;
+use crate::parser::generated_parser::GeneratedParser;
+use crate::parser::grammar::{Grammar, Member, Modifier, Rule, RuleKind, Terminal, TokenClass};
+use crate::parser::parse::Parser;
+use crate::parser::tracer::NopeTracer;
+use crate::tokenizer::token_stream::TokenStream;
+use crate::tokenizer::tokenize::Tokenizer;
+use crate::tokenizer::tokens::{Comment, Literal, Token};
+
+/// How deeply rules may be nested before the interpreter gives up, so that a
+/// left recursive grammar fails instead of overflowing the stack.
+const MAX_RULE_DEPTH: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tree {
+    Product {
+        rule: String,
+        /// The rule names of the fields and their values. Terminals are left
+        /// out, like in the AST.
+        fields: Vec<(String, Tree)>,
+    },
+    Variant {
+        name: String,
+        value: Box<Tree>,
+    },
+    /// A product of a single `#` terminal, like an identifier, with the
+    /// value of its token as `ASTPrinter` prints it.
+    Leaf {
+        rule: String,
+        value: String,
+    },
+    List(Vec<Tree>),
+    /// A node of a hook, printed by `ASTPrinter`.
+    Hook(String),
+    None,
+}
+
+impl Display for Tree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tree::Product { rule, fields } => {
+                write!(f, r#"{{"type":"{rule}""#)?;
+                for (name, value) in fields {
+                    write!(f, r#","{name}":{value}"#)?;
+                }
+                write!(f, "}}")
+            }
+            Tree::Variant { name, value } => write!(f, r#"{{"kind":"{name}","value":{value}}}"#),
+            Tree::Leaf { rule, value } => write!(f, r#"{{"type":"{rule}","value":"{value}"}}"#),
+            Tree::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Tree::Hook(printed) => write!(f, "{printed}"),
+            Tree::None => write!(f, "null"),
+        }
+    }
+}
+
+/// Prints a node of a hook with `ASTPrinter`.
+struct Printed<'a, N> {
+    node: N,
+    arena: &'a Arena,
+    source: &'a str,
+}
+
+impl<'a, N> Printed<'a, N> {
+    fn new(node: N, arena: &'a Arena, source: &'a str) -> Self {
+        Self {
+            node,
+            arena,
+            source,
+        }
+    }
+}
+
+impl<N: Node> Display for Printed<'_, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let source_holder = SourceHolder::from_source(self.source);
+        let mut printer = ASTPrinter {
+            interner: Interner::global(),
+            arena: self.arena,
+            source_holder: &source_holder,
+            writer: f,
+        };
+        self.node.accept(&mut printer)
+    }
+}
+
+/// The source doesn't match the grammar. `span` is the token the
+/// interpreter got furthest to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterpretErr {
+    pub span: Span,
+    /// What would have been accepted at `span`.
+    pub expected: String,
+}
+
+impl Display for InterpretErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected {} at {}", self.expected, self.span)
+    }
+}
+
+pub struct GrammarInterpreter<'g> {
+    grammar: &'g Grammar,
+}
+
+impl<'g> GrammarInterpreter<'g> {
+    pub fn new(grammar: &'g Grammar) -> Self {
+        Self { grammar }
+    }
+
+    /// Parses all of `source` as the rule `rule`.
+    pub fn parse(&self, rule: &str, source: &str) -> Result<Tree, InterpretErr> {
+        let rule = self.grammar.get(rule).unwrap_or_else(|| {
+            panic!("rule `{rule}` is not defined");
+        });
+        let mut symbols = SymbolFactory::new(source);
+        let mut diagnostic_stream = StockDiagnosticStream::new();
+        let (tokens, _) = Tokenizer::new(source, &mut symbols).tokenize(&mut diagnostic_stream);
+        let mut run = Run {
+            grammar: self.grammar,
+            source,
+            all_tokens: tokens.clone(),
+            tokens: TokenStream::new(tokens),
+            depth: 0,
+            furthest: None,
+        };
+        let tree = run.rule(rule);
+        match tree {
+            Some(tree) if run.peek() == Some(Token::EndOfFile) => Ok(tree),
+            Some(_) => {
+                run.fail("the end of the file".to_string());
+                Err(run.error())
+            }
+            None => Err(run.error()),
+        }
+    }
+}
+
+struct Run<'g, 'src> {
+    grammar: &'g Grammar,
+    source: &'src str,
+    /// The tokens `tokens` was made of, for the parser of the hooks.
+    all_tokens: Vec<(Token, Span)>,
+    tokens: TokenStream,
+    depth: usize,
+    /// The furthest position parsing failed at and what was expected there.
+    furthest: Option<(usize, Span, String)>,
+}
+
+impl<'g> Run<'g, '_> {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.peek(0).map(|(token, _)| token)
+    }
+
+    fn fail(&mut self, expected: String) {
+        let position = self.tokens.position();
+        if self
+            .furthest
+            .as_ref()
+            .is_some_and(|(furthest, _, _)| *furthest > position)
+        {
+            return;
+        }
+        self.furthest = Some((position, self.tokens.get_now_span(), expected));
+    }
+
+    fn error(self) -> InterpretErr {
+        let (_, span, expected) = self.furthest.expect("parsing failed somewhere");
+        InterpretErr { span, expected }
+    }
+
+    /// Runs `parse` and rewinds the tokens if it fails.
+    fn attempt(&mut self, parse: impl FnOnce(&mut Self) -> Option<Tree>) -> Option<Tree> {
+        self.tokens.checkpoint();
+        let tree = parse(self);
+        if tree.is_some() {
+            self.tokens.commit();
+        } else {
+            self.tokens.rollback();
+        }
+        tree
+    }
+
+    /// Like `attempt`, but a tree which matches no tokens is thrown away.
+    fn attempt_nonempty(&mut self, rule: &Rule) -> Option<Tree> {
+        let start = self.tokens.position();
+        self.tokens.checkpoint();
+        let tree = self.rule(rule);
+        if tree.is_some() && self.tokens.position() > start {
+            self.tokens.commit();
+            tree
+        } else {
+            self.tokens.rollback();
+            None
+        }
+    }
+
+    fn rule(&mut self, rule: &Rule) -> Option<Tree> {
+        if self.depth == MAX_RULE_DEPTH {
+            self.fail(format!("less nesting than {MAX_RULE_DEPTH} rules"));
+            return None;
+        }
+        self.depth += 1;
+        let tree = match &rule.kind {
+            RuleKind::Branch(variants) => self.branch(variants),
+            RuleKind::Product(members) => match members.as_slice() {
+                [Member::Terminal(Terminal::Class(class))] if class.has_value() => {
+                    self.leaf(&rule.name, *class)
+                }
+                members => self.product(&rule.name, members),
+            },
+            RuleKind::Hook(_) => self.hook(&rule.name),
+        };
+        self.depth -= 1;
+        tree
+    }
+
+    fn get(&self, name: &str) -> &'g Rule {
+        self.grammar
+            .get(name)
+            .expect("the grammar checks that every rule is defined")
+    }
+
+    fn branch(&mut self, variants: &[String]) -> Option<Tree> {
+        for variant in variants {
+            let rule = self.get(variant);
+            if let Some(value) = self.attempt(|this| this.rule(rule)) {
+                return Some(Tree::Variant {
+                    name: variant.clone(),
+                    value: Box::new(value),
+                });
+            }
+        }
+        None
+    }
+
+    fn product(&mut self, name: &str, members: &[Member]) -> Option<Tree> {
+        let mut fields = Vec::new();
+        for member in members {
+            match member {
+                Member::Terminal(terminal) => self.terminal(terminal)?,
+                Member::Field { rule, modifier, .. } => {
+                    let rule = self.get(rule);
+                    let value = match modifier {
+                        Modifier::None => self.rule(rule)?,
+                        Modifier::Option => self.attempt_nonempty(rule).unwrap_or(Tree::None),
+                        Modifier::List => {
+                            Tree::List(std::iter::from_fn(|| self.attempt_nonempty(rule)).collect())
+                        }
+                    };
+                    fields.push((rule.name.clone(), value));
+                }
+            }
+        }
+        Some(Tree::Product {
+            rule: name.to_string(),
+            fields,
+        })
+    }
+
+    fn terminal(&mut self, terminal: &Terminal) -> Option<()> {
+        let matched = match (terminal, self.peek()) {
+            (Terminal::Token(expected), Some(found)) => *expected == found,
+            (Terminal::Class(class), Some(found)) => class.matches(&found),
+            (_, None) => false,
+        };
+        if matched {
+            self.tokens.next();
+            Some(())
+        } else {
+            self.fail(match terminal {
+                Terminal::Token(token) => format!("`{token}`"),
+                Terminal::Class(class) => format!("{class:?}"),
+            });
+            None
+        }
+    }
+
+    /// Parses the hook `name` with the hand written parser, from the current
+    /// token on.
+    fn hook(&mut self, name: &str) -> Option<Tree> {
+        let mut symbols = SymbolFactory::new(self.source);
+        let mut diagnostic_stream = StockDiagnosticStream::new();
+        let arena = Arena::new();
+        let mut parser: Parser<'_, '_, '_, _, NopeTracer> = Parser::new(
+            TokenStream::new(self.all_tokens.clone()),
+            CompilerFrontendContext {
+                source: self.source,
+                symbol_factory: &mut symbols,
+                ast_arena: &arena,
+            },
+            &mut diagnostic_stream,
+        );
+        parser.tokens.seek(self.tokens.position());
+        let source = self.source;
+        let printed = match name {
+            "Expression" => parser
+                .parse_Expression()
+                .map(|node| Printed::new(node, &arena, source).to_string()),
+            "Type" => parser
+                .parse_Type()
+                .map(|node| Printed::new(node, &arena, source).to_string()),
+            "Statement" => parser
+                .parse_Statement()
+                .map(|node| Printed::new(node, &arena, source).to_string()),
+            "Pattern" => parser
+                .parse_Pattern()
+                .map(|node| Printed::new(node, &arena, source).to_string()),
+            "Annotation" => parser
+                .parse_Annotation()
+                .map(|node| Printed::new(node, &arena, source).to_string()),
+            name => panic!("the hook `{name}` has no parser"),
+        };
+        let end = parser.tokens.position();
+        match printed {
+            Ok(printed) if diagnostic_stream.into_vec().is_empty() => {
+                self.tokens.seek(end);
+                Some(Tree::Hook(printed))
+            }
+            _ => {
+                self.fail(name.to_string());
+                None
+            }
+        }
+    }
+
+    /// Parses the token of a product of a single `#` terminal and prints its
+    /// value like `ASTPrinter` prints the node `manual_parser` makes of it.
+    fn leaf(&mut self, rule: &str, class: TokenClass) -> Option<Tree> {
+        let Some(token) = self.peek().filter(|token| class.matches(token)) else {
+            self.fail(format!("{class:?}"));
+            return None;
+        };
+        self.tokens.next();
+        let value = match token {
+            Token::Identifier(symbol) => symbol.as_str().to_string(),
+            Token::Literal(Literal::StringLiteral(span)) => span.into(self.source).to_string(),
+            Token::Comment(Comment::DocComment(span)) => format!("{rule} {{ span: {span:?} }}"),
+            Token::Literal(Literal::IntegerLiteral(value)) => {
+                format!("{rule} {{ value: {value:?} }}")
+            }
+            Token::Literal(Literal::FloatLiteral(value)) => {
+                format!("{rule} {{ value: {value:?} }}")
+            }
+            Token::Literal(Literal::DoubleIntegerLiteral(value)) => {
+                format!("{rule} {{ value: {value:?} }}")
+            }
+            Token::Literal(Literal::DoubleFloatLiteral(value)) => {
+                format!("{rule} {{ value: {value:?} }}")
+            }
+            Token::Literal(Literal::CharLiteral(value)) => format!("{rule} {{ value: {value:?} }}"),
+            Token::Literal(Literal::BoolLiteral(value)) => format!("{rule} {{ value: {value:?} }}"),
+            token => unreachable!("{token:?} has no value"),
+        };
+        Some(Tree::Leaf {
+            rule: rule.to_string(),
+            value,
+        })
+    }
+}
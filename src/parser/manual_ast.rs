@@ -1,8 +1,17 @@
+#![allow(non_snake_case)]
+
 use crate::{
-    compiler::{span::Span, symbol::Symbol},
+    compiler::{arena::Arena, span::Span, symbol::Symbol},
+    parser::ast::{ASTFolder, ASTVisitorMut},
+    parser::ast_node::{fold_iter, visit_boxed_mut, visit_iter_mut},
     utility::hashable_float::HashableFloat,
 };
 
+pub use crate::parser::annotation::Annotation;
+pub use crate::parser::expression::{Expression, Type};
+pub use crate::parser::pattern::Pattern;
+pub use crate::parser::statement::Statement;
+
 #[derive(Debug, Copy, Clone, std::hash::Hash, PartialEq, Eq)]
 pub struct Identifier {
     pub symbol: Symbol,
@@ -53,3 +62,340 @@ pub struct LineComment {}
 
 #[derive(Debug, Copy, Clone, std::hash::Hash, PartialEq, Eq)]
 pub struct BlockComment {}
+
+pub fn walk_Expression<V: ASTVisitorMut>(
+    visitor: &mut V,
+    node: &mut Expression,
+    arena: &mut Arena,
+) {
+    let mut visit = |v: &mut Expression, arena: &mut Arena| visitor.visit_Expression(v, arena);
+    match node {
+        Expression::Literal(_) | Expression::This | Expression::Invalid => {}
+        Expression::Identifier(name) => visitor.visit_Identifier(name, arena),
+        Expression::Path(segments) => visit_iter_mut(*segments, arena, |v, arena| {
+            visitor.visit_Identifier(v, arena)
+        }),
+        Expression::Parenthesized(inner) | Expression::Propagate(inner) => {
+            visit_boxed_mut(*inner, arena, visit)
+        }
+        Expression::Tuple(items) | Expression::Array(items) => visit_iter_mut(*items, arena, visit),
+        Expression::Struct { name, fields } => {
+            visitor.visit_Identifier(name, arena);
+            visit_iter_mut(*fields, arena, |v, arena| visitor.visit_FieldInit(v, arena));
+        }
+        Expression::Block(block) => visitor.visit_BlockExpression(block, arena),
+        Expression::If {
+            condition,
+            then,
+            otherwise,
+        } => {
+            visit_boxed_mut(*condition, arena, &mut visit);
+            if let Some(otherwise) = otherwise {
+                visit_boxed_mut(*otherwise, arena, visit);
+            }
+            visitor.visit_BlockExpression(then, arena);
+        }
+        Expression::Match { scrutinee, arms } => {
+            visit_boxed_mut(*scrutinee, arena, visit);
+            visit_iter_mut(*arms, arena, |v, arena| visitor.visit_MatchArm(v, arena));
+        }
+        Expression::For {
+            pattern,
+            iterable,
+            body,
+        } => {
+            visit_boxed_mut(*iterable, arena, visit);
+            visitor.visit_Pattern(pattern, arena);
+            visitor.visit_BlockExpression(body, arena);
+        }
+        Expression::Return(value) => {
+            if let Some(value) = value {
+                visit_boxed_mut(*value, arena, visit);
+            }
+        }
+        Expression::TryElse { value, fallback } => {
+            visit_boxed_mut(*value, arena, &mut visit);
+            visit_boxed_mut(*fallback, arena, visit);
+        }
+        Expression::Prefix { operand, .. } => visit_boxed_mut(*operand, arena, visit),
+        Expression::Infix { lhs, rhs, .. } => {
+            visit_boxed_mut(*lhs, arena, &mut visit);
+            visit_boxed_mut(*rhs, arena, visit);
+        }
+        Expression::Cast { value, ty } => {
+            visit_boxed_mut(*value, arena, visit);
+            visitor.visit_Type(ty, arena);
+        }
+        Expression::Call { callee, arguments } => {
+            visit_boxed_mut(*callee, arena, &mut visit);
+            visit_iter_mut(*arguments, arena, visit);
+        }
+        Expression::MethodCall {
+            receiver,
+            method,
+            arguments,
+        } => {
+            visit_boxed_mut(*receiver, arena, &mut visit);
+            visit_iter_mut(*arguments, arena, visit);
+            visitor.visit_Identifier(method, arena);
+        }
+        Expression::Field { receiver, field } => {
+            visit_boxed_mut(*receiver, arena, visit);
+            visitor.visit_Identifier(field, arena);
+        }
+        Expression::Index { receiver, index } => {
+            visit_boxed_mut(*receiver, arena, &mut visit);
+            visit_boxed_mut(*index, arena, visit);
+        }
+    }
+}
+
+pub fn rebuild_Expression<F: ASTFolder>(
+    folder: &mut F,
+    node: &Expression,
+    from: &Arena,
+    to: &Arena,
+) -> Expression {
+    macro_rules! fold_box {
+        ($boxed:expr) => {{
+            let folded = folder.fold_Expression($boxed.get(from), from, to);
+            to.alloc(folded)
+        }};
+    }
+    macro_rules! fold_iter {
+        ($items:expr) => {
+            fold_iter($items, from, to, |v| folder.fold_Expression(v, from, to))
+        };
+    }
+    match *node {
+        Expression::Identifier(identifier) => {
+            Expression::Identifier(folder.fold_Identifier(&identifier, from, to))
+        }
+        Expression::Literal(literal) => Expression::Literal(literal),
+        Expression::This => Expression::This,
+        Expression::Path(segments) => Expression::Path(fold_iter(segments, from, to, |v| {
+            folder.fold_Identifier(v, from, to)
+        })),
+        Expression::Parenthesized(inner) => Expression::Parenthesized(fold_box!(inner)),
+        Expression::Tuple(items) => Expression::Tuple(fold_iter!(items)),
+        Expression::Array(items) => Expression::Array(fold_iter!(items)),
+        Expression::Struct { name, fields } => Expression::Struct {
+            name: folder.fold_Identifier(&name, from, to),
+            fields: fold_iter(fields, from, to, |v| folder.fold_FieldInit(v, from, to)),
+        },
+        Expression::Block(block) => {
+            Expression::Block(folder.fold_BlockExpression(&block, from, to))
+        }
+        Expression::If {
+            condition,
+            then,
+            otherwise,
+        } => Expression::If {
+            condition: fold_box!(condition),
+            then: folder.fold_BlockExpression(&then, from, to),
+            otherwise: otherwise.map(|otherwise| fold_box!(otherwise)),
+        },
+        Expression::Match { scrutinee, arms } => Expression::Match {
+            scrutinee: fold_box!(scrutinee),
+            arms: fold_iter(arms, from, to, |v| folder.fold_MatchArm(v, from, to)),
+        },
+        Expression::For {
+            pattern,
+            iterable,
+            body,
+        } => Expression::For {
+            pattern: folder.fold_Pattern(&pattern, from, to),
+            iterable: fold_box!(iterable),
+            body: folder.fold_BlockExpression(&body, from, to),
+        },
+        Expression::Return(value) => Expression::Return(value.map(|value| fold_box!(value))),
+        Expression::TryElse { value, fallback } => Expression::TryElse {
+            value: fold_box!(value),
+            fallback: fold_box!(fallback),
+        },
+        Expression::Prefix { operation, operand } => Expression::Prefix {
+            operation,
+            operand: fold_box!(operand),
+        },
+        Expression::Infix {
+            operation,
+            lhs,
+            rhs,
+        } => Expression::Infix {
+            operation,
+            lhs: fold_box!(lhs),
+            rhs: fold_box!(rhs),
+        },
+        Expression::Propagate(inner) => Expression::Propagate(fold_box!(inner)),
+        Expression::Cast { value, ty } => Expression::Cast {
+            value: fold_box!(value),
+            ty: folder.fold_Type(&ty, from, to),
+        },
+        Expression::Call { callee, arguments } => Expression::Call {
+            callee: fold_box!(callee),
+            arguments: fold_iter!(arguments),
+        },
+        Expression::MethodCall {
+            receiver,
+            method,
+            arguments,
+        } => Expression::MethodCall {
+            receiver: fold_box!(receiver),
+            method: folder.fold_Identifier(&method, from, to),
+            arguments: fold_iter!(arguments),
+        },
+        Expression::Field { receiver, field } => Expression::Field {
+            receiver: fold_box!(receiver),
+            field: folder.fold_Identifier(&field, from, to),
+        },
+        Expression::Index { receiver, index } => Expression::Index {
+            receiver: fold_box!(receiver),
+            index: fold_box!(index),
+        },
+        Expression::Invalid => Expression::Invalid,
+    }
+}
+
+pub fn walk_Type<V: ASTVisitorMut>(visitor: &mut V, node: &mut Type, arena: &mut Arena) {
+    match node {
+        Type::Named(name) => visitor.visit_Identifier(name, arena),
+        Type::Generic { name, arguments } => {
+            visitor.visit_Identifier(name, arena);
+            visit_iter_mut(*arguments, arena, |v, arena| visitor.visit_Type(v, arena));
+        }
+        Type::Primitive(_) | Type::Invalid => {}
+    }
+}
+
+pub fn rebuild_Type<F: ASTFolder>(folder: &mut F, node: &Type, from: &Arena, to: &Arena) -> Type {
+    match *node {
+        Type::Named(name) => Type::Named(folder.fold_Identifier(&name, from, to)),
+        Type::Generic { name, arguments } => Type::Generic {
+            name: folder.fold_Identifier(&name, from, to),
+            arguments: fold_iter(arguments, from, to, |v| folder.fold_Type(v, from, to)),
+        },
+        node => node,
+    }
+}
+
+pub fn walk_Pattern<V: ASTVisitorMut>(visitor: &mut V, node: &mut Pattern, arena: &mut Arena) {
+    match node {
+        Pattern::Binding { name, .. } => visitor.visit_Identifier(name, arena),
+        Pattern::Tuple(items) => {
+            visit_iter_mut(*items, arena, |v, arena| visitor.visit_Pattern(v, arena))
+        }
+        Pattern::Path(path) => {
+            visit_iter_mut(*path, arena, |v, arena| visitor.visit_Identifier(v, arena))
+        }
+        Pattern::TupleStruct { path, items } => {
+            visit_iter_mut(*path, arena, |v, arena| visitor.visit_Identifier(v, arena));
+            visit_iter_mut(*items, arena, |v, arena| visitor.visit_Pattern(v, arena));
+        }
+        Pattern::Struct { path, fields } => {
+            visit_iter_mut(*path, arena, |v, arena| visitor.visit_Identifier(v, arena));
+            visit_iter_mut(*fields, arena, |v, arena| {
+                visitor.visit_FieldPattern(v, arena)
+            });
+        }
+        Pattern::Wildcard | Pattern::Literal(_) | Pattern::Invalid => {}
+    }
+}
+
+pub fn rebuild_Pattern<F: ASTFolder>(
+    folder: &mut F,
+    node: &Pattern,
+    from: &Arena,
+    to: &Arena,
+) -> Pattern {
+    match *node {
+        Pattern::Binding { name, mutable } => Pattern::Binding {
+            name: folder.fold_Identifier(&name, from, to),
+            mutable,
+        },
+        Pattern::Tuple(items) => Pattern::Tuple(fold_iter(items, from, to, |v| {
+            folder.fold_Pattern(v, from, to)
+        })),
+        Pattern::Path(path) => Pattern::Path(fold_iter(path, from, to, |v| {
+            folder.fold_Identifier(v, from, to)
+        })),
+        Pattern::TupleStruct { path, items } => Pattern::TupleStruct {
+            path: fold_iter(path, from, to, |v| folder.fold_Identifier(v, from, to)),
+            items: fold_iter(items, from, to, |v| folder.fold_Pattern(v, from, to)),
+        },
+        Pattern::Struct { path, fields } => Pattern::Struct {
+            path: fold_iter(path, from, to, |v| folder.fold_Identifier(v, from, to)),
+            fields: fold_iter(fields, from, to, |v| folder.fold_FieldPattern(v, from, to)),
+        },
+        node => node,
+    }
+}
+
+pub fn walk_Statement<V: ASTVisitorMut>(visitor: &mut V, node: &mut Statement, arena: &mut Arena) {
+    match node {
+        Statement::Let { pattern, ty, value } => {
+            visitor.visit_Pattern(pattern, arena);
+            if let Some(v) = ty {
+                visitor.visit_Type(v, arena);
+            }
+            if let Some(v) = value {
+                visitor.visit_Expression(v, arena);
+            }
+        }
+        Statement::Ignore(value) | Statement::Expression(value) | Statement::Value(value) => {
+            visitor.visit_Expression(value, arena)
+        }
+        Statement::Invalid => {}
+    }
+}
+
+pub fn rebuild_Statement<F: ASTFolder>(
+    folder: &mut F,
+    node: &Statement,
+    from: &Arena,
+    to: &Arena,
+) -> Statement {
+    match *node {
+        Statement::Let { pattern, ty, value } => Statement::Let {
+            pattern: folder.fold_Pattern(&pattern, from, to),
+            ty: ty.map(|v| folder.fold_Type(&v, from, to)),
+            value: value.map(|v| folder.fold_Expression(&v, from, to)),
+        },
+        Statement::Ignore(value) => Statement::Ignore(folder.fold_Expression(&value, from, to)),
+        Statement::Expression(value) => {
+            Statement::Expression(folder.fold_Expression(&value, from, to))
+        }
+        Statement::Value(value) => Statement::Value(folder.fold_Expression(&value, from, to)),
+        Statement::Invalid => Statement::Invalid,
+    }
+}
+
+pub fn walk_Annotation<V: ASTVisitorMut>(
+    visitor: &mut V,
+    node: &mut Annotation,
+    arena: &mut Arena,
+) {
+    match node {
+        Annotation::Parsed { name, .. } => visitor.visit_Identifier(name, arena),
+        Annotation::Invalid => {}
+    }
+}
+
+pub fn rebuild_Annotation<F: ASTFolder>(
+    folder: &mut F,
+    node: &Annotation,
+    from: &Arena,
+    to: &Arena,
+) -> Annotation {
+    match *node {
+        Annotation::Parsed {
+            name,
+            arguments,
+            span,
+        } => Annotation::Parsed {
+            name: folder.fold_Identifier(&name, from, to),
+            arguments: fold_iter(arguments, from, to, |argument| *argument),
+            span,
+        },
+        Annotation::Invalid => Annotation::Invalid,
+    }
+}
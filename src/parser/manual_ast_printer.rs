@@ -0,0 +1,295 @@
+//! Printers of the hook rules of the grammar, which the generated printer
+//! calls. They print the same JSON as the generated ones: an object with the
+//! `type` of the node, the `kind` of its variant and its children, or `null`
+//! for an invalid node.
+
+use crate::compiler::arena::{ArenaBox, ArenaIter};
+use crate::parser::annotation::{AnnotationArgument, AnnotationValue};
+use crate::parser::ast::*;
+use crate::parser::ast_node::Node;
+use crate::parser::generated_ast_printer::ASTPrinter;
+use crate::tokenizer::tokens::Literal;
+use std::fmt::{Result, Write};
+
+#[allow(non_snake_case)]
+impl ASTPrinter<'_, '_, '_, '_> {
+    pub(super) fn print_Expression(&mut self, node: &Expression) -> Result {
+        let kind = match node {
+            Expression::Identifier(_) => "Identifier",
+            Expression::Literal(_) => "Literal",
+            Expression::This => "This",
+            Expression::Path(_) => "Path",
+            Expression::Array(_) => "Array",
+            Expression::Struct { .. } => "Struct",
+            Expression::Block(_) => "Block",
+            Expression::If { .. } => "If",
+            Expression::Match { .. } => "Match",
+            Expression::For { .. } => "For",
+            Expression::Return(_) => "Return",
+            Expression::Parenthesized(_) => "Parenthesized",
+            Expression::Tuple(_) => "Tuple",
+            Expression::Prefix { .. } => "Prefix",
+            Expression::Infix { .. } => "Infix",
+            Expression::TryElse { .. } => "TryElse",
+            Expression::Propagate(_) => "Propagate",
+            Expression::Cast { .. } => "Cast",
+            Expression::Call { .. } => "Call",
+            Expression::MethodCall { .. } => "MethodCall",
+            Expression::Field { .. } => "Field",
+            Expression::Index { .. } => "Index",
+            Expression::Invalid => return self.writer.write_str("null"),
+        };
+        write!(self.writer, r#"{{"type":"Expression","kind":"{kind}""#)?;
+        match *node {
+            Expression::Identifier(identifier) => self.print_child("name", &identifier)?,
+            Expression::Literal(literal) => {
+                self.writer.write_str(r#","value":"#)?;
+                self.print_literal(literal)?;
+            }
+            Expression::This => {}
+            Expression::Path(segments) => self.print_iter("segments", segments)?,
+            Expression::Struct { name, fields } => {
+                self.print_child("name", &name)?;
+                self.print_iter("fields", fields)?;
+            }
+            Expression::Block(block) => self.print_child("block", &block)?,
+            Expression::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                self.print_boxed("condition", condition)?;
+                self.print_child("then", &then)?;
+                self.print_option("otherwise", otherwise.map(|v| *v.get(self.arena)))?;
+            }
+            Expression::Match { scrutinee, arms } => {
+                self.print_boxed("scrutinee", scrutinee)?;
+                self.print_iter("arms", arms)?;
+            }
+            Expression::For {
+                pattern,
+                iterable,
+                body,
+            } => {
+                self.print_child("pattern", &pattern)?;
+                self.print_boxed("iterable", iterable)?;
+                self.print_child("body", &body)?;
+            }
+            Expression::Return(value) => {
+                self.print_option("value", value.map(|v| *v.get(self.arena)))?
+            }
+            Expression::TryElse { value, fallback } => {
+                self.print_boxed("value", value)?;
+                self.print_boxed("fallback", fallback)?;
+            }
+            Expression::Parenthesized(inner) | Expression::Propagate(inner) => {
+                self.print_boxed("value", inner)?
+            }
+            Expression::Tuple(items) | Expression::Array(items) => {
+                self.print_iter("items", items)?
+            }
+            Expression::Prefix { operation, operand } => {
+                write!(self.writer, r#","operation":"{operation:?}""#)?;
+                self.print_boxed("operand", operand)?;
+            }
+            Expression::Infix {
+                operation,
+                lhs,
+                rhs,
+            } => {
+                write!(self.writer, r#","operation":"{operation:?}""#)?;
+                self.print_boxed("lhs", lhs)?;
+                self.print_boxed("rhs", rhs)?;
+            }
+            Expression::Cast { value, ty } => {
+                self.print_boxed("value", value)?;
+                self.print_child("Type", &ty)?;
+            }
+            Expression::Call { callee, arguments } => {
+                self.print_boxed("callee", callee)?;
+                self.print_iter("arguments", arguments)?;
+            }
+            Expression::MethodCall {
+                receiver,
+                method,
+                arguments,
+            } => {
+                self.print_boxed("receiver", receiver)?;
+                self.print_child("method", &method)?;
+                self.print_iter("arguments", arguments)?;
+            }
+            Expression::Field { receiver, field } => {
+                self.print_boxed("receiver", receiver)?;
+                self.print_child("field", &field)?;
+            }
+            Expression::Index { receiver, index } => {
+                self.print_boxed("receiver", receiver)?;
+                self.print_boxed("index", index)?;
+            }
+            Expression::Invalid => unreachable!(),
+        }
+        self.writer.write_str("}")
+    }
+
+    pub(super) fn print_Type(&mut self, node: &Type) -> Result {
+        match *node {
+            Type::Primitive(keyword) => write!(
+                self.writer,
+                r#"{{"type":"Type","kind":"Primitive","value":"{keyword:?}"}}"#
+            ),
+            Type::Named(name) => {
+                self.writer.write_str(r#"{"type":"Type","kind":"Named""#)?;
+                self.print_child("name", &name)?;
+                self.writer.write_str("}")
+            }
+            Type::Generic { name, arguments } => {
+                self.writer
+                    .write_str(r#"{"type":"Type","kind":"Generic""#)?;
+                self.print_child("name", &name)?;
+                self.print_iter("arguments", arguments)?;
+                self.writer.write_str("}")
+            }
+            Type::Invalid => self.writer.write_str("null"),
+        }
+    }
+
+    pub(super) fn print_Statement(&mut self, node: &Statement) -> Result {
+        let kind = match node {
+            Statement::Let { .. } => "Let",
+            Statement::Ignore(_) => "Ignore",
+            Statement::Expression(_) => "Expression",
+            Statement::Value(_) => "Value",
+            Statement::Invalid => return self.writer.write_str("null"),
+        };
+        write!(self.writer, r#"{{"type":"Statement","kind":"{kind}""#)?;
+        match *node {
+            Statement::Let { pattern, ty, value } => {
+                self.print_child("pattern", &pattern)?;
+                self.print_option("Type", ty)?;
+                self.print_option("value", value)?;
+            }
+            Statement::Ignore(value) | Statement::Expression(value) | Statement::Value(value) => {
+                self.print_child("value", &value)?
+            }
+            Statement::Invalid => unreachable!(),
+        }
+        self.writer.write_str("}")
+    }
+
+    pub(super) fn print_Pattern(&mut self, node: &Pattern) -> Result {
+        let kind = match node {
+            Pattern::Wildcard => "Wildcard",
+            Pattern::Binding { .. } => "Binding",
+            Pattern::Literal(_) => "Literal",
+            Pattern::Tuple(_) => "Tuple",
+            Pattern::Path(_) => "Path",
+            Pattern::TupleStruct { .. } => "TupleStruct",
+            Pattern::Struct { .. } => "Struct",
+            Pattern::Invalid => return self.writer.write_str("null"),
+        };
+        write!(self.writer, r#"{{"type":"Pattern","kind":"{kind}""#)?;
+        match *node {
+            Pattern::Wildcard => {}
+            Pattern::Binding { name, mutable } => {
+                self.print_child("name", &name)?;
+                write!(self.writer, r#","mutable":{mutable}"#)?;
+            }
+            Pattern::Literal(literal) => {
+                self.writer.write_str(r#","value":"#)?;
+                self.print_literal(literal)?;
+            }
+            Pattern::Tuple(items) => self.print_iter("items", items)?,
+            Pattern::Path(path) => self.print_iter("path", path)?,
+            Pattern::TupleStruct { path, items } => {
+                self.print_iter("path", path)?;
+                self.print_iter("items", items)?;
+            }
+            Pattern::Struct { path, fields } => {
+                self.print_iter("path", path)?;
+                self.print_iter("fields", fields)?;
+            }
+            Pattern::Invalid => unreachable!(),
+        }
+        self.writer.write_str("}")
+    }
+
+    pub(super) fn print_Annotation(&mut self, node: &Annotation) -> Result {
+        let Annotation::Parsed {
+            name, arguments, ..
+        } = *node
+        else {
+            return self.writer.write_str("null");
+        };
+        self.writer.write_str(r#"{"type":"Annotation""#)?;
+        self.print_child("name", &name)?;
+        self.writer.write_str(r#","arguments":["#)?;
+        for (i, argument) in arguments.as_slice(self.arena).iter().enumerate() {
+            if i > 0 {
+                self.writer.write_str(",")?;
+            }
+            match *argument {
+                AnnotationArgument::Positional(value) => {
+                    self.writer.write_str(r#"{"kind":"Positional""#)?;
+                    self.print_annotation_value(value)?;
+                }
+                AnnotationArgument::Named { key, value } => {
+                    self.writer.write_str(r#"{"kind":"Named""#)?;
+                    self.print_child("key", &key)?;
+                    self.print_annotation_value(value)?;
+                }
+            }
+            self.writer.write_str("}")?;
+        }
+        self.writer.write_str("]}")
+    }
+
+    fn print_annotation_value(&mut self, value: AnnotationValue) -> Result {
+        match value {
+            AnnotationValue::Literal(literal) => {
+                self.writer.write_str(r#","value":"#)?;
+                self.print_literal(literal)
+            }
+            AnnotationValue::Identifier(identifier) => self.print_child("value", &identifier),
+        }
+    }
+
+    /// Prints a literal token as the literal node of the grammar.
+    fn print_literal(&mut self, literal: Literal) -> Result {
+        match literal {
+            Literal::IntegerLiteral(value) => IntLiteral { value }.accept(self),
+            Literal::FloatLiteral(value) => FloatLiteral { value }.accept(self),
+            Literal::DoubleIntegerLiteral(value) => DoubleIntLiteral { value }.accept(self),
+            Literal::DoubleFloatLiteral(value) => DoubleFloatLiteral { value }.accept(self),
+            Literal::StringLiteral(span) => StringLiteral { span }.accept(self),
+            Literal::CharLiteral(value) => CharLiteral { value }.accept(self),
+            Literal::BoolLiteral(value) => BoolLiteral { value }.accept(self),
+        }
+    }
+
+    fn print_child(&mut self, name: &str, child: &impl Node) -> Result {
+        write!(self.writer, r#","{name}":"#)?;
+        child.accept(self)
+    }
+
+    fn print_option(&mut self, name: &str, child: Option<impl Node>) -> Result {
+        match child {
+            Some(child) => self.print_child(name, &child),
+            None => write!(self.writer, r#","{name}":null"#),
+        }
+    }
+
+    fn print_boxed(&mut self, name: &str, child: ArenaBox<Expression>) -> Result {
+        self.print_child(name, child.get(self.arena))
+    }
+
+    fn print_iter<T: Node + Copy>(&mut self, name: &str, items: ArenaIter<T>) -> Result {
+        write!(self.writer, r#","{name}":["#)?;
+        for (i, item) in items.as_slice(self.arena).iter().enumerate() {
+            if i > 0 {
+                self.writer.write_str(",")?;
+            }
+            item.accept(self)?;
+        }
+        self.writer.write_str("]")
+    }
+}
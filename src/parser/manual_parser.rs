@@ -23,6 +23,31 @@ impl<S: DiagnosticStream, TR: Tracer> GeneratedParser for Parser<'_, '_, '_, S,
             Err(Self::Error::build(true, &[], self.enviroment()))
         }
     }
+    fn parse_Expression(&mut self) -> Result<generated_ast::Expression, Self::Error> {
+        self.parse_expression()
+    }
+
+    fn parse_Type(&mut self) -> Result<generated_ast::Type, Self::Error> {
+        self.parse_type()
+    }
+
+    fn parse_Statement(&mut self) -> Result<generated_ast::Statement, Self::Error> {
+        self.parse_statement()
+    }
+
+    fn parse_Pattern(&mut self) -> Result<generated_ast::Pattern, Self::Error> {
+        self.parse_pattern()
+    }
+
+    fn parse_Annotation(&mut self) -> Result<generated_ast::Annotation, Self::Error> {
+        self.parse_annotation()
+    }
+
+    // replaces the generated one, see `parser::annotation`
+    fn parse_Item(&mut self) -> Result<generated_ast::Item, Self::Error> {
+        self.parse_item()
+    }
+
     fn parse_StringLiteral(&mut self) -> Result<generated_ast::StringLiteral, Self::Error> {
         if let Some(Token::Literal(Literal::StringLiteral(span))) = self.peek::<0>() {
             self.consume_token();
@@ -0,0 +1,87 @@
+//! Memo table for packrat parsing.
+//!
+//! The generator marks the rules it can only choose between by backtracking
+//! as ambiguous, and parses them through `BaseParser::memoize`. The result of
+//! such a rule is remembered together with the token position it ended at, so
+//! trying the next alternative does not parse the same tokens again, and
+//! nested ambiguous rules are parsed in linear time instead of exponential.
+//! The rules `optional` and `repeat` try are memoized the same way, so an
+//! item parsed again to report its errors does not try them again.
+//!
+//! A `Parser` starts with `MemoTable::new()`, and a parse opts out by setting
+//! `Parser::memo` to `MemoTable::disabled()`. Rules which hit the nesting
+//! limit are not remembered, since their result depends on more than the
+//! position they started at.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+use crate::parser::errors::ParseErr;
+
+pub struct MemoTable {
+    enabled: bool,
+    /// Keyed by the rule and the token position it started at. The values are
+    /// `(Result<T, ParseErr>, usize)`, where `T` is the node of the rule and the
+    /// `usize` the token position it ended at.
+    entries: HashMap<(TypeId, usize), Box<dyn Any>>,
+}
+
+impl MemoTable {
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// A table which remembers nothing, so every rule is parsed again when it
+    /// is backtracked over.
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the result of rule `T` started at token position `start` and
+    /// the position it ended at, if it was parsed before.
+    pub fn get<T: Copy + 'static>(&self, start: usize) -> Option<(Result<T, ParseErr>, usize)> {
+        self.entries
+            .get(&(TypeId::of::<T>(), start))
+            .map(|entry| *entry.downcast_ref().unwrap())
+    }
+
+    pub fn insert<T: Copy + 'static>(
+        &mut self,
+        start: usize,
+        result: Result<T, ParseErr>,
+        end: usize,
+    ) {
+        if self.enabled {
+            self.entries
+                .insert((TypeId::of::<T>(), start), Box::new((result, end)));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl Default for MemoTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,66 +1,116 @@
 use crate::compiler::runtime::{Engine, Query};
 use crate::compiler::{
-    arena::{Arena, ArenaBox},
+    arena::{Arena, ArenaBox, ArenaStats},
     context::frontend::CompilerFrontendContext,
     source_holder::SourceHolder,
-    symbol::SymbolFactory,
+    symbol::{Interner, SymbolFactory},
 };
 use crate::diagnostic::stream::StockDiagnosticStream;
 use crate::diagnostic::Diagnostic;
-use crate::parser::ast::ASTVisitor;
-use crate::parser::tracer::LogTracer;
+use crate::parser::annotation_registry::AnnotationRegistry;
+use crate::parser::ast::{ASTFolder, ASTVisitor, ASTVisitorMut};
+use crate::parser::ast_node::ASTNode;
+use crate::parser::base_parser::BaseParser;
+use crate::parser::errors::ParseErr;
+use crate::parser::expression::{Expression, Type};
+use crate::parser::pattern::Pattern;
+use crate::parser::statement::Statement;
+use crate::parser::tracer::{LogTracer, NopeTracer, Tracer};
 use crate::parser::{generated_ast_printer::ASTPrinter, parse::Parser};
 use crate::tokenizer::token_stream::TokenStream;
 use crate::tokenizer::tokenize::Tokenizer;
+use crate::tokenizer::tokens::Token;
 use std::fmt::Display;
 
 #[cfg(test)]
 mod tests;
 
+pub mod annotation;
+pub mod annotation_registry;
 mod ast_node;
-mod base_parser;
+pub mod base_parser;
 mod diagnostic;
 mod errors;
+pub mod expression;
+pub mod fuzz;
 mod generated_ast; // generated by script
 mod generated_ast_printer;
 mod generated_parser; // generated by script
+pub mod grammar;
+pub mod interpreter;
 mod manual_ast;
+mod manual_ast_printer;
 mod manual_parser;
-mod recovery;
+pub mod memo;
+pub mod operators;
 pub mod parse;
+pub mod pattern;
+mod recovery;
+pub mod statement;
 pub mod tracer;
 
 pub mod ast {
     pub use crate::parser::generated_ast::*;
 }
 
-pub struct Ast<'src> {
-    ast: ArenaBox<generated_ast::Module>,
+/// A parsed module, or a fragment of one like an expression, together with
+/// the arena its nodes live in.
+pub struct Ast<'src, N: Copy = generated_ast::Module> {
+    ast: ArenaBox<N>,
     ast_arena: Arena,
-    symbols: SymbolFactory<'src>,
     source_holder: SourceHolder<'src>,
 }
 
-impl<'src> Ast<'src> {
-    pub fn new(
-        ast: ArenaBox<generated_ast::Module>,
-        arena: Arena,
-        source_holder: SourceHolder<'src>,
-        symbols: SymbolFactory<'src>,
-    ) -> Self {
+impl<'src, N: Copy> Ast<'src, N> {
+    pub fn new(ast: ArenaBox<N>, arena: Arena, source_holder: SourceHolder<'src>) -> Self {
         Self {
             ast,
             ast_arena: arena,
-            symbols,
             source_holder,
         }
     }
+
+    /// Reports the memory used by the nodes of this AST.
+    pub fn arena_stats(&self) -> ArenaStats {
+        self.ast_arena.stats()
+    }
+
+    pub fn root(&self) -> &N {
+        self.ast.get(&self.ast_arena)
+    }
+
+    /// The arena the boxed children of `root` are allocated in.
+    pub fn arena(&self) -> &Arena {
+        &self.ast_arena
+    }
+
+    pub fn source_holder(&self) -> &SourceHolder<'src> {
+        &self.source_holder
+    }
 }
 
-impl<'src> Display for Ast<'src> {
+impl<'src> Ast<'src> {
+    /// Visits the whole AST in place.
+    pub fn visit_mut(&mut self, visitor: &mut impl ASTVisitorMut) {
+        let mut module = *self.ast.get(&self.ast_arena);
+        visitor.visit_Module(&mut module, &mut self.ast_arena);
+        *self.ast.get_mut(&mut self.ast_arena) = module;
+    }
+
+    /// Rebuilds the whole AST into a new arena. Nodes which are not reachable
+    /// from the module any more are left behind.
+    pub fn fold(&self, folder: &mut impl ASTFolder) -> Ast<'src> {
+        let arena = Arena::new();
+        let module = folder.fold_Module(self.root(), &self.ast_arena, &arena);
+        let ast = arena.alloc(module);
+        Ast::new(ast, arena, self.source_holder.clone())
+    }
+}
+
+impl Display for Ast<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut printer = ASTPrinter {
-            symbols: &self.symbols,
+            interner: Interner::global(),
             arena: &self.ast_arena,
             source_holder: &self.source_holder,
             writer: f,
@@ -102,14 +152,32 @@ impl Query for ParseFileQuery {
     }
 }
 
-pub fn parse_for_test<'a>(source: &'a str) -> (Ast<'a>, Vec<Diagnostic>) {
+/// Parses a whole file, printing which rules are entered to stdout.
+pub fn parse_for_test(source: &str) -> (Ast<'_>, Vec<Diagnostic>) {
+    parse_module_with::<LogTracer>(source)
+}
+
+pub fn parse_module(source: &str) -> (Ast<'_>, Vec<Diagnostic>) {
+    parse_module_with::<NopeTracer>(source)
+}
+
+/// Parses a whole file, reporting the rules it enters to `TR`.
+pub fn parse_module_with<TR: Tracer>(source: &str) -> (Ast<'_>, Vec<Diagnostic>) {
+    parse_module_into::<TR>(source, Arena::new())
+}
+
+/// Parses a whole file like `parse_module_with`, into `ast_arena`, which can
+/// be made to record its type stats.
+pub fn parse_module_into<TR: Tracer>(
+    source: &str,
+    mut ast_arena: Arena,
+) -> (Ast<'_>, Vec<Diagnostic>) {
     let mut diagnostic_stream = StockDiagnosticStream::new();
     let mut symbols = SymbolFactory::new(source);
     let tokenizer = Tokenizer::new(source, &mut symbols);
     let (tokens, line_starts) = tokenizer.tokenize(&mut diagnostic_stream);
     let stream = TokenStream::new(tokens);
-    let mut ast_arena = Arena::new();
-    let mut parser: Parser<'_, '_, '_, _, LogTracer> = Parser::new(
+    let mut parser: Parser<'_, '_, '_, _, TR> = Parser::new(
         stream,
         CompilerFrontendContext {
             source,
@@ -120,12 +188,65 @@ pub fn parse_for_test<'a>(source: &'a str) -> (Ast<'a>, Vec<Diagnostic>) {
     );
     let ast = parser.parse();
 
-    let ast = Ast::new(
-        ast,
-        ast_arena,
-        SourceHolder::new(source, line_starts),
-        symbols,
+    let ast = Ast::new(ast, ast_arena, SourceHolder::new(source, line_starts));
+    AnnotationRegistry::builtin().validate(&ast, &mut diagnostic_stream);
+
+    (ast, diagnostic_stream.into_vec())
+}
+
+/// Parses `source` as a single expression, like `1 + 2`.
+pub fn parse_expression(source: &str) -> (Ast<'_, Expression>, Vec<Diagnostic>) {
+    parse_fragment(source, |parser| parser.parse_expression())
+}
+
+/// Parses `source` as a single type, like `Int`.
+pub fn parse_type(source: &str) -> (Ast<'_, Type>, Vec<Diagnostic>) {
+    parse_fragment(source, |parser| parser.parse_type())
+}
+
+/// Parses `source` as a single pattern, like `(a, _)`.
+pub fn parse_pattern(source: &str) -> (Ast<'_, Pattern>, Vec<Diagnostic>) {
+    parse_fragment(source, |parser| parser.parse_pattern())
+}
+
+/// Parses `source` as a single statement, like `let a = 1;`.
+pub fn parse_statement(source: &str) -> (Ast<'_, Statement>, Vec<Diagnostic>) {
+    parse_fragment(source, |parser| parser.parse_statement())
+}
+
+type FragmentParser<'ctx, 'src, 's> = Parser<'ctx, 'src, 's, StockDiagnosticStream, NopeTracer>;
+
+/// Parses all of `source` with `parser_fn`. Tokens left over after it are an
+/// error. If parsing fails, the root is the error node of `N`.
+fn parse_fragment<N: ASTNode<Target = N>>(
+    source: &str,
+    parser_fn: impl FnOnce(&mut FragmentParser<'_, '_, '_>) -> Result<N, ParseErr>,
+) -> (Ast<'_, N>, Vec<Diagnostic>) {
+    let mut diagnostic_stream = StockDiagnosticStream::new();
+    let mut symbols = SymbolFactory::new(source);
+    let tokenizer = Tokenizer::new(source, &mut symbols);
+    let (tokens, line_starts) = tokenizer.tokenize(&mut diagnostic_stream);
+    let ast_arena = Arena::new();
+    let mut parser: FragmentParser<'_, '_, '_> = Parser::new(
+        TokenStream::new(tokens),
+        CompilerFrontendContext {
+            source,
+            symbol_factory: &mut symbols,
+            ast_arena: &ast_arena,
+        },
+        &mut diagnostic_stream,
     );
+    let node = parser_fn(&mut parser).and_then(|node| {
+        parser.expect(&Token::EndOfFile)?;
+        Ok(node)
+    });
+    let node = node.unwrap_or_else(|err| {
+        parser.report_error(err);
+        N::get_error_situation(err).expect("fragments have an error node")
+    });
+    let ast = ast_arena.alloc(node);
+
+    let ast = Ast::new(ast, ast_arena, SourceHolder::new(source, line_starts));
 
     (ast, diagnostic_stream.into_vec())
 }
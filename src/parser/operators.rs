@@ -0,0 +1,205 @@
+//! The operator table of the expression parser.
+//!
+//! Every operator which can appear in an expression is listed in `OPERATORS`
+//! once, with its position relative to the operand (`Fixity`), its precedence
+//! and, for infix operators, its associativity. The expression parser in
+//! `parser::expression` knows nothing about individual operators beyond this
+//! table, so changing the precedence of an operator is a one-line change here.
+//!
+//! Precedences, from the loosest to the tightest binding:
+//!
+//! | precedence | operators                       | associativity |
+//! |------------|---------------------------------|---------------|
+//! | 1          | `=` `+=` `-=` `*=` `/=`         | right         |
+//! | 2          | `\|>`                           | left          |
+//! | 3          | `..` `..=`                      | none          |
+//! | 4          | `\|\|`                          | left          |
+//! | 5          | `&&`                            | left          |
+//! | 6          | `==` `!=` `<` `>` `<=` `>=`     | none          |
+//! | 7          | `\|`                            | left          |
+//! | 8          | `^`                             | left          |
+//! | 9          | `&`                             | left          |
+//! | 10         | `<<` `>>`                       | left          |
+//! | 11         | `+` `-`                         | left          |
+//! | 12         | `*` `/` `%`                     | left          |
+//! | 13         | `as`                            | left          |
+//! | 14         | prefix `-` `!` `~` `try` `await`|               |
+//! | 15         | `**`                            | right         |
+//! | 16         | postfix `?`, calls, `.`, `[]`   |               |
+
+use crate::tokenizer::tokens::{Delimiter, Keyword, Operator, Token};
+use Associativity::{Left, Right};
+use Fixity::{Infix, Postfix, Prefix};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Associativity {
+    Left,
+    Right,
+    /// `a < b < c` is rejected instead of being grouped either way.
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fixity {
+    Prefix,
+    Infix(Associativity),
+    Postfix,
+}
+
+/// What an operator does, independent of the token it is written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Assign,
+    AddAssign,
+    SubtractAssign,
+    MultiplyAssign,
+    DivideAssign,
+    Pipe,
+    Range,
+    RangeInclusive,
+    LogicalOr,
+    LogicalAnd,
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterThan,
+    LessThanOrEqual,
+    GreaterThanOrEqual,
+    BitwiseOr,
+    BitwiseXor,
+    BitwiseAnd,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Cast,
+    Negate,
+    Not,
+    BitwiseNot,
+    Try,
+    Await,
+    Power,
+    Propagate,
+    Call,
+    Index,
+    Member,
+}
+
+impl Operation {
+    pub const fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            Operation::Equal
+                | Operation::NotEqual
+                | Operation::LessThan
+                | Operation::GreaterThan
+                | Operation::LessThanOrEqual
+                | Operation::GreaterThanOrEqual
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub struct OperatorEntry {
+    pub token: Token,
+    pub fixity: Fixity,
+    pub precedence: u8,
+    pub operation: Operation,
+}
+
+impl OperatorEntry {
+    /// The binding powers of the left and the right side of the operator.
+    ///
+    /// An operator binds an operand if its binding power on that side is at
+    /// least the minimum binding power the parser is currently looking for.
+    /// Prefix operators have no left side and postfix operators no right side,
+    /// the respective power is 0 for them.
+    pub const fn binding_power(&self) -> (u8, u8) {
+        let power = self.precedence * 2;
+        match self.fixity {
+            Fixity::Prefix => (0, power),
+            Fixity::Infix(Associativity::Right) => (power + 1, power),
+            Fixity::Infix(Associativity::Left | Associativity::None) => (power, power + 1),
+            Fixity::Postfix => (power, 0),
+        }
+    }
+}
+
+const fn operator(
+    token: Token,
+    fixity: Fixity,
+    precedence: u8,
+    operation: Operation,
+) -> OperatorEntry {
+    OperatorEntry {
+        token,
+        fixity,
+        precedence,
+        operation,
+    }
+}
+
+const NON_ASSOCIATIVE: Fixity = Infix(Associativity::None);
+
+#[rustfmt::skip]
+pub const OPERATORS: &[OperatorEntry] = &[
+    operator(Token::Operator(Operator::Assignment), Infix(Right), 1, Operation::Assign),
+    operator(Token::Operator(Operator::AddAssign), Infix(Right), 1, Operation::AddAssign),
+    operator(Token::Operator(Operator::SubtractAssign), Infix(Right), 1, Operation::SubtractAssign),
+    operator(Token::Operator(Operator::MultiplyAssign), Infix(Right), 1, Operation::MultiplyAssign),
+    operator(Token::Operator(Operator::DivideAssign), Infix(Right), 1, Operation::DivideAssign),
+    operator(Token::Operator(Operator::Pipe), Infix(Left), 2, Operation::Pipe),
+    operator(Token::Operator(Operator::RangeExclusive), NON_ASSOCIATIVE, 3, Operation::Range),
+    operator(Token::Operator(Operator::RangeInclusive), NON_ASSOCIATIVE, 3, Operation::RangeInclusive),
+    operator(Token::Operator(Operator::LogicalOr), Infix(Left), 4, Operation::LogicalOr),
+    operator(Token::Operator(Operator::LogicalAnd), Infix(Left), 5, Operation::LogicalAnd),
+    operator(Token::Operator(Operator::Equality), NON_ASSOCIATIVE, 6, Operation::Equal),
+    operator(Token::Operator(Operator::Inequality), NON_ASSOCIATIVE, 6, Operation::NotEqual),
+    operator(Token::Operator(Operator::LessThan), NON_ASSOCIATIVE, 6, Operation::LessThan),
+    operator(Token::Operator(Operator::GreaterThan), NON_ASSOCIATIVE, 6, Operation::GreaterThan),
+    operator(Token::Operator(Operator::LessThanOrEqual), NON_ASSOCIATIVE, 6, Operation::LessThanOrEqual),
+    operator(Token::Operator(Operator::GreaterThanOrEqual), NON_ASSOCIATIVE, 6, Operation::GreaterThanOrEqual),
+    operator(Token::Operator(Operator::Or), Infix(Left), 7, Operation::BitwiseOr),
+    operator(Token::Operator(Operator::Xor), Infix(Left), 8, Operation::BitwiseXor),
+    operator(Token::Operator(Operator::And), Infix(Left), 9, Operation::BitwiseAnd),
+    operator(Token::Operator(Operator::ShiftLeft), Infix(Left), 10, Operation::ShiftLeft),
+    operator(Token::Operator(Operator::ShiftRight), Infix(Left), 10, Operation::ShiftRight),
+    operator(Token::Operator(Operator::Add), Infix(Left), 11, Operation::Add),
+    operator(Token::Operator(Operator::Subtract), Infix(Left), 11, Operation::Subtract),
+    operator(Token::Operator(Operator::Multiply), Infix(Left), 12, Operation::Multiply),
+    operator(Token::Operator(Operator::Divide), Infix(Left), 12, Operation::Divide),
+    operator(Token::Operator(Operator::Remainder), Infix(Left), 12, Operation::Remainder),
+    operator(Token::Keyword(Keyword::As), Infix(Left), 13, Operation::Cast),
+    operator(Token::Operator(Operator::Subtract), Prefix, 14, Operation::Negate),
+    operator(Token::Operator(Operator::Not), Prefix, 14, Operation::Not),
+    operator(Token::Operator(Operator::BitwiseNot), Prefix, 14, Operation::BitwiseNot),
+    operator(Token::Keyword(Keyword::Try), Prefix, 14, Operation::Try),
+    operator(Token::Keyword(Keyword::Await), Prefix, 14, Operation::Await),
+    operator(Token::Operator(Operator::PowerOf), Infix(Right), 15, Operation::Power),
+    operator(Token::Operator(Operator::Question), Postfix, 16, Operation::Propagate),
+    operator(Token::Delimiter(Delimiter::LeftParen), Postfix, 16, Operation::Call),
+    operator(Token::Delimiter(Delimiter::LeftBracket), Postfix, 16, Operation::Index),
+    operator(Token::Operator(Operator::MemberAccess), Postfix, 16, Operation::Member),
+];
+
+fn find(token: Option<Token>, matches: impl Fn(Fixity) -> bool) -> Option<&'static OperatorEntry> {
+    let token = token?;
+    OPERATORS
+        .iter()
+        .find(|entry| entry.token == token && matches(entry.fixity))
+}
+
+pub fn prefix(token: Option<Token>) -> Option<&'static OperatorEntry> {
+    find(token, |fixity| fixity == Prefix)
+}
+
+pub fn infix(token: Option<Token>) -> Option<&'static OperatorEntry> {
+    find(token, |fixity| matches!(fixity, Infix(_)))
+}
+
+pub fn postfix(token: Option<Token>) -> Option<&'static OperatorEntry> {
+    find(token, |fixity| fixity == Postfix)
+}
@@ -2,12 +2,20 @@ use std::marker::PhantomData;
 
 use crate::compiler::arena::ArenaBox;
 use crate::compiler::context::frontend::CompilerFrontendContext;
+use crate::compiler::span::Span;
 use crate::diagnostic::stream::DiagnosticStream;
 use crate::parser::base_parser::BaseParser;
+use crate::parser::errors::{IParseErr, NestingTooDeepErr, ParseErr};
 use crate::parser::generated_ast::Module;
 use crate::parser::generated_parser::GeneratedParser;
+use crate::parser::memo::MemoTable;
 use crate::parser::tracer::Tracer;
 use crate::tokenizer::token_stream::TokenStream;
+use crate::tokenizer::tokens::Token;
+
+/// How deeply boxed nodes and expressions may be nested before the parser
+/// gives up on them, instead of running out of stack.
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 256;
 
 pub struct Parser<'ctx, 'src, 's, S: DiagnosticStream, TR: Tracer> {
     pub ctx: CompilerFrontendContext<'ctx, 'src>,
@@ -15,6 +23,21 @@ pub struct Parser<'ctx, 'src, 's, S: DiagnosticStream, TR: Tracer> {
     pub diagnostic_stream: &'s mut S,
     _marker: PhantomData<TR>,
     pub is_panic_or_backtrack_mode: bool,
+    /// A parse can opt out by setting it to `MemoTable::disabled()`.
+    pub memo: MemoTable,
+    pub max_nesting_depth: usize,
+    nesting: Nesting,
+}
+
+/// The rules the parser is currently nested in, see `Parser::nested`.
+struct Nesting {
+    depth: usize,
+    /// Where the outermost of the rules started.
+    outermost: Span,
+    /// Whether `NestingTooDeepErr` was reported for the outermost rule.
+    reported: bool,
+    /// How often a rule failed because of the limit, over the whole parse.
+    cut_off: usize,
 }
 
 impl<'ctx, 'src, 's, S: DiagnosticStream, TR: Tracer> Parser<'ctx, 'src, 's, S, TR> {
@@ -29,14 +52,79 @@ impl<'ctx, 'src, 's, S: DiagnosticStream, TR: Tracer> Parser<'ctx, 'src, 's, S,
             diagnostic_stream,
             _marker: PhantomData,
             is_panic_or_backtrack_mode: false,
+            memo: MemoTable::new(),
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            nesting: Nesting {
+                depth: 0,
+                outermost: Span::new(0, 0),
+                reported: false,
+                cut_off: 0,
+            },
         }
     }
 
+    /// Parses a rule which can contain itself, like a boxed node or an
+    /// operand of an expression.
+    ///
+    /// Past `max_nesting_depth` levels the rule fails without being parsed,
+    /// and a `NestingTooDeepErr` spanning from the outermost rule is reported
+    /// once. It is not reported while backtracking, since the rule may be
+    /// parsed again for real. The `ParseErr` it fails with is not reported
+    /// on top of it.
+    pub(crate) fn nested<T>(
+        &mut self,
+        parser_fn: impl FnOnce(&mut Self) -> Result<T, ParseErr>,
+    ) -> Result<T, ParseErr> {
+        if self.nesting.depth == 0 {
+            self.nesting.outermost = self.now_span();
+            self.nesting.reported = false;
+        }
+        if self.nesting.depth >= self.max_nesting_depth {
+            self.nesting.cut_off += 1;
+            if !self.nesting.reported && !self.is_panic_or_backtrack_mode {
+                self.nesting.reported = true;
+                let innermost = self.now_span();
+                self.diagnostic_stream.pour(
+                    NestingTooDeepErr {
+                        outermost: Span {
+                            begin: self.nesting.outermost.begin,
+                            end: innermost.end,
+                        },
+                        innermost,
+                    },
+                    &(),
+                );
+            }
+            return Err(ParseErr {
+                reported: self.nesting.reported,
+                ..ParseErr::build(false, &[], self.enviroment())
+            });
+        }
+        self.nesting.depth += 1;
+        let result = parser_fn(self);
+        self.nesting.depth -= 1;
+        result
+    }
+
+    /// How often a rule failed because of `max_nesting_depth` so far. The
+    /// result of a rule which was cut off depends on how deeply it was
+    /// nested, so it is not memoized.
+    pub(crate) fn nesting_cut_offs(&self) -> usize {
+        self.nesting.cut_off
+    }
+
     pub fn parse(&mut self) -> ArenaBox<Module> {
         match self.parse_Module() {
-            Ok(module) => self.ctx.ast_arena.alloc(module),
+            Ok(module) => {
+                // the items stop at the first one which fails to parse, which
+                // leaves the rest of the file unparsed
+                if let Err(err) = self.expect(&Token::EndOfFile) {
+                    self.report_error(err);
+                }
+                self.ctx.ast_arena.alloc(module)
+            }
             Err(err) => {
-                self.diagnostic_stream.pour(err, &self.enviroment());
+                self.report_error(err);
                 self.ctx.ast_arena.alloc(Module {
                     declarations: self.ctx.ast_arena.alloc_with(|| None),
                 })
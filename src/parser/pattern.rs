@@ -0,0 +1,158 @@
+//! Pattern parser.
+//!
+//! Patterns are what `let`, `for` and match arms bind to. Like expressions
+//! they are parsed by hand instead of by generated rules, and the grammar
+//! refers to them as the hook `Pattern`.
+
+use crate::compiler::arena::ArenaIter;
+use crate::compiler::symbol::sym;
+use crate::diagnostic::stream::DiagnosticStream;
+use crate::parser::ast::{FieldPattern, Identifier};
+use crate::parser::base_parser::BaseParser;
+use crate::parser::errors::{IParseErr, ParseErr};
+use crate::parser::generated_parser::GeneratedParser;
+use crate::parser::parse::Parser;
+use crate::parser::tracer::Tracer;
+use crate::tokenizer::tokens::{Delimiter, Keyword, Literal, Operator, Token};
+
+#[derive(Debug, Copy, Clone, std::hash::Hash, PartialEq, Eq)]
+pub enum Pattern {
+    /// `_`
+    Wildcard,
+    /// `name` or `mut name`
+    Binding {
+        name: Identifier,
+        mutable: bool,
+    },
+    Literal(Literal),
+    /// `()` or `(a, b)`. A single element is only a tuple with a trailing comma.
+    Tuple(ArenaIter<Pattern>),
+    /// `a::b`, with at least two segments.
+    Path(ArenaIter<Identifier>),
+    /// `Name(a, b)` or `a::Name(a, b)`
+    TupleStruct {
+        path: ArenaIter<Identifier>,
+        items: ArenaIter<Pattern>,
+    },
+    /// `Name { field: pattern }` or `a::Name { field: pattern }`
+    Struct {
+        path: ArenaIter<Identifier>,
+        fields: ArenaIter<FieldPattern>,
+    },
+    Invalid,
+}
+
+impl<S: DiagnosticStream, TR: Tracer> Parser<'_, '_, '_, S, TR> {
+    pub fn parse_pattern(&mut self) -> Result<Pattern, ParseErr> {
+        let _guard = TR::trace("Pattern");
+        self.nested(Self::parse_pattern_kind)
+    }
+
+    fn parse_pattern_kind(&mut self) -> Result<Pattern, ParseErr> {
+        match self.peek::<0>() {
+            // `_` is tokenized like an identifier
+            Some(Token::Operator(Operator::Wildcard) | Token::Identifier(sym::Underscore)) => {
+                self.consume_token();
+                Ok(Pattern::Wildcard)
+            }
+            Some(Token::Keyword(Keyword::Mut)) => {
+                self.consume_token();
+                Ok(Pattern::Binding {
+                    name: self.parse_name()?,
+                    mutable: true,
+                })
+            }
+            Some(Token::Identifier(_)) => self.parse_named_pattern(),
+            Some(Token::Literal(literal)) => {
+                self.consume_token();
+                Ok(Pattern::Literal(literal))
+            }
+            Some(Token::Delimiter(Delimiter::LeftParen)) => {
+                self.consume_token();
+                self.parse_parenthesized_pattern()
+            }
+            _ => Err(ParseErr::build(
+                true,
+                &[
+                    Token::Operator(Operator::Wildcard),
+                    Token::Keyword(Keyword::Mut),
+                    Token::Delimiter(Delimiter::LeftParen),
+                ],
+                self.enviroment(),
+            )),
+        }
+    }
+
+    /// Parses a binding, a path or a pattern of a tuple struct or a struct.
+    fn parse_named_pattern(&mut self) -> Result<Pattern, ParseErr> {
+        let name = self.parse_name()?;
+        let next = self.peek::<0>();
+        let path = match next {
+            Some(Token::Operator(Operator::NamespaceResolver)) => self.parse_path(name)?,
+            Some(Token::Delimiter(Delimiter::LeftParen | Delimiter::LeftBrace)) => {
+                self.ctx.ast_arena.alloc_iter(std::iter::once(name))
+            }
+            _ => {
+                return Ok(Pattern::Binding {
+                    name,
+                    mutable: false,
+                })
+            }
+        };
+        match self.peek::<0>() {
+            Some(Token::Delimiter(Delimiter::LeftParen)) => {
+                self.consume_token();
+                let (items, _) = self.parse_pattern_items()?;
+                Ok(Pattern::TupleStruct {
+                    path,
+                    items: self.ctx.ast_arena.alloc_iter(items.into_iter()),
+                })
+            }
+            Some(Token::Delimiter(Delimiter::LeftBrace)) => {
+                self.consume_token();
+                let mut fields = Vec::new();
+                while self.peek::<0>() != Some(Token::Delimiter(Delimiter::RightBrace)) {
+                    fields.push(self.parse_FieldPattern()?);
+                    if self.peek::<0>() != Some(Token::Delimiter(Delimiter::Comma)) {
+                        break;
+                    }
+                    self.consume_token();
+                }
+                self.expect(&Token::Delimiter(Delimiter::RightBrace))?;
+                Ok(Pattern::Struct {
+                    path,
+                    fields: self.ctx.ast_arena.alloc_iter(fields.into_iter()),
+                })
+            }
+            _ => Ok(Pattern::Path(path)),
+        }
+    }
+
+    /// Parses the rest of `(a)`, `()` or `(a, b)` after the `(`.
+    fn parse_parenthesized_pattern(&mut self) -> Result<Pattern, ParseErr> {
+        let (items, trailing_comma) = self.parse_pattern_items()?;
+        if let ([item], false) = (&items[..], trailing_comma) {
+            return Ok(*item);
+        }
+        Ok(Pattern::Tuple(
+            self.ctx.ast_arena.alloc_iter(items.into_iter()),
+        ))
+    }
+
+    /// Parses comma separated patterns up to and including the `)`, and
+    /// whether they end with a comma.
+    fn parse_pattern_items(&mut self) -> Result<(Vec<Pattern>, bool), ParseErr> {
+        let mut items = Vec::new();
+        let mut trailing_comma = false;
+        while self.peek::<0>() != Some(Token::Delimiter(Delimiter::RightParen)) {
+            items.push(self.parse_pattern()?);
+            trailing_comma = self.peek::<0>() == Some(Token::Delimiter(Delimiter::Comma));
+            if !trailing_comma {
+                break;
+            }
+            self.consume_token();
+        }
+        self.expect(&Token::Delimiter(Delimiter::RightParen))?;
+        Ok((items, trailing_comma))
+    }
+}
@@ -1,8 +1,45 @@
 use crate::{
     diagnostic::stream::DiagnosticStream,
-    parser::{ast_node::ASTNode, base_parser::BaseParser, parse::Parser, tracer::Tracer},
+    parser::{
+        ast_node::ASTNode,
+        base_parser::BaseParser,
+        errors::{IParseErr, ParseErr},
+        parse::Parser,
+        tracer::Tracer,
+    },
+    tokenizer::tokens::Token,
 };
 
+/// The error for a token which can't start a `T`. It is the error `parser_fn`
+/// fails with at the token, which knows the tokens a `T` starts with. Nothing
+/// `parser_fn` parses is kept.
+pub fn unexpected<'ctx, 'src, 's, T, S, TR>(
+    parser: &mut Parser<'ctx, 'src, 's, S, TR>,
+    parser_fn: impl FnOnce(&mut Parser<'ctx, 'src, 's, S, TR>) -> Result<T, ParseErr>,
+) -> ParseErr
+where
+    S: DiagnosticStream,
+    TR: Tracer,
+{
+    let backtracking = parser.is_panic_or_backtrack_mode();
+    parser.set_panic_or_backtrack_mode(true);
+    let start = parser.tokens.position();
+    parser.tokens.checkpoint();
+    TR::backtrack(start);
+    let result = parser_fn(parser);
+    let end = parser.tokens.position();
+    parser.tokens.rollback();
+    TR::rollback(end, start);
+    parser.set_panic_or_backtrack_mode(backtracking);
+    match result {
+        // an error further on is about the tokens there
+        Err(err) if end == start => err,
+        _ => ParseErr::build(false, &[], parser.enviroment()),
+    }
+}
+
+/// Skips tokens up to one which can start or follow a `WhileParsing`, or up
+/// to the end of the file. Errors are not reported while skipping.
 pub fn recover<'ctx, 'src, 's, WhileParsing, S, TR>(parser: &mut Parser<'ctx, 'src, 's, S, TR>)
 where
     WhileParsing: ASTNode,
@@ -10,6 +47,12 @@ where
     TR: Tracer,
 {
     parser.set_panic_or_backtrack_mode(true);
-    panic!("Error");
+    loop {
+        let next = parser.peek::<0>();
+        if next.is_none() || next == Some(Token::EndOfFile) || WhileParsing::is_sync_point(&next) {
+            break;
+        }
+        parser.consume_token();
+    }
     parser.set_panic_or_backtrack_mode(false);
 }
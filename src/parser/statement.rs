@@ -0,0 +1,70 @@
+//! Statement parser.
+//!
+//! Only the statements which can appear in a block are parsed here. Items
+//! like functions are top level statements of the generated grammar, which
+//! refers to these as the hook `Statement`.
+
+use crate::diagnostic::stream::DiagnosticStream;
+use crate::parser::base_parser::BaseParser;
+use crate::parser::errors::ParseErr;
+use crate::parser::expression::{Expression, Type};
+use crate::parser::parse::Parser;
+use crate::parser::pattern::Pattern;
+use crate::parser::tracer::Tracer;
+use crate::tokenizer::tokens::{Delimiter, Keyword, Operator, Token};
+
+#[derive(Debug, Copy, Clone, std::hash::Hash, PartialEq, Eq)]
+pub enum Statement {
+    /// `let pattern: ty = value;`, where the type and the value are optional.
+    Let {
+        pattern: Pattern,
+        ty: Option<Type>,
+        value: Option<Expression>,
+    },
+    /// `ignore value;`, which drops the value on purpose.
+    Ignore(Expression),
+    /// `value;`, or `value` if it ends with a block, like `if`.
+    Expression(Expression),
+    /// `value` without a `;`, which is the value of the block it ends.
+    Value(Expression),
+    Invalid,
+}
+
+impl<S: DiagnosticStream, TR: Tracer> Parser<'_, '_, '_, S, TR> {
+    pub fn parse_statement(&mut self) -> Result<Statement, ParseErr> {
+        let _guard = TR::trace("Statement");
+        let statement = if self.peek::<0>() == Some(Token::Keyword(Keyword::Let)) {
+            self.consume_token();
+            let pattern = self.parse_pattern()?;
+            let ty = if self.peek::<0>() == Some(Token::Operator(Operator::Colon)) {
+                self.consume_token();
+                Some(self.parse_type()?)
+            } else {
+                None
+            };
+            let value = if self.peek::<0>() == Some(Token::Operator(Operator::Assignment)) {
+                self.consume_token();
+                Some(self.parse_expression()?)
+            } else {
+                None
+            };
+            Statement::Let { pattern, ty, value }
+        } else if self.peek::<0>() == Some(Token::Keyword(Keyword::Ignore)) {
+            self.consume_token();
+            Statement::Ignore(self.parse_expression()?)
+        } else {
+            let value = self.parse_statement_expression()?;
+            match self.peek::<0>() {
+                Some(Token::Delimiter(Delimiter::RightBrace)) => {
+                    return Ok(Statement::Value(value))
+                }
+                Some(Token::Delimiter(Delimiter::Semicolon)) => {}
+                _ if value.is_block_like() => return Ok(Statement::Expression(value)),
+                _ => {}
+            }
+            Statement::Expression(value)
+        };
+        self.expect(&Token::Delimiter(Delimiter::Semicolon))?;
+        Ok(statement)
+    }
+}
@@ -0,0 +1,208 @@
+use crate::compiler::arena::Arena;
+use crate::compiler::symbol::Symbol;
+use crate::diagnostic::stream::StockDiagnosticStream;
+use crate::diagnostic::{Diagnostic, DiagnosticLevel};
+use crate::parser::annotation::{Annotation, AnnotationArgument, AnnotationValue};
+use crate::parser::annotation_registry::{
+    AnnotationRegistry, AnnotationSpec, AnnotationTarget, ArgumentKind,
+};
+use crate::parser::ast::{ASTFolder, Identifier, Module, TopLevelStatement};
+use crate::parser::{parse_module, Ast};
+use crate::tokenizer::tokens::Literal;
+
+fn messages(diagnostics: &[Diagnostic]) -> Vec<(DiagnosticLevel, &'static str)> {
+    diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.level, diagnostic.message))
+        .collect()
+}
+
+/// The names of the annotations of each item of `ast`.
+fn annotation_names(ast: &Ast<'_>) -> Vec<Vec<&'static str>> {
+    ast.root()
+        .declarations()
+        .as_slice(ast.arena())
+        .iter()
+        .map(|item| {
+            item.annotations()
+                .as_slice(ast.arena())
+                .iter()
+                .map(|annotation| match annotation {
+                    Annotation::Parsed { name, .. } => name.symbol.as_str(),
+                    Annotation::Invalid => "invalid",
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn test_annotations_are_attached_to_the_next_item() {
+    let (ast, diagnostics) = parse_module(
+        "extern fn puts(); @Link \"math_lib\"; module M { extern fn sin(); } @EntryPoint; fn main() {}",
+    );
+    assert!(messages(&diagnostics).is_empty());
+    assert_eq!(
+        annotation_names(&ast),
+        [vec![], vec!["Link"], vec!["EntryPoint"]]
+    );
+
+    let item = &ast.root().declarations().as_slice(ast.arena())[1];
+    let Annotation::Parsed { arguments, .. } = item.annotations().as_slice(ast.arena())[0] else {
+        panic!("{item:?} has no annotation");
+    };
+    let [AnnotationArgument::Positional(AnnotationValue::Literal(Literal::StringLiteral(span)))] =
+        arguments.as_slice(ast.arena())
+    else {
+        panic!("{arguments:?} is not a single string");
+    };
+    assert_eq!(ast.source_holder().get_snippet(*span), "math_lib");
+}
+
+#[test]
+fn test_annotations_of_a_failed_item_are_dropped() {
+    // the first item has no name, so it fails to parse
+    let (ast, diagnostics) = parse_module("@EntryPoint; extern fn 1; extern fn b();");
+    assert_eq!(
+        messages(&diagnostics),
+        [(DiagnosticLevel::Error, "unexpected token")]
+    );
+    assert_eq!(annotation_names(&ast), [Vec::<&str>::new()]);
+}
+
+#[test]
+fn test_annotations_are_kept_by_folding() {
+    /// Removes the extern functions from the module.
+    struct DropExterns;
+    impl ASTFolder for DropExterns {
+        fn fold_Module(&mut self, node: &Module, from: &Arena, to: &Arena) -> Module {
+            let items = node
+                .declarations()
+                .as_slice(from)
+                .iter()
+                .filter(|item| !matches!(item.declaration(), TopLevelStatement::ExternFunction(_)))
+                .map(|item| self.fold_Item(item, from, to))
+                .collect::<Vec<_>>();
+            Module {
+                declarations: to.alloc_iter(items.into_iter()),
+            }
+        }
+    }
+    let (ast, _) = parse_module("extern fn puts(); @Link \"m\"; module M { extern fn sin(); }");
+    let folded = ast.fold(&mut DropExterns);
+    assert_eq!(annotation_names(&folded), [vec!["Link"]]);
+}
+
+#[test]
+fn test_annotation_arguments() {
+    let (ast, _) = parse_module("@Custom 1 target flag=true; @Other; fn main() {}");
+    assert_eq!(annotation_names(&ast), [vec!["Custom", "Other"]]);
+    let item = &ast.root().declarations().as_slice(ast.arena())[0];
+    let Annotation::Parsed { arguments, .. } = item.annotations().as_slice(ast.arena())[0] else {
+        panic!("{item:?} has no annotation");
+    };
+    assert_eq!(
+        arguments.as_slice(ast.arena()),
+        [
+            AnnotationArgument::Positional(AnnotationValue::Literal(Literal::IntegerLiteral(1))),
+            AnnotationArgument::Positional(AnnotationValue::Identifier(Identifier {
+                symbol: Symbol::intern("target"),
+            })),
+            AnnotationArgument::Named {
+                key: Identifier {
+                    symbol: Symbol::intern("flag"),
+                },
+                value: AnnotationValue::Literal(Literal::BoolLiteral(true)),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_annotations_are_validated() {
+    let (_, diagnostics) = parse_module("@EntryPoint; extern fn main();");
+    assert_eq!(
+        messages(&diagnostics),
+        [(
+            DiagnosticLevel::Error,
+            "annotation is only allowed on functions without parameters"
+        )]
+    );
+    let (_, diagnostics) = parse_module("@Link; extern fn sin();");
+    assert_eq!(
+        messages(&diagnostics),
+        [(DiagnosticLevel::Error, "wrong arguments for annotation")]
+    );
+    let (_, diagnostics) = parse_module("@MockNetwork; extern fn fetch();");
+    assert_eq!(
+        messages(&diagnostics),
+        [(
+            DiagnosticLevel::Error,
+            "annotation is only allowed on functions"
+        )]
+    );
+    let (_, diagnostics) = parse_module("@Inline; fn main() {}");
+    assert_eq!(
+        messages(&diagnostics),
+        [(DiagnosticLevel::Warning, "unknown annotation")]
+    );
+    let (_, diagnostics) = parse_module("fn main() {} @EntryPoint;");
+    assert_eq!(
+        messages(&diagnostics),
+        [(
+            DiagnosticLevel::Error,
+            "annotation is not followed by an item"
+        )]
+    );
+    // the items of a module are checked like the items of the file, after
+    // the annotations the parser reports
+    let (_, diagnostics) = parse_module("module M { @EntryPoint 1; fn main() {} @MockNetwork; }");
+    assert_eq!(
+        messages(&diagnostics),
+        [
+            (
+                DiagnosticLevel::Error,
+                "annotation is not followed by an item"
+            ),
+            (DiagnosticLevel::Error, "wrong arguments for annotation"),
+        ]
+    );
+}
+
+#[test]
+fn test_link_needs_a_module_with_extern_functions() {
+    let (_, diagnostics) = parse_module("@Link \"m\"; module M { extern fn sin(); }");
+    assert!(messages(&diagnostics).is_empty());
+    for source in [
+        "@Link \"m\"; fn sin() {}",
+        "@Link \"m\"; extern fn sin();",
+        "@Link \"m\"; struct Vector {}",
+        "@Link \"m\"; module M { fn sin() {} }",
+    ] {
+        assert_eq!(
+            messages(&parse_module(source).1),
+            [(
+                DiagnosticLevel::Error,
+                "annotation is only allowed on modules with extern functions"
+            )],
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn test_registered_annotations_are_known() {
+    let (ast, _) = parse_module("@Inline; fn main() {}");
+    let mut registry = AnnotationRegistry::builtin();
+    registry.register(AnnotationSpec {
+        name: "Inline",
+        arguments: &[],
+        target: AnnotationTarget::Function,
+    });
+    let mut diagnostic_stream = StockDiagnosticStream::new();
+    registry.validate(&ast, &mut diagnostic_stream);
+    assert!(diagnostic_stream.into_vec().is_empty());
+
+    let spec = registry.get(Symbol::intern("ModuleConfig")).unwrap();
+    assert_eq!(spec.arguments, [ArgumentKind::String, ArgumentKind::String]);
+}
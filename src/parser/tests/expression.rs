@@ -0,0 +1,294 @@
+use crate::compiler::arena::{Arena, ArenaBox};
+use crate::compiler::context::frontend::CompilerFrontendContext;
+use crate::compiler::symbol::SymbolFactory;
+use crate::diagnostic::stream::StockDiagnosticStream;
+use crate::diagnostic::Diagnostic;
+use crate::parser::ast::TopLevelStatement;
+use crate::parser::ast_node::ASTNode;
+use crate::parser::expression::{Expression, Type};
+use crate::parser::operators::{self, Fixity, Operation, OPERATORS};
+use crate::parser::parse::Parser;
+use crate::parser::parse_module;
+use crate::parser::statement::Statement;
+use crate::parser::tracer::NopeTracer;
+use crate::tokenizer::token_stream::TokenStream;
+use crate::tokenizer::tokenize::Tokenizer;
+use crate::tokenizer::tokens::{Literal, Operator, Token};
+
+/// Parses `source` as an expression and prints it as an s-expression.
+fn parse(source: &str) -> (String, Vec<Diagnostic>) {
+    let mut diagnostic_stream = StockDiagnosticStream::new();
+    let mut symbols = SymbolFactory::new(source);
+    let (tokens, _) = Tokenizer::new(source, &mut symbols).tokenize(&mut diagnostic_stream);
+    let arena = Arena::new();
+    let mut parser: Parser<'_, '_, '_, _, NopeTracer> = Parser::new(
+        TokenStream::new(tokens),
+        CompilerFrontendContext {
+            source,
+            symbol_factory: &mut symbols,
+            ast_arena: &arena,
+        },
+        &mut diagnostic_stream,
+    );
+    let printed = match parser.parse_expression() {
+        Ok(expression) => print(&expression, &arena),
+        Err(_) => "error".to_string(),
+    };
+    (printed, diagnostic_stream.into_vec())
+}
+
+fn print(expression: &Expression, arena: &Arena) -> String {
+    let list = |items: &[Expression]| {
+        items
+            .iter()
+            .map(|item| format!(" {}", print(item, arena)))
+            .collect::<String>()
+    };
+    let boxed = |value: &ArenaBox<Expression>| print(value.get(arena), arena);
+    match expression {
+        Expression::Identifier(identifier) => identifier.symbol.as_str().to_string(),
+        Expression::Literal(Literal::IntegerLiteral(value)) => value.to_string(),
+        Expression::Literal(literal) => format!("{literal:?}"),
+        Expression::This => "this".to_string(),
+        Expression::Path(segments) => segments
+            .as_slice(arena)
+            .iter()
+            .map(|segment| segment.symbol.as_str())
+            .collect::<Vec<_>>()
+            .join("::"),
+        Expression::Array(items) => format!("(array{})", list(items.as_slice(arena))),
+        Expression::Struct { name, fields } => format!(
+            "(struct {}{})",
+            name.symbol.as_str(),
+            fields
+                .as_slice(arena)
+                .iter()
+                .map(|field| format!(" {}", field.name().symbol.as_str()))
+                .collect::<String>()
+        ),
+        Expression::Block(block) => format!("(block {})", block.statements().len()),
+        Expression::If {
+            condition,
+            otherwise,
+            ..
+        } => match otherwise {
+            Some(otherwise) => format!("(if {} else {})", boxed(condition), boxed(otherwise)),
+            None => format!("(if {})", boxed(condition)),
+        },
+        Expression::Match { scrutinee, arms } => {
+            format!("(match {} {})", boxed(scrutinee), arms.len())
+        }
+        Expression::For { iterable, .. } => format!("(for {})", boxed(iterable)),
+        Expression::Return(value) => match value {
+            Some(value) => format!("(return {})", boxed(value)),
+            None => "(return)".to_string(),
+        },
+        Expression::TryElse { value, fallback } => {
+            format!("(try {} else {})", boxed(value), boxed(fallback))
+        }
+        Expression::Parenthesized(inner) => print(inner.get(arena), arena),
+        Expression::Tuple(items) => format!("(tuple{})", list(items.as_slice(arena))),
+        Expression::Prefix { operation, operand } => {
+            format!("({operation:?} {})", print(operand.get(arena), arena))
+        }
+        Expression::Infix {
+            operation,
+            lhs,
+            rhs,
+        } => format!(
+            "({operation:?} {} {})",
+            print(lhs.get(arena), arena),
+            print(rhs.get(arena), arena)
+        ),
+        Expression::Propagate(value) => format!("(? {})", print(value.get(arena), arena)),
+        Expression::Cast { value, ty } => {
+            let ty = match ty {
+                Type::Primitive(keyword) => format!("{keyword}"),
+                Type::Named(identifier)
+                | Type::Generic {
+                    name: identifier, ..
+                } => identifier.symbol.as_str().to_string(),
+                Type::Invalid => "invalid".to_string(),
+            };
+            format!("(as {} {ty})", print(value.get(arena), arena))
+        }
+        Expression::Call { callee, arguments } => format!(
+            "(call {}{})",
+            print(callee.get(arena), arena),
+            list(arguments.as_slice(arena))
+        ),
+        Expression::MethodCall {
+            receiver,
+            method,
+            arguments,
+        } => format!(
+            "(method {} {}{})",
+            print(receiver.get(arena), arena),
+            method.symbol.as_str(),
+            list(arguments.as_slice(arena))
+        ),
+        Expression::Field { receiver, field } => format!(
+            "(field {} {})",
+            print(receiver.get(arena), arena),
+            field.symbol.as_str()
+        ),
+        Expression::Index { receiver, index } => format!(
+            "(index {} {})",
+            print(receiver.get(arena), arena),
+            print(index.get(arena), arena)
+        ),
+        Expression::Invalid => "invalid".to_string(),
+    }
+}
+
+fn assert_parses(source: &str, expected: &str) {
+    let (printed, diagnostics) = parse(source);
+    assert_eq!(printed, expected, "while parsing `{source}`");
+    assert!(
+        diagnostics.is_empty(),
+        "diagnostics while parsing `{source}`"
+    );
+}
+
+#[test]
+fn test_precedence_and_associativity() {
+    assert_parses("1 + 2 * 3", "(Add 1 (Multiply 2 3))");
+    assert_parses("1 - 2 - 3", "(Subtract (Subtract 1 2) 3)");
+    assert_parses("a = b = c", "(Assign a (Assign b c))");
+    assert_parses("a ** b ** c", "(Power a (Power b c))");
+    assert_parses("-a ** b", "(Negate (Power a b))");
+    assert_parses("a || b && c", "(LogicalOr a (LogicalAnd b c))");
+    assert_parses(
+        "a | b ^ c & d",
+        "(BitwiseOr a (BitwiseXor b (BitwiseAnd c d)))",
+    );
+    assert_parses("a << 1 + b", "(ShiftLeft a (Add 1 b))");
+    assert_parses("x |> f |> g", "(Pipe (Pipe x f) g)");
+    assert_parses("a..b + 1", "(Range a (Add b 1))");
+    assert_parses("a += b == c", "(AddAssign a (Equal b c))");
+    assert_parses("(1 + 2) * 3", "(Multiply (Add 1 2) 3)");
+}
+
+#[test]
+fn test_postfix_and_prefix_operators() {
+    assert_parses("-x as Float", "(as (Negate x) Float)");
+    assert_parses("a + b as Int", "(Add a (as b Int))");
+    assert_parses("a as Meters as Int", "(as (as a Meters) Int)");
+    assert_parses("try f()?", "(Try (? (call f)))");
+    assert_parses("await a.b(1, 2)", "(Await (method a b 1 2))");
+    assert_parses("!a.b[0]", "(Not (index (field a b) 0))");
+    assert_parses("f(a)(b,)", "(call (call f a) b)");
+    assert_parses("(a, b)", "(tuple a b)");
+    assert_parses("(a,)", "(tuple a)");
+    assert_parses("()", "(tuple)");
+}
+
+#[test]
+fn test_primary_expressions() {
+    assert_parses("this.a", "(field this a)");
+    assert_parses("Math::sin(x)", "(call Math::sin x)");
+    assert_parses("[1, a + 2]", "(array 1 (Add a 2))");
+    assert_parses("[]", "(array)");
+    assert_parses("Point { x: 1, y: 2 }", "(struct Point x y)");
+    assert_parses("return a == b", "(return (Equal a b))");
+    assert_parses("return", "(return)");
+    assert_parses("try f(a) else 0", "(try (call f a) else 0)");
+    assert_parses("try a else b + 1", "(Add (try a else b) 1)");
+}
+
+#[test]
+fn test_expressions_ending_with_blocks() {
+    assert_parses("if a { b }", "(if a)");
+    assert_parses(
+        "if a {} else if b {} else {}",
+        "(if a else (if b else (block 0)))",
+    );
+    assert_parses(
+        "match (a, b) { (0, _) => 1, _ => {} }",
+        "(match (tuple a b) 2)",
+    );
+    assert_parses("match a { 0 => {} _ => 1 }", "(match a 2)");
+    assert_parses("for x in xs { f(x); }", "(for xs)");
+    // the block is not taken for the fields of a struct literal
+    assert_parses("if a == b { c }", "(if (Equal a b))");
+    assert_parses("for x in (Range { end: 1 }) {}", "(for (struct Range end))");
+
+    // an arm which does not end with a block needs a `,` after it
+    assert_eq!(parse("match a { 0 => b 1 => c }").0, "error");
+}
+
+#[test]
+fn test_chained_comparison_is_rejected() {
+    let (printed, diagnostics) = parse("a < b < c");
+    assert_eq!(printed, "(LessThan (LessThan a b) c)");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "comparison operators cannot be chained"
+    );
+
+    let (_, diagnostics) = parse("a == b != c + 1 <= d");
+    assert_eq!(diagnostics.len(), 2);
+    let (_, diagnostics) = parse("a..b..c");
+    assert_eq!(diagnostics[0].message, "range operators cannot be chained");
+
+    assert_parses(
+        "a < b && b < c",
+        "(LogicalAnd (LessThan a b) (LessThan b c))",
+    );
+    assert_parses("(a < b) < c", "(LessThan (LessThan a b) c)");
+}
+
+#[test]
+fn test_operator_table_is_unambiguous() {
+    for (i, entry) in OPERATORS.iter().enumerate() {
+        let same_position = |other: Fixity| match (entry.fixity, other) {
+            (Fixity::Infix(_), Fixity::Infix(_)) => true,
+            (Fixity::Infix(_), Fixity::Postfix) | (Fixity::Postfix, Fixity::Infix(_)) => true,
+            (fixity, other) => fixity == other,
+        };
+        assert!(
+            !OPERATORS[i + 1..]
+                .iter()
+                .any(|other| other.token == entry.token && same_position(other.fixity)),
+            "{:?} is listed twice",
+            entry.token
+        );
+    }
+    assert_eq!(
+        operators::prefix(Some(Token::Operator(Operator::Subtract))).map(|entry| entry.operation),
+        Some(Operation::Negate)
+    );
+    assert!(operators::infix(Some(Token::EndOfFile)).is_none());
+}
+
+#[test]
+fn test_expressions_start_with_every_prefix_operator() {
+    // the grammar lists the tokens of the `Expression` hook by hand
+    for entry in OPERATORS {
+        if entry.fixity == Fixity::Prefix {
+            assert!(
+                Expression::is_first1_sets(&Some(entry.token)),
+                "{:?} does not start an expression",
+                entry.token
+            );
+        }
+    }
+}
+
+#[test]
+fn test_generated_parser_parses_expressions_by_precedence() {
+    let (ast, diagnostics) = parse_module("fn answer() { -1 + 2 * f(3) }");
+    assert!(diagnostics.is_empty());
+    let function = *ast.root().declarations().as_slice(ast.arena())[0].declaration();
+    let TopLevelStatement::FunctionDeclaration(function) = function else {
+        panic!("{function:?} is not a function");
+    };
+    let [Statement::Value(body)] = function.body().statements().as_slice(ast.arena()) else {
+        panic!("the body is not a single value");
+    };
+    assert_eq!(
+        print(body, ast.arena()),
+        "(Add (Negate 1) (Multiply 2 (call f 3)))"
+    );
+}
@@ -0,0 +1,120 @@
+use crate::compiler::symbol::Symbol;
+use crate::parser::ast::{Identifier, TopLevelStatement};
+use crate::parser::ast_node::ASTNode;
+use crate::parser::expression::{Expression, Type};
+use crate::parser::operators::Operation;
+use crate::parser::pattern::Pattern;
+use crate::parser::statement::Statement;
+use crate::parser::{parse_expression, parse_module, parse_pattern, parse_statement, parse_type};
+use crate::tokenizer::tokens::{Keyword, Literal};
+
+fn name(name: &str) -> Identifier {
+    Identifier {
+        symbol: Symbol::intern(name),
+    }
+}
+
+#[test]
+fn test_parse_expression() {
+    let (ast, diagnostics) = parse_expression("1 + 2");
+    assert!(diagnostics.is_empty());
+    let Expression::Infix { operation, lhs, .. } = *ast.root() else {
+        panic!("{:?} is not an infix expression", ast.root());
+    };
+    assert_eq!(operation, Operation::Add);
+    assert_eq!(
+        *lhs.get(ast.arena()),
+        Expression::Literal(Literal::IntegerLiteral(1))
+    );
+
+    // the whole source has to be one expression
+    let (ast, diagnostics) = parse_expression("1 2");
+    assert_eq!(*ast.root(), Expression::Invalid);
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn test_parse_type() {
+    assert_eq!(*parse_type("Int").0.root(), Type::Primitive(Keyword::Int));
+    assert_eq!(*parse_type("Meters").0.root(), Type::Named(name("Meters")));
+    assert_eq!(*parse_type("1").0.root(), Type::Invalid);
+}
+
+#[test]
+fn test_parse_pattern() {
+    let (ast, diagnostics) = parse_pattern("(mut a, _, (1))");
+    assert!(diagnostics.is_empty());
+    let Pattern::Tuple(items) = *ast.root() else {
+        panic!("{:?} is not a tuple pattern", ast.root());
+    };
+    assert_eq!(
+        items.as_slice(ast.arena()),
+        [
+            Pattern::Binding {
+                name: name("a"),
+                mutable: true
+            },
+            Pattern::Wildcard,
+            Pattern::Literal(Literal::IntegerLiteral(1)),
+        ]
+    );
+    assert!(matches!(parse_pattern("(a,)").0.root(), Pattern::Tuple(_)));
+}
+
+#[test]
+fn test_parse_statement() {
+    let (ast, diagnostics) = parse_statement("let x: Int = f(1);");
+    assert!(diagnostics.is_empty());
+    let Statement::Let { pattern, ty, value } = *ast.root() else {
+        panic!("{:?} is not a let statement", ast.root());
+    };
+    assert_eq!(
+        pattern,
+        Pattern::Binding {
+            name: name("x"),
+            mutable: false
+        }
+    );
+    assert_eq!(ty, Some(Type::Primitive(Keyword::Int)));
+    assert!(matches!(value, Some(Expression::Call { .. })));
+
+    assert!(matches!(
+        parse_statement("x += 1;").0.root(),
+        Statement::Expression(Expression::Infix { .. })
+    ));
+    let (ast, diagnostics) = parse_statement("let x");
+    assert_eq!(*ast.root(), Statement::Invalid);
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn test_block_ends_with_its_value() {
+    let (ast, diagnostics) = parse_module("fn f() { let a = 1; g(a); a }");
+    assert!(diagnostics.is_empty());
+    let TopLevelStatement::FunctionDeclaration(function) =
+        *ast.root().declarations().as_slice(ast.arena())[0].declaration()
+    else {
+        panic!("{:?} is not a function", ast.root());
+    };
+    let statements = function.body().statements().as_slice(ast.arena());
+    assert!(matches!(
+        statements,
+        [
+            Statement::Let { .. },
+            Statement::Expression(Expression::Call { .. }),
+            Statement::Value(Expression::Identifier(_)),
+        ]
+    ));
+
+    // only the last expression of a block can go without a `;`
+    let (_, diagnostics) = parse_module("fn f() { g(a) a }");
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn test_statements_start_like_expressions() {
+    assert_eq!(
+        Statement::FIRST_1_SETS.union(Expression::FIRST_1_SETS),
+        Statement::FIRST_1_SETS
+    );
+}
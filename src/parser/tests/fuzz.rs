@@ -0,0 +1,126 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::diagnostic::diagnotice_patterns::NESTING_TOO_DEEP;
+use crate::diagnostic::Diagnostic;
+use crate::parser::fuzz::{Program, ProgramGenerator, Rng};
+use crate::parser::grammar::{Grammar, TokenMap, GRAMMAR_PATH, TOKEN_MAP_PATH};
+use crate::parser::interpreter::GrammarInterpreter;
+use crate::parser::parse::DEFAULT_MAX_NESTING_DEPTH;
+use crate::parser::{parse_expression, parse_module, parse_pattern, parse_statement};
+use crate::tokenizer::tokens::{Keyword, Token};
+
+/// How many programs `test_fuzz_parser` generates for every entry point,
+/// unless `HYDENT_FUZZ_ITERATIONS` says otherwise.
+const DEFAULT_ITERATIONS: u64 = 200;
+
+/// Parses a source and returns its diagnostics.
+type EntryPoint = fn(&str) -> Vec<Diagnostic>;
+
+/// The rules the parser has an entry point for, and the entry point.
+const ENTRY_POINTS: [(&str, EntryPoint); 4] = [
+    ("Module", |source| parse_module(source).1),
+    ("Expression", |source| parse_expression(source).1),
+    ("Pattern", |source| parse_pattern(source).1),
+    ("Statement", |source| parse_statement(source).1),
+];
+
+fn load() -> (Grammar, TokenMap) {
+    let grammar = std::fs::read_to_string(GRAMMAR_PATH).unwrap();
+    let token_map = std::fs::read_to_string(TOKEN_MAP_PATH).unwrap();
+    (
+        Grammar::parse(&grammar).unwrap(),
+        TokenMap::parse(&token_map).unwrap(),
+    )
+}
+
+/// Parses `program` with `parse`, turning a panic into a failure which
+/// tells how to generate the program again.
+fn parse_without_panic(parse: EntryPoint, program: &Program, seed: u64) -> Vec<Diagnostic> {
+    let source = program.to_string();
+    catch_unwind(AssertUnwindSafe(|| parse(&source)))
+        .unwrap_or_else(|_| panic!("the parser panicked on `{source}` (seed {seed})"))
+}
+
+#[test]
+fn test_fuzz_parser() {
+    let (grammar, token_map) = load();
+    let interpreter = GrammarInterpreter::new(&grammar);
+    let iterations = std::env::var("HYDENT_FUZZ_ITERATIONS")
+        .ok()
+        .and_then(|iterations| iterations.parse().ok())
+        .unwrap_or(DEFAULT_ITERATIONS);
+
+    for (rule, parse) in ENTRY_POINTS {
+        for seed in 0..iterations {
+            let mut generator = ProgramGenerator::new(&grammar, &token_map, seed);
+            let program = generator.generate(rule);
+            assert!(
+                interpreter.parse(rule, &program.to_string()).is_ok(),
+                "`{program}` (seed {seed}) is not a {rule} of the grammar"
+            );
+            let diagnostics = parse_without_panic(parse, &program, seed);
+            assert!(
+                diagnostics.is_empty(),
+                "`{program}` (seed {seed}) is a valid {rule} but has diagnostics"
+            );
+
+            let Some(mutated) = generator.mutate(rule, &program) else {
+                continue;
+            };
+            let diagnostics = parse_without_panic(parse, &mutated, seed);
+            assert!(
+                !diagnostics.is_empty(),
+                "`{mutated}` (seed {seed}) is a broken {rule} but has no diagnostics"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_fuzz_nesting_limit() {
+    let (grammar, token_map) = load();
+    for (rule, parse) in [ENTRY_POINTS[1], ENTRY_POINTS[2]] {
+        for seed in 0..20 {
+            let mut generator = ProgramGenerator::new(&grammar, &token_map, seed);
+            let program = generator.generate(rule);
+            let nested = generator.nest(rule, &program, DEFAULT_MAX_NESTING_DEPTH);
+            let diagnostics = parse_without_panic(parse, &nested, seed);
+            assert!(
+                diagnostics
+                    .iter()
+                    .any(|diagnostic| diagnostic.id == NESTING_TOO_DEEP.id),
+                "`{nested}` (seed {seed}) is nested too deeply but not reported"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_generator_is_deterministic() {
+    let (grammar, token_map) = load();
+    let generate = |seed| {
+        let mut generator = ProgramGenerator::new(&grammar, &token_map, seed);
+        let program = generator.generate("Module");
+        let mutated = generator.mutate("Module", &program);
+        let expression = generator.generate("Expression");
+        let nested = generator.nest("Expression", &expression, 4);
+        (program, mutated, nested)
+    };
+    assert_eq!(generate(7), generate(7));
+    assert_ne!(generate(7), generate(8));
+
+    let mut rng = Rng::new(1);
+    assert!((0..100).all(|_| rng.below(3) < 3));
+}
+
+#[test]
+fn test_generator_respects_limits() {
+    let (grammar, token_map) = load();
+    for seed in 0..20 {
+        let mut generator = ProgramGenerator::new(&grammar, &token_map, seed).with_limits(2, 0);
+        // only the shallowest derivation fits, which is an empty module
+        assert_eq!(generator.generate("Module").tokens, Vec::<String>::new());
+    }
+    let spelling = token_map.spelling(&Token::Keyword(Keyword::Extern));
+    assert_eq!(spelling, Some("extern"));
+}
@@ -0,0 +1,152 @@
+use std::path::{Path, PathBuf};
+
+use crate::diagnostic::diagnotice_patterns::{
+    ANNOTATION_ARGUMENTS, ANNOTATION_TARGET, UNKNOWN_ANNOTATION,
+};
+use crate::diagnostic::{Diagnostic, DiagnosticLevel};
+use crate::parser::grammar::{
+    Grammar, Member, Modifier, RuleKind, Terminal, TokenClass, GRAMMAR_PATH,
+};
+use crate::parser::interpreter::{GrammarInterpreter, Tree};
+use crate::parser::parse_module;
+use crate::tokenizer::tokens::{Delimiter, Token};
+
+const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixture");
+
+fn load_grammar() -> Grammar {
+    let source = std::fs::read_to_string(GRAMMAR_PATH).expect("the grammar is readable");
+    Grammar::parse(&source).unwrap_or_else(|err| panic!("{GRAMMAR_PATH}: {err}"))
+}
+
+fn fixtures(dir: &Path, found: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).expect("the fixture directory is readable") {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            fixtures(&path, found);
+        } else if path.extension().is_some_and(|extension| extension == "hyt") {
+            found.push(path);
+        }
+    }
+}
+
+/// Parses every fixture with both the interpreter and the generated parser.
+/// A fixture with an AST snapshot has to be accepted by both, with the same
+/// AST, and one without has to be rejected by both. So a fixture which stops
+/// parsing fails here even if it stops parsing for the grammar too.
+#[test]
+fn test_generated_parser_matches_interpreter() {
+    let grammar = load_grammar();
+    let interpreter = GrammarInterpreter::new(&grammar);
+    let mut paths = Vec::new();
+    fixtures(Path::new(FIXTURE_DIR), &mut paths);
+    paths.sort();
+
+    let mut failures = Vec::new();
+    let (mut accepted, mut rejected) = (0, 0);
+    for path in &paths {
+        let source = std::fs::read_to_string(path).unwrap();
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let accepts = path.with_file_name(format!("{name}-ast.json")).exists();
+        let (ast, diagnostics) = parse_module(&source);
+        let parsed = !rejects_syntax(&diagnostics);
+        let interpreted = interpreter.parse("Module", &source);
+        match (accepts, parsed, &interpreted) {
+            (true, true, Ok(tree)) => {
+                if ast.to_string() != tree.to_string() {
+                    failures.push(format!(
+                        "{}: the generated parser and the grammar disagree",
+                        path.display()
+                    ));
+                }
+                accepted += 1;
+            }
+            (false, false, Err(_)) => rejected += 1,
+            _ => failures.push(format!(
+                "{}: should be {}, but the generated parser {} it and the grammar {}",
+                path.display(),
+                if accepts { "accepted" } else { "rejected" },
+                if parsed { "accepts" } else { "rejects" },
+                match &interpreted {
+                    Ok(_) => "accepts it".to_string(),
+                    Err(err) => format!("rejects it ({err})"),
+                },
+            )),
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+    assert!(accepted > 0 && rejected > 0);
+}
+
+/// Whether the generated parser reported an error other than the ones of
+/// `AnnotationRegistry`, which checks the annotations after parsing and which
+/// the grammar knows nothing about.
+fn rejects_syntax(diagnostics: &[Diagnostic]) -> bool {
+    let annotation_codes = [UNKNOWN_ANNOTATION, ANNOTATION_ARGUMENTS, ANNOTATION_TARGET];
+    diagnostics.iter().any(|diagnostic| {
+        diagnostic.level == DiagnosticLevel::Error
+            && !annotation_codes.iter().any(|code| code.id == diagnostic.id)
+    })
+}
+
+#[test]
+fn test_grammar_is_read() {
+    let grammar = load_grammar();
+    let module = grammar.get("Module").unwrap();
+    let RuleKind::Product(members) = &module.kind else {
+        panic!("Module is not a product");
+    };
+    assert_eq!(
+        members,
+        &[Member::Field {
+            name: "declarations".to_string(),
+            rule: "Item".to_string(),
+            modifier: Modifier::List,
+        }]
+    );
+
+    let err = Grammar::parse("product A { b: B }").unwrap_err();
+    assert_eq!(err.message, "rule `B` is not defined");
+    assert_eq!(err.position, 15);
+    let err = Grammar::parse(r#"product A { "not a token" }"#).unwrap_err();
+    assert_eq!(err.message, "`not a token` is not a token");
+
+    let expression = grammar.get("Expression").unwrap();
+    let RuleKind::Hook(terminals) = &expression.kind else {
+        panic!("Expression is not a hook");
+    };
+    assert!(terminals.contains(&Terminal::Class(TokenClass::Identifier)));
+    assert!(terminals.contains(&Terminal::Token(Token::Delimiter(Delimiter::LeftParen))));
+}
+
+#[test]
+fn test_interpreter_backtracks() {
+    let grammar = Grammar::parse(
+        r##"
+        product Module { items: *Item }
+        branch Item { Call Name }
+        // `Call` and `Name` both start with a name, so `Call` has to be undone
+        product Call { name: Identifier "(" ")" ";" }
+        product Name { name: Identifier ";" }
+        product Identifier with "#identifier"
+        "##,
+    )
+    .unwrap();
+    let interpreter = GrammarInterpreter::new(&grammar);
+    let tree = interpreter.parse("Module", "a; b();").unwrap();
+    let Tree::Product { fields, .. } = &tree else {
+        panic!("{tree} is not a product");
+    };
+    let Tree::List(items) = &fields[0].1 else {
+        panic!("{} is not a list", fields[0].1);
+    };
+    assert_eq!(
+        items.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        [
+            r#"{"kind":"Name","value":{"type":"Name","Identifier":{"type":"Identifier","value":"a"}}}"#,
+            r#"{"kind":"Call","value":{"type":"Call","Identifier":{"type":"Identifier","value":"b"}}}"#,
+        ]
+    );
+
+    let err = interpreter.parse("Module", "a; b(;").unwrap_err();
+    assert_eq!(err.expected, "`)`");
+}
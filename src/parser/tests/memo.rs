@@ -0,0 +1,141 @@
+use std::cell::Cell;
+
+use crate::compiler::arena::Arena;
+use crate::compiler::context::frontend::CompilerFrontendContext;
+use crate::compiler::source_holder::SourceHolder;
+use crate::compiler::symbol::{sym, SymbolFactory};
+use crate::diagnostic::stream::StockDiagnosticStream;
+use crate::parser::ast::Identifier;
+use crate::parser::base_parser::BaseParser;
+use crate::parser::generated_parser::GeneratedParser;
+use crate::parser::memo::MemoTable;
+use crate::parser::parse::Parser;
+use crate::parser::tracer::{NopeTracer, Tracer};
+use crate::parser::{parse_module_with, Ast};
+use crate::tokenizer::token_stream::TokenStream;
+use crate::tokenizer::tokenize::Tokenizer;
+
+type TestParser<'ctx, 'src, 's> = Parser<'ctx, 'src, 's, StockDiagnosticStream, NopeTracer>;
+
+/// Runs `parse` on a parser of `source` with `memo` as its memo table.
+/// Returns what it returned, the arena of the parser and the number of
+/// remembered rules.
+fn with_memo<R>(
+    source: &str,
+    memo: MemoTable,
+    parse: impl FnOnce(&mut TestParser<'_, '_, '_>) -> R,
+) -> (R, Arena, usize) {
+    let mut diagnostic_stream = StockDiagnosticStream::new();
+    let mut symbols = SymbolFactory::new(source);
+    let (tokens, _) = Tokenizer::new(source, &mut symbols).tokenize(&mut diagnostic_stream);
+    let arena = Arena::new();
+    let mut parser: TestParser<'_, '_, '_> = Parser::new(
+        TokenStream::new(tokens),
+        CompilerFrontendContext {
+            source,
+            symbol_factory: &mut symbols,
+            ast_arena: &arena,
+        },
+        &mut diagnostic_stream,
+    );
+    parser.memo = memo;
+    let parsed = parse(&mut parser);
+    let remembered = parser.memo.len();
+    drop(parser);
+    (parsed, arena, remembered)
+}
+
+#[test]
+fn test_memo_table_is_keyed_by_rule_and_position() {
+    let mut memo = MemoTable::new();
+    let main = Identifier { symbol: sym::main };
+    memo.insert(3, Ok(main), 5);
+
+    assert_eq!(
+        memo.get::<Identifier>(3)
+            .map(|(node, end)| (node.ok(), end)),
+        Some((Some(main), 5))
+    );
+    assert!(memo.get::<Identifier>(4).is_none());
+    assert!(memo.get::<u32>(3).is_none());
+    assert_eq!(memo.len(), 1);
+}
+
+#[test]
+fn test_disabled_memo_table_remembers_nothing() {
+    let mut memo = MemoTable::disabled();
+    memo.insert(0, Ok(7u32), 1);
+    assert!(memo.is_empty());
+    assert!(memo.get::<u32>(0).is_none());
+}
+
+#[test]
+fn test_memoized_parse_matches_the_plain_one() {
+    for source in [
+        "extern fn puts(); @EntryPoint; fn main() {} extern fn exit();",
+        "fn f(a: Int, b) {} struct S { a: Int b: Int }",
+    ] {
+        let parse = |memo| {
+            let (module, arena, _) = with_memo(source, memo, |parser| parser.parse());
+            Ast::new(module, arena, SourceHolder::from_source(source)).to_string()
+        };
+        assert_eq!(parse(MemoTable::new()), parse(MemoTable::disabled()));
+    }
+}
+
+thread_local! {
+    static MEMO_HITS: Cell<Vec<usize>> = const { Cell::new(Vec::new()) };
+}
+
+/// Records the positions of the memo hits on this thread.
+struct MemoHitTracer;
+
+impl Tracer for MemoHitTracer {
+    type Guard = ();
+    fn enter(_: &'static str) {}
+    fn leave(_: &'static str) {}
+    fn trace(_: &'static str) -> Self::Guard {}
+
+    fn memo_hit(position: usize) {
+        MEMO_HITS.with(|hits| {
+            let mut positions = hits.take();
+            positions.push(position);
+            hits.set(positions);
+        });
+    }
+}
+
+#[test]
+fn test_parse_module_hits_the_memo_table() {
+    // the function fails at `b` while it is tried as an item, and is parsed
+    // again to report why, which takes its params from the memo table
+    MEMO_HITS.with(Cell::take);
+    let (_, diagnostics) = parse_module_with::<MemoHitTracer>("fn f(a: Int, b) {}");
+    assert!(!diagnostics.is_empty());
+    assert_eq!(MEMO_HITS.with(Cell::take), [3]);
+}
+
+#[test]
+fn test_rule_cut_off_by_the_nesting_limit_is_not_memoized() {
+    // a boxed item is one level deep
+    let parse_item = |parser: &mut TestParser<'_, '_, '_>| {
+        parser.memoize(|this| {
+            let item = this.alloc_box(|this| this.parse_TopLevelStatement())?;
+            Ok(*item.get(this.ctx.ast_arena))
+        })
+    };
+    let (item, _, remembered) = with_memo("extern fn puts();", MemoTable::new(), |parser| {
+        parser.max_nesting_depth = 1;
+        parse_item(parser)
+    });
+    assert!(item.is_ok());
+    assert_eq!(remembered, 1);
+
+    // nested once more, the limit cuts the item off
+    let (item, _, remembered) = with_memo("extern fn puts();", MemoTable::new(), |parser| {
+        parser.max_nesting_depth = 1;
+        parser.alloc_box(parse_item)
+    });
+    assert!(item.is_err());
+    assert_eq!(remembered, 0);
+}
@@ -1 +1,11 @@
+mod annotation;
 mod ast_size_checker;
+mod expression;
+mod fragment;
+mod fuzz;
+mod interpreter;
+mod memo;
+mod nesting;
+mod recovery;
+mod tracer;
+mod visitor;
@@ -0,0 +1,100 @@
+use crate::compiler::arena::Arena;
+use crate::compiler::context::frontend::CompilerFrontendContext;
+use crate::compiler::span::Span;
+use crate::compiler::symbol::SymbolFactory;
+use crate::diagnostic::stream::StockDiagnosticStream;
+use crate::diagnostic::Diagnostic;
+use crate::parser::base_parser::BaseParser;
+use crate::parser::parse::{Parser, DEFAULT_MAX_NESTING_DEPTH};
+use crate::parser::parse_expression;
+use crate::parser::tracer::NopeTracer;
+use crate::tokenizer::token_stream::TokenStream;
+use crate::tokenizer::tokenize::Tokenizer;
+
+fn parse(source: &str, max_nesting_depth: usize) -> (bool, Vec<Diagnostic>) {
+    parse_with(source, max_nesting_depth, |parser| {
+        parser.parse_expression().is_ok()
+    })
+}
+
+/// Runs `parser_fn` on a parser of `source` and returns what it returned and
+/// the diagnostics.
+fn parse_with(
+    source: &str,
+    max_nesting_depth: usize,
+    parser_fn: impl FnOnce(&mut Parser<'_, '_, '_, StockDiagnosticStream, NopeTracer>) -> bool,
+) -> (bool, Vec<Diagnostic>) {
+    let mut diagnostic_stream = StockDiagnosticStream::new();
+    let mut symbols = SymbolFactory::new(source);
+    let (tokens, _) = Tokenizer::new(source, &mut symbols).tokenize(&mut diagnostic_stream);
+    let arena = Arena::new();
+    let mut parser: Parser<'_, '_, '_, _, NopeTracer> = Parser::new(
+        TokenStream::new(tokens),
+        CompilerFrontendContext {
+            source,
+            symbol_factory: &mut symbols,
+            ast_arena: &arena,
+        },
+        &mut diagnostic_stream,
+    );
+    parser.max_nesting_depth = max_nesting_depth;
+    let parsed = parser_fn(&mut parser);
+    (parsed, diagnostic_stream.into_vec())
+}
+
+#[test]
+fn test_deep_nesting_is_reported_instead_of_overflowing() {
+    let depth = 10_000;
+    let source = format!("{}a{}", "(".repeat(depth), ")".repeat(depth));
+    let (parsed, diagnostics) = parse(&source, DEFAULT_MAX_NESTING_DEPTH);
+
+    assert!(!parsed);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "expression nested too deeply");
+    assert_eq!(diagnostics[0].id, 2);
+    // from the outermost `(` to the one the limit was reached at
+    assert_eq!(
+        diagnostics[0].primary,
+        Span::new(0, DEFAULT_MAX_NESTING_DEPTH + 1)
+    );
+}
+
+#[test]
+fn test_nesting_limit_is_configurable() {
+    // the expression itself is one level, every parenthesis or operand one more
+    let (parsed, diagnostics) = parse("(((a)))", 4);
+    assert!(parsed && diagnostics.is_empty());
+    assert!(!parse("((((a))))", 4).0);
+    assert!(parse("-(-(-a))", 6).0);
+    assert!(!parse("-(-(-a))", 5).0);
+    // siblings do not add up, and neither do left associative chains
+    assert!(parse("((a)) + ((b))", 4).0);
+    assert!(parse(&vec!["a"; 100].join(" + "), 4).0);
+    assert!(!parse(&vec!["a"; 100].join(" = "), 4).0);
+}
+
+#[test]
+fn test_deep_nesting_is_reported_once_by_parse_expression() {
+    let source = format!("{}a{}", "(".repeat(1_000), ")".repeat(1_000));
+    let (_, diagnostics) = parse_expression(&source);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "expression nested too deeply");
+}
+
+#[test]
+fn test_deep_nesting_is_not_reported_while_backtracking() {
+    let (parsed, diagnostics) = parse_with("((((a))))", 4, |parser| {
+        parser.backtrack(|this| this.parse_expression()).is_ok()
+    });
+    assert!(!parsed);
+    assert!(diagnostics.is_empty());
+
+    // parsed for real after the backtrack, it is reported
+    let (parsed, diagnostics) = parse_with("((((a))))", 4, |parser| {
+        let _ = parser.backtrack(|this| this.parse_expression());
+        parser.parse_expression().is_ok()
+    });
+    assert!(!parsed);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "expression nested too deeply");
+}
@@ -0,0 +1,47 @@
+use crate::compiler::span::Span;
+use crate::parser::ast::TopLevelStatement;
+use crate::parser::parse_module;
+
+#[test]
+fn test_nested_list_failing_while_backtracking() {
+    // the statements are a list inside the backtracked top-level item, and
+    // they fail at `=`, so the item is parsed again to recover from there
+    let source = "fn a() { let = 1; }\nextern fn b();\nextern fn c();\n";
+    let (ast, diagnostics) = parse_module(source);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].primary, Span::new(13, 14));
+
+    let names = ast
+        .root()
+        .declarations()
+        .as_slice(ast.arena())
+        .iter()
+        .map(|item| match item.declaration() {
+            TopLevelStatement::ExternFunction(function) => function.name().symbol.as_str(),
+            TopLevelStatement::FunctionDeclaration(function) => function.name().symbol.as_str(),
+            _ => "other",
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["a", "b", "c"]);
+}
+
+#[test]
+fn test_unexpected_token_notes_the_expected_tokens() {
+    let notes = |source| {
+        parse_module(source)
+            .1
+            .into_iter()
+            .flat_map(|diagnostic| diagnostic.notes)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(notes("extern fn a()\nfn b() {}"), ["expected `;`"]);
+    assert_eq!(notes("import a;"), ["expected `{`"]);
+    assert_eq!(
+        notes("let a;"),
+        [
+            "expected one of `async`, `class`, `const`, `enum`, `extern`, `fn`, `import`, \
+          `module`, `protocol`, `pub`, `static`, `struct`, `type`"
+        ]
+    );
+    assert_eq!(notes("extern fn ();"), ["expected an identifier"]);
+}
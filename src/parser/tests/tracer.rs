@@ -0,0 +1,113 @@
+use crate::compiler::arena::Arena;
+use crate::compiler::context::frontend::CompilerFrontendContext;
+use crate::compiler::symbol::SymbolFactory;
+use crate::diagnostic::stream::StockDiagnosticStream;
+use crate::parser::base_parser::BaseParser;
+use crate::parser::expression::Expression;
+use crate::parser::parse::Parser;
+use crate::parser::tracer::{ParseTrace, TraceNode, Tracer, TreeTracer};
+use crate::tokenizer::token_stream::TokenStream;
+use crate::tokenizer::tokenize::Tokenizer;
+use crate::tokenizer::tokens::{Delimiter, Token};
+
+/// Parses `source` as an expression, then backtracks over a `;` which is not
+/// there, and returns the trace.
+fn trace(source: &str) -> ParseTrace {
+    let mut diagnostic_stream = StockDiagnosticStream::new();
+    let mut symbols = SymbolFactory::new(source);
+    let (tokens, _) = Tokenizer::new(source, &mut symbols).tokenize(&mut diagnostic_stream);
+    let arena = Arena::new();
+    let mut parser: Parser<'_, '_, '_, _, TreeTracer> = Parser::new(
+        TokenStream::new(tokens),
+        CompilerFrontendContext {
+            source,
+            symbol_factory: &mut symbols,
+            ast_arena: &arena,
+        },
+        &mut diagnostic_stream,
+    );
+    TreeTracer::start();
+    {
+        let _guard = TreeTracer::trace("Fragment");
+        parser.parse_expression().unwrap();
+        let _ = parser.backtrack(|this| {
+            this.consume_token();
+            this.expect(&Token::Delimiter(Delimiter::Semicolon))?;
+            Ok(Expression::Invalid)
+        });
+    }
+    TreeTracer::finish()
+}
+
+fn rules(nodes: &[TraceNode]) -> Vec<(&'static str, std::ops::Range<usize>)> {
+    nodes
+        .iter()
+        .filter_map(|node| match node {
+            TraceNode::Rule(rule) => Some((rule.name, rule.tokens.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_tree_tracer_records_rules_and_backtracks() {
+    let trace = trace("a + b");
+    let [TraceNode::Rule(fragment)] = &trace.nodes[..] else {
+        panic!("{:?} has not exactly one root", trace.nodes);
+    };
+    assert_eq!(fragment.tokens, 0..3);
+    assert_eq!(rules(&fragment.children), [("Expression", 0..3)]);
+    let TraceNode::Rule(expression) = &fragment.children[0] else {
+        unreachable!()
+    };
+    assert_eq!(rules(&expression.children), [("Expression", 2..3)]);
+    assert!(matches!(
+        fragment.children[1..],
+        [
+            TraceNode::Backtrack { position: 3, .. },
+            TraceNode::Rollback { from: 4, to: 3, .. }
+        ]
+    ));
+}
+
+#[test]
+fn test_trace_output_formats() {
+    let trace = trace("a + b");
+
+    let mut text = Vec::new();
+    trace.write_text(&mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    let lines = text
+        .lines()
+        .map(|line| line.rsplit_once(' ').map_or(line, |(rule, _)| rule))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            "Fragment 0..3",
+            "  Expression 0..3",
+            "    Expression 2..3",
+            "  backtrack at",
+            "  rollback 4 ->",
+        ]
+    );
+
+    let mut chrome = Vec::new();
+    trace.write_chrome_trace(&mut chrome).unwrap();
+    let chrome = String::from_utf8(chrome).unwrap();
+    assert!(chrome.starts_with("{\"traceEvents\":["));
+    assert_eq!(chrome.matches("\"ph\":\"X\"").count(), 3);
+    assert_eq!(chrome.matches("\"ph\":\"i\"").count(), 2);
+
+    let hot_rules = trace.hot_rules();
+    let expression = hot_rules
+        .iter()
+        .find(|rule| rule.name == "Expression")
+        .unwrap();
+    assert_eq!(expression.calls, 2);
+    let fragment = hot_rules
+        .iter()
+        .find(|rule| rule.name == "Fragment")
+        .unwrap();
+    assert_eq!(fragment.rollbacks, 1);
+}
@@ -0,0 +1,95 @@
+use crate::compiler::arena::Arena;
+use crate::compiler::symbol::Symbol;
+use crate::parser::ast::{
+    rebuild_TopLevelStatement, ASTFolder, ASTVisitorMut, BlockExpression, ExternFunction,
+    FunctionDeclaration, Identifier, TopLevelStatement,
+};
+use crate::parser::{parse_module, Ast};
+
+/// The names of the declarations of `ast`, prefixed with `fn` or `extern`.
+fn declarations(ast: &Ast<'_>) -> Vec<String> {
+    ast.root()
+        .declarations()
+        .as_slice(ast.arena())
+        .iter()
+        .map(|item| match item.declaration() {
+            TopLevelStatement::ExternFunction(function) => {
+                format!("extern {}", function.name().symbol.as_str())
+            }
+            TopLevelStatement::FunctionDeclaration(function) => {
+                format!("fn {}", function.name().symbol.as_str())
+            }
+            _ => "other".to_string(),
+        })
+        .collect()
+}
+
+struct Renamer;
+
+impl ASTVisitorMut for Renamer {
+    fn visit_Identifier(&mut self, node: &mut Identifier, _: &mut Arena) {
+        node.symbol = Symbol::intern(&format!("renamed_{}", node.symbol.as_str()));
+    }
+}
+
+#[test]
+fn test_visitor_mut_rewrites_in_place() {
+    let (mut ast, diagnostics) = parse_module("extern fn puts(); extern fn exit();");
+    assert!(diagnostics.is_empty());
+
+    ast.visit_mut(&mut Renamer);
+    assert_eq!(
+        declarations(&ast),
+        ["extern renamed_puts", "extern renamed_exit"]
+    );
+}
+
+/// Turns every extern function into a function with an empty body.
+struct DefineExterns;
+
+impl ASTFolder for DefineExterns {
+    fn fold_TopLevelStatement(
+        &mut self,
+        node: &TopLevelStatement,
+        from: &Arena,
+        to: &Arena,
+    ) -> TopLevelStatement {
+        // fold the children first, so `params` points into `to`
+        match rebuild_TopLevelStatement(self, node, from, to) {
+            TopLevelStatement::ExternFunction(ExternFunction {
+                visibility,
+                is_async,
+                name,
+                params,
+                return_type,
+                panics,
+            }) => TopLevelStatement::FunctionDeclaration(FunctionDeclaration {
+                visibility,
+                is_async,
+                name,
+                generics: None,
+                params,
+                return_type,
+                panics,
+                body: BlockExpression {
+                    statements: to.alloc_iter(std::iter::empty()),
+                },
+            }),
+            node => node,
+        }
+    }
+}
+
+#[test]
+fn test_folder_rebuilds_into_new_arena() {
+    let (ast, _) = parse_module("extern fn puts(); extern fn exit();");
+
+    let folded = ast.fold(&mut DefineExterns);
+    assert_eq!(declarations(&folded), ["fn puts", "fn exit"]);
+    assert_eq!(declarations(&ast), ["extern puts", "extern exit"]);
+
+    // folding without replacements copies the tree
+    struct Identity;
+    impl ASTFolder for Identity {}
+    assert_eq!(declarations(&ast.fold(&mut Identity)), declarations(&ast));
+}
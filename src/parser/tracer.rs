@@ -1,5 +1,11 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::Range;
+use std::time::{Duration, Instant};
 use std::{fmt, io};
 
+use crate::compiler::runtime::profile::escape_json;
 use crate::compiler::span::Span;
 
 pub trait Tracer: Sized {
@@ -7,6 +13,19 @@ pub trait Tracer: Sized {
     fn enter(name: &'static str);
     fn leave(name: &'static str);
     fn trace(name: &'static str) -> Self::Guard;
+
+    /// The parser moved to the token at `position`.
+    fn advance(_position: usize) {}
+
+    /// The parser started a backtrack at the token at `position`.
+    fn backtrack(_position: usize) {}
+
+    /// A failed backtrack moved the parser back from `from` to `to`.
+    fn rollback(_from: usize, _to: usize) {}
+
+    /// A rule started at the token at `position` was taken from the memo
+    /// table instead of being parsed again.
+    fn memo_hit(_position: usize) {}
 }
 
 pub struct NopeTracer;
@@ -44,3 +63,303 @@ impl Drop for TraceGuard {
         print!("</{}>", self.name);
     }
 }
+
+/// Records the rules the parser enters as a tree, see `ParseTrace`.
+///
+/// Rules are recorded per thread, from `TreeTracer::start` until
+/// `TreeTracer::finish`.
+pub struct TreeTracer;
+
+thread_local! {
+    static RECORDER: RefCell<Recorder> = RefCell::new(Recorder::new());
+}
+
+impl TreeTracer {
+    /// Throws away what was recorded on this thread so far.
+    pub fn start() {
+        RECORDER.with_borrow_mut(|recorder| *recorder = Recorder::new());
+    }
+
+    /// Returns what was recorded on this thread since `start`. Rules which
+    /// were entered but not left yet are dropped.
+    pub fn finish() -> ParseTrace {
+        RECORDER.with_borrow_mut(|recorder| ParseTrace {
+            nodes: std::mem::replace(recorder, Recorder::new()).nodes,
+        })
+    }
+}
+
+impl Tracer for TreeTracer {
+    type Guard = TreeGuard;
+    fn enter(name: &'static str) {
+        RECORDER.with_borrow_mut(|recorder| recorder.enter(name));
+    }
+
+    fn leave(_: &'static str) {
+        RECORDER.with_borrow_mut(Recorder::leave);
+    }
+
+    fn trace(name: &'static str) -> TreeGuard {
+        Self::enter(name);
+        TreeGuard { name }
+    }
+
+    fn advance(position: usize) {
+        RECORDER.with_borrow_mut(|recorder| recorder.position = position);
+    }
+
+    fn backtrack(position: usize) {
+        RECORDER.with_borrow_mut(|recorder| {
+            let at = recorder.started.elapsed();
+            recorder.push(TraceNode::Backtrack { at, position });
+        });
+    }
+
+    fn rollback(from: usize, to: usize) {
+        RECORDER.with_borrow_mut(|recorder| {
+            let at = recorder.started.elapsed();
+            recorder.position = to;
+            recorder.push(TraceNode::Rollback { at, from, to });
+        });
+    }
+}
+
+pub struct TreeGuard {
+    name: &'static str,
+}
+
+impl Drop for TreeGuard {
+    fn drop(&mut self) {
+        TreeTracer::leave(self.name);
+    }
+}
+
+struct Recorder {
+    started: Instant,
+    /// The position of the next token.
+    position: usize,
+    /// The rules entered but not left yet, the innermost last.
+    open: Vec<RuleTrace>,
+    nodes: Vec<TraceNode>,
+}
+
+impl Recorder {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            position: 0,
+            open: Vec::new(),
+            nodes: Vec::new(),
+        }
+    }
+
+    fn enter(&mut self, name: &'static str) {
+        self.open.push(RuleTrace {
+            name,
+            tokens: self.position..self.position,
+            start: self.started.elapsed(),
+            duration: Duration::ZERO,
+            children: Vec::new(),
+        });
+    }
+
+    fn leave(&mut self) {
+        let Some(mut rule) = self.open.pop() else {
+            return;
+        };
+        rule.tokens.end = self.position;
+        rule.duration = self.started.elapsed() - rule.start;
+        self.push(TraceNode::Rule(rule));
+    }
+
+    /// Adds `node` to the innermost open rule.
+    fn push(&mut self, node: TraceNode) {
+        match self.open.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => self.nodes.push(node),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TraceNode {
+    Rule(RuleTrace),
+    Backtrack {
+        /// Relative to `TreeTracer::start`.
+        at: Duration,
+        position: usize,
+    },
+    Rollback {
+        /// Relative to `TreeTracer::start`.
+        at: Duration,
+        from: usize,
+        to: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleTrace {
+    pub name: &'static str,
+    /// The positions of the tokens the rule consumed.
+    pub tokens: Range<usize>,
+    /// Relative to `TreeTracer::start`.
+    pub start: Duration,
+    pub duration: Duration,
+    pub children: Vec<TraceNode>,
+}
+
+impl RuleTrace {
+    /// The time spent in this rule but not in the rules it entered.
+    pub fn self_time(&self) -> Duration {
+        let children = self
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                TraceNode::Rule(rule) => Some(rule.duration),
+                _ => None,
+            })
+            .sum();
+        self.duration.saturating_sub(children)
+    }
+}
+
+/// Summary of every entry of one rule, see `ParseTrace::hot_rules`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotRule {
+    pub name: &'static str,
+    pub calls: usize,
+    /// The sum of `RuleTrace::self_time` of every entry.
+    pub self_time: Duration,
+    /// How many backtracks failed directly inside the rule.
+    pub rollbacks: usize,
+}
+
+/// The rules recorded by `TreeTracer`, in the order they were entered.
+#[derive(Debug, Clone, Default)]
+pub struct ParseTrace {
+    pub nodes: Vec<TraceNode>,
+}
+
+impl ParseTrace {
+    /// Writes the trace as an indented tree, one rule or event per line.
+    pub fn write_text(&self, writer: &mut impl Write) -> io::Result<()> {
+        fn write_nodes(
+            nodes: &[TraceNode],
+            depth: usize,
+            writer: &mut impl Write,
+        ) -> io::Result<()> {
+            let indent = "  ".repeat(depth);
+            for node in nodes {
+                match node {
+                    TraceNode::Rule(rule) => {
+                        writeln!(
+                            writer,
+                            "{indent}{} {}..{} {:.3?}",
+                            rule.name, rule.tokens.start, rule.tokens.end, rule.duration
+                        )?;
+                        write_nodes(&rule.children, depth + 1, writer)?;
+                    }
+                    TraceNode::Backtrack { position, .. } => {
+                        writeln!(writer, "{indent}backtrack at {position}")?;
+                    }
+                    TraceNode::Rollback { from, to, .. } => {
+                        writeln!(writer, "{indent}rollback {from} -> {to}")?;
+                    }
+                }
+            }
+            Ok(())
+        }
+        write_nodes(&self.nodes, 0, writer)
+    }
+
+    /// Writes the trace in the Chrome trace event format. Rules are complete
+    /// events, backtracks and rollbacks instant events.
+    pub fn write_chrome_trace(&self, writer: &mut impl Write) -> io::Result<()> {
+        fn events(nodes: &[TraceNode], out: &mut Vec<String>) {
+            for node in nodes {
+                out.push(match node {
+                    TraceNode::Rule(rule) => format!(
+                        "{{\"name\":\"{}\",\"cat\":\"rule\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":1,\"args\":{{\"tokens\":\"{}..{}\"}}}}",
+                        escape_json(rule.name),
+                        rule.start.as_micros(),
+                        rule.duration.as_micros(),
+                        rule.tokens.start,
+                        rule.tokens.end,
+                    ),
+                    TraceNode::Backtrack { at, position } => format!(
+                        "{{\"name\":\"backtrack\",\"cat\":\"backtrack\",\"ph\":\"i\",\"s\":\"t\",\"ts\":{},\"pid\":1,\"tid\":1,\"args\":{{\"position\":{position}}}}}",
+                        at.as_micros(),
+                    ),
+                    TraceNode::Rollback { at, from, to } => format!(
+                        "{{\"name\":\"rollback\",\"cat\":\"backtrack\",\"ph\":\"i\",\"s\":\"t\",\"ts\":{},\"pid\":1,\"tid\":1,\"args\":{{\"from\":{from},\"to\":{to}}}}}",
+                        at.as_micros(),
+                    ),
+                });
+                if let TraceNode::Rule(rule) = node {
+                    events(&rule.children, out);
+                }
+            }
+        }
+        let mut out = Vec::new();
+        events(&self.nodes, &mut out);
+        writeln!(writer, "{{\"traceEvents\":[")?;
+        for (i, event) in out.iter().enumerate() {
+            let separator = if i + 1 == out.len() { "" } else { "," };
+            writeln!(writer, "{event}{separator}")?;
+        }
+        writeln!(writer, "]}}")
+    }
+
+    /// Sums up the entries of every rule, the rule with the most self time
+    /// first.
+    pub fn hot_rules(&self) -> Vec<HotRule> {
+        fn collect(nodes: &[TraceNode], rules: &mut HashMap<&'static str, HotRule>) {
+            for node in nodes {
+                let TraceNode::Rule(rule) = node else {
+                    continue;
+                };
+                let hot = rules.entry(rule.name).or_insert(HotRule {
+                    name: rule.name,
+                    calls: 0,
+                    self_time: Duration::ZERO,
+                    rollbacks: 0,
+                });
+                hot.calls += 1;
+                hot.self_time += rule.self_time();
+                hot.rollbacks += rule
+                    .children
+                    .iter()
+                    .filter(|child| matches!(child, TraceNode::Rollback { .. }))
+                    .count();
+                collect(&rule.children, rules);
+            }
+        }
+        let mut rules = HashMap::new();
+        collect(&self.nodes, &mut rules);
+        let mut rules = rules.into_values().collect::<Vec<_>>();
+        rules.sort_by(|a, b| b.self_time.cmp(&a.self_time).then(a.name.cmp(b.name)));
+        rules
+    }
+
+    /// Writes a table of the `n` rules with the most self time.
+    pub fn write_hot_rules(&self, n: usize, writer: &mut impl Write) -> io::Result<()> {
+        let mut hot_rules = self.hot_rules();
+        hot_rules.truncate(n);
+        writeln!(
+            writer,
+            "{:>12}  {:>8}  {:>9}  rule",
+            "self time", "calls", "rollbacks",
+        )?;
+        for rule in hot_rules {
+            writeln!(
+                writer,
+                "{:>12}  {:>8}  {:>9}  {}",
+                format!("{:.3?}", rule.self_time),
+                rule.calls,
+                rule.rollbacks,
+                rule.name,
+            )?;
+        }
+        Ok(())
+    }
+}
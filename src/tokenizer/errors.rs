@@ -15,8 +15,8 @@ pub enum TokenizeErrKind {
 
 #[derive(Debug)]
 pub struct TokenizeErr {
-    kind: TokenizeErrKind,
-    span: Span,
+    pub kind: TokenizeErrKind,
+    pub span: Span,
 }
 
 impl TokenizeErr {
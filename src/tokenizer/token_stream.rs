@@ -26,6 +26,16 @@ impl TokenStream {
         self.tokens.get(self.cursor + index).copied()
     }
 
+    /// The index of the next token, used to key the memo table of the parser.
+    pub fn position(&self) -> usize {
+        self.cursor
+    }
+
+    /// Moves to `position`, which must have been returned by `position`.
+    pub fn seek(&mut self, position: usize) {
+        self.cursor = position;
+    }
+
     pub fn checkpoint(&mut self) {
         self.checkpoints.push(self.cursor);
     }
@@ -51,6 +61,12 @@ impl TokenStream {
             .map(|(_, span)| *span)
             .unwrap_or_else(|| self.tokens.last().map(|(_, span)| *span).unwrap())
     }
+
+    /// The span of the token before the next one.
+    pub fn get_previous_span(&self) -> Option<Span> {
+        let previous = self.cursor.min(self.tokens.len()).checked_sub(1)?;
+        Some(self.tokens[previous].1)
+    }
 }
 
 impl std::iter::Iterator for TokenStream {
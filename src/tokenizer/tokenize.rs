@@ -27,7 +27,7 @@ impl<'src, 'ctx> Tokenizer<'src, 'ctx> {
             current_pos: 0,
             input: input.as_bytes(),
             symbol_factory,
-            line_starts: Vec::new(),
+            line_starts: vec![0],
         }
     }
 
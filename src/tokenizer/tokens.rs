@@ -1,7 +1,7 @@
 //! token types
 
 use crate::compiler::span::Span;
-use crate::compiler::symbol::Symbol;
+use crate::compiler::symbol::{Interner, Symbol};
 use crate::utility::hashable_float::HashableFloat;
 
 #[derive(Debug, PartialEq, Clone, Copy, Hash)]
@@ -17,7 +17,7 @@ pub enum Token {
 }
 
 /// Represents the keywords in the Hydent programming language.
-#[derive(Debug, PartialEq, Clone, Copy, Hash)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 #[repr(u8)]
 pub enum Keyword {
     DoubleFloat,
@@ -68,7 +68,7 @@ pub enum Keyword {
     In,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy, Hash)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum Literal {
     IntegerLiteral(i32),
     FloatLiteral(HashableFloat<f32>),
@@ -118,6 +118,7 @@ pub enum Operator {
     BitwiseNot,         // ~
     MemberAccess,       // .
     Wildcard,           // _
+    Question,           // ?
 }
 
 /// Represents the delimiters in the Hydent programming language.
@@ -147,19 +148,42 @@ impl Token {
     pub const fn is_identifier(&self) -> bool {
         matches!(self, Self::Identifier(_))
     }
+
+    /// Returns an adaptor which displays identifiers with their names in `interner`.
+    pub fn display<'a>(&'a self, interner: &'a Interner) -> TokenDisplay<'a> {
+        TokenDisplay {
+            token: self,
+            interner,
+        }
+    }
+}
+
+/// Displays a `Token`, see `Token::display`.
+pub struct TokenDisplay<'a> {
+    token: &'a Token,
+    interner: &'a Interner,
 }
 
+/// Displays identifiers with their names in the global interner.
 impl std::fmt::Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Comment(_) => write!(f, "comment"),
-            Self::Delimiter(delimiter) => write!(f, "{}", delimiter),
-            Self::EndOfFile => write!(f, "EOF"),
-            Self::Identifier(symbol) => write!(f, "identifier ({})", symbol.raw()),
-            Self::Keyword(keyword) => write!(f, "{} keyword", keyword),
-            Self::Literal(literal) => write!(f, "{}", literal),
-            Self::Operator(operator) => write!(f, "{} operator", operator),
-            Self::Invalid => write!(f, "invalid token"),
+        self.display(Interner::global()).fmt(f)
+    }
+}
+
+impl std::fmt::Display for TokenDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.token {
+            Token::Comment(_) => write!(f, "comment"),
+            Token::Delimiter(delimiter) => write!(f, "{}", delimiter),
+            Token::EndOfFile => write!(f, "EOF"),
+            Token::Identifier(symbol) => {
+                write!(f, "identifier `{}`", symbol.display(self.interner))
+            }
+            Token::Keyword(keyword) => write!(f, "{} keyword", keyword),
+            Token::Literal(literal) => write!(f, "{}", literal),
+            Token::Operator(operator) => write!(f, "{} operator", operator),
+            Token::Invalid => write!(f, "invalid token"),
         }
     }
 }
@@ -286,6 +310,7 @@ impl std::fmt::Display for Operator {
             Self::BitwiseNot => write!(f, "~"),
             Self::MemberAccess => write!(f, "."),
             Self::Wildcard => write!(f, "_"),
+            Self::Question => write!(f, "?"),
         }
     }
 }
@@ -3,6 +3,7 @@
 pub mod hashable_float;
 pub mod peekable_n;
 pub mod read_file_query;
+pub mod vfs;
 
 #[cfg(test)]
 mod tests;
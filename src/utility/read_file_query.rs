@@ -1,4 +1,10 @@
-use crate::compiler::runtime::Query;
+use std::hash::Hash;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::compiler::runtime::persist::{CacheErr, Decoder, Encoder, Persist};
+use crate::compiler::runtime::query_sys::{Database, PersistentQuery, Query};
 
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub enum FileOpenErr {
@@ -7,19 +13,119 @@ pub enum FileOpenErr {
     LockedByAnotherProcess,
     FileTooLarge,
     TooManyOpenFiles,
+    NotUtf8,
+    Other(ErrorKind),
+}
+
+impl FileOpenErr {
+    pub fn from_io(err: &std::io::Error) -> Self {
+        match err.kind() {
+            ErrorKind::NotFound => FileOpenErr::NotFound,
+            ErrorKind::PermissionDenied => FileOpenErr::PermissionDenied,
+            ErrorKind::ResourceBusy | ErrorKind::WouldBlock => FileOpenErr::LockedByAnotherProcess,
+            ErrorKind::FileTooLarge => FileOpenErr::FileTooLarge,
+            ErrorKind::InvalidData => FileOpenErr::NotUtf8,
+            kind => match err.raw_os_error() {
+                // EMFILE and ENFILE
+                #[cfg(unix)]
+                Some(23 | 24) => FileOpenErr::TooManyOpenFiles,
+                // ERROR_TOO_MANY_OPEN_FILES
+                #[cfg(windows)]
+                Some(4) => FileOpenErr::TooManyOpenFiles,
+                // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+                #[cfg(windows)]
+                Some(32 | 33) => FileOpenErr::LockedByAnotherProcess,
+                _ => FileOpenErr::Other(kind),
+            },
+        }
+    }
+}
+
+/// The kind of an `Other` error is not written, since `ErrorKind` can't be
+/// built back from a number. It is read back as `ErrorKind::Other`.
+impl Persist for FileOpenErr {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_u8(match self {
+            FileOpenErr::NotFound => 0,
+            FileOpenErr::PermissionDenied => 1,
+            FileOpenErr::LockedByAnotherProcess => 2,
+            FileOpenErr::FileTooLarge => 3,
+            FileOpenErr::TooManyOpenFiles => 4,
+            FileOpenErr::NotUtf8 => 5,
+            FileOpenErr::Other(_) => 6,
+        });
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        match decoder.read_u8()? {
+            0 => Ok(FileOpenErr::NotFound),
+            1 => Ok(FileOpenErr::PermissionDenied),
+            2 => Ok(FileOpenErr::LockedByAnotherProcess),
+            3 => Ok(FileOpenErr::FileTooLarge),
+            4 => Ok(FileOpenErr::TooManyOpenFiles),
+            5 => Ok(FileOpenErr::NotUtf8),
+            6 => Ok(FileOpenErr::Other(ErrorKind::Other)),
+            _ => Err(CacheErr::Corrupted),
+        }
+    }
+}
+
+/// The contents of a source file together with their hash.
+///
+/// Only the hash is fed to `Hash`, so the query system does not hash the
+/// whole file again to find out whether it changed.
+#[derive(Debug, Clone)]
+pub struct FileContents {
+    text: Arc<str>,
+    hash: u64,
+}
+
+impl FileContents {
+    pub fn new(text: Arc<str>, hash: u64) -> Self {
+        Self { text, hash }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl Hash for FileContents {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.hash.hash(state);
+    }
+}
+
+impl PartialEq for FileContents {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.text == other.text
+    }
+}
+
+impl Eq for FileContents {}
+
+impl Persist for FileContents {
+    fn encode(&self, encoder: &mut Encoder<'_>) {
+        encoder.write_str(&self.text);
+        encoder.write_u64(self.hash);
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, CacheErr> {
+        let text = decoder.read_str()?.into();
+        let hash = decoder.read_u64()?;
+        Ok(Self { text, hash })
+    }
 }
 
 pub struct ReadFileQuery;
 impl Query for ReadFileQuery {
     type From = String;
-    type To = Result<String, FileOpenErr>;
-    fn run<E: crate::compiler::runtime::Engine>(engine: &E, src: Self::From) -> Self::To {
-        // check if file has edited
-        let mut file = std::fs::File::open(&src);
-
-        // read file
-
-        // return file contents as string
-        todo!()
+    type To = Result<FileContents, FileOpenErr>;
+    const IS_INPUT: bool = true;
+    fn run(db: &Database, src: Self::From) -> Self::To {
+        db.source_files().read(Path::new(&src))
     }
 }
+impl PersistentQuery for ReadFileQuery {
+    const NAME: &'static str = "ReadFile";
+}
@@ -1 +1,2 @@
 mod peekable_n;
+mod vfs;
@@ -0,0 +1,112 @@
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::compiler::runtime::query_sys::Database;
+use crate::utility::read_file_query::{FileOpenErr, ReadFileQuery};
+use crate::utility::vfs::{DiskVfs, MapVfs, OverlayVfs, SourceFiles, Vfs};
+
+#[test]
+fn test_read_file_query_through_map_vfs() {
+    let mut db = Database::new();
+    db.set_source_files(SourceFiles::new(MapVfs::from([(
+        "main.hyd",
+        "fn main() {}",
+    )])));
+
+    let contents = db.fetch::<ReadFileQuery>("main.hyd".to_string()).unwrap();
+    assert_eq!(contents.unwrap().text(), "fn main() {}");
+    let missing = db.fetch::<ReadFileQuery>("lib.hyd".to_string()).unwrap();
+    assert_eq!(missing, Err(FileOpenErr::NotFound));
+}
+
+#[test]
+fn test_size_limit() {
+    let files = SourceFiles::new(MapVfs::from([("small.hyd", "a"), ("large.hyd", "abcdef")]))
+        .with_size_limit(4);
+    assert!(files.read(Path::new("small.hyd")).is_ok());
+    assert_eq!(
+        files.read(Path::new("large.hyd")),
+        Err(FileOpenErr::FileTooLarge)
+    );
+}
+
+#[test]
+fn test_overlay_shadows_base() {
+    let overlay = Arc::new(OverlayVfs::new(MapVfs::from([("main.hyd", "saved")])));
+    let mut db = Database::new();
+    db.set_source_files(SourceFiles::new(overlay.clone()));
+    let read = |db: &Database| {
+        db.fetch::<ReadFileQuery>("main.hyd".to_string())
+            .unwrap()
+            .unwrap()
+            .text()
+            .to_string()
+    };
+    assert_eq!(read(&db), "saved");
+
+    overlay.set_buffer("main.hyd", "unsaved");
+    db.new_revision();
+    assert_eq!(read(&db), "unsaved");
+
+    overlay.close_buffer(Path::new("main.hyd"));
+    db.new_revision();
+    assert_eq!(read(&db), "saved");
+}
+
+#[test]
+fn test_disk_vfs_detects_changes() {
+    let dir = std::env::temp_dir().join(format!("hydent-vfs-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.hyd");
+    std::fs::write(&path, "first").unwrap();
+
+    let files = SourceFiles::new(DiskVfs);
+    let first = files.read(&path).unwrap();
+    assert_eq!(first, files.read(&path).unwrap());
+
+    std::fs::write(&path, "second version").unwrap();
+    assert_eq!(files.read(&path).unwrap().text(), "second version");
+
+    // a rewrite of the same length within the granularity of modification
+    // times leaves the metadata as it was
+    let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+    std::fs::write(&path, "second edition").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    assert_eq!(files.read(&path).unwrap().text(), "second edition");
+    assert_eq!(
+        DiskVfs.read(&dir.join("missing.hyd")),
+        Err(FileOpenErr::NotFound)
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_io_error_kinds_map_to_file_open_err() {
+    let map = |kind: ErrorKind| FileOpenErr::from_io(&std::io::Error::from(kind));
+    assert_eq!(map(ErrorKind::NotFound), FileOpenErr::NotFound);
+    assert_eq!(
+        map(ErrorKind::PermissionDenied),
+        FileOpenErr::PermissionDenied
+    );
+    assert_eq!(map(ErrorKind::FileTooLarge), FileOpenErr::FileTooLarge);
+    assert_eq!(
+        map(ErrorKind::ResourceBusy),
+        FileOpenErr::LockedByAnotherProcess
+    );
+    assert_eq!(map(ErrorKind::InvalidData), FileOpenErr::NotUtf8);
+    assert_eq!(
+        map(ErrorKind::Interrupted),
+        FileOpenErr::Other(ErrorKind::Interrupted)
+    );
+    #[cfg(unix)]
+    assert_eq!(
+        FileOpenErr::from_io(&std::io::Error::from_raw_os_error(24)),
+        FileOpenErr::TooManyOpenFiles
+    );
+}
@@ -0,0 +1,222 @@
+//! Virtual file system the compiler reads source files through.
+//!
+//! `DiskVfs` reads the real file system, `OverlayVfs` puts the unsaved buffers
+//! of an editor on top of another `Vfs`, and `MapVfs` serves files from a map,
+//! which is handy in tests. `SourceFiles` sits in front of a `Vfs` and skips
+//! reading files whose length and modification time did not change.
+
+use std::collections::{hash_map::DefaultHasher, HashMap};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use crate::utility::read_file_query::{FileContents, FileOpenErr};
+
+/// Files larger than this are rejected with `FileOpenErr::FileTooLarge` by default.
+pub const DEFAULT_SIZE_LIMIT: u64 = 64 * 1024 * 1024;
+
+/// How coarsely file systems may store modification times. A file modified
+/// this shortly before it was read can change again without its modification
+/// time changing.
+const MTIME_GRANULARITY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub len: u64,
+    /// `None` if the backend can not tell, in which case the file is always read.
+    pub modified: Option<SystemTime>,
+}
+
+pub trait Vfs: Send + Sync {
+    fn metadata(&self, path: &Path) -> Result<FileMetadata, FileOpenErr>;
+    fn read(&self, path: &Path) -> Result<String, FileOpenErr>;
+}
+
+impl<V: Vfs + ?Sized> Vfs for Arc<V> {
+    fn metadata(&self, path: &Path) -> Result<FileMetadata, FileOpenErr> {
+        (**self).metadata(path)
+    }
+
+    fn read(&self, path: &Path) -> Result<String, FileOpenErr> {
+        (**self).read(path)
+    }
+}
+
+/// Reads files from the disk.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiskVfs;
+
+impl Vfs for DiskVfs {
+    fn metadata(&self, path: &Path) -> Result<FileMetadata, FileOpenErr> {
+        let metadata = std::fs::metadata(path).map_err(|err| FileOpenErr::from_io(&err))?;
+        Ok(FileMetadata {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    fn read(&self, path: &Path) -> Result<String, FileOpenErr> {
+        std::fs::read_to_string(path).map_err(|err| FileOpenErr::from_io(&err))
+    }
+}
+
+/// Puts unsaved editor buffers on top of another `Vfs`.
+pub struct OverlayVfs<V: Vfs> {
+    base: V,
+    buffers: RwLock<HashMap<PathBuf, String>>,
+}
+
+impl<V: Vfs> OverlayVfs<V> {
+    pub fn new(base: V) -> Self {
+        Self {
+            base,
+            buffers: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Makes `path` read as `contents` until the buffer is closed.
+    pub fn set_buffer(&self, path: impl Into<PathBuf>, contents: impl Into<String>) {
+        self.buffers
+            .write()
+            .unwrap()
+            .insert(path.into(), contents.into());
+    }
+
+    /// Makes `path` read from the underlying `Vfs` again.
+    pub fn close_buffer(&self, path: &Path) {
+        self.buffers.write().unwrap().remove(path);
+    }
+}
+
+impl<V: Vfs> Vfs for OverlayVfs<V> {
+    fn metadata(&self, path: &Path) -> Result<FileMetadata, FileOpenErr> {
+        match self.buffers.read().unwrap().get(path) {
+            Some(buffer) => Ok(FileMetadata {
+                len: buffer.len() as u64,
+                modified: None,
+            }),
+            None => self.base.metadata(path),
+        }
+    }
+
+    fn read(&self, path: &Path) -> Result<String, FileOpenErr> {
+        match self.buffers.read().unwrap().get(path) {
+            Some(buffer) => Ok(buffer.clone()),
+            None => self.base.read(path),
+        }
+    }
+}
+
+/// Serves files from a map, e.g. `MapVfs::from([("main.hyd", "...")])`.
+#[derive(Debug, Clone, Default)]
+pub struct MapVfs {
+    files: HashMap<PathBuf, String>,
+}
+
+impl<P: Into<PathBuf>, S: Into<String>, const N: usize> From<[(P, S); N]> for MapVfs {
+    fn from(files: [(P, S); N]) -> Self {
+        Self {
+            files: files
+                .into_iter()
+                .map(|(path, contents)| (path.into(), contents.into()))
+                .collect(),
+        }
+    }
+}
+
+impl Vfs for MapVfs {
+    fn metadata(&self, path: &Path) -> Result<FileMetadata, FileOpenErr> {
+        let contents = self.files.get(path).ok_or(FileOpenErr::NotFound)?;
+        Ok(FileMetadata {
+            len: contents.len() as u64,
+            modified: None,
+        })
+    }
+
+    fn read(&self, path: &Path) -> Result<String, FileOpenErr> {
+        self.files.get(path).cloned().ok_or(FileOpenErr::NotFound)
+    }
+}
+
+struct SeenFile {
+    metadata: FileMetadata,
+    contents: FileContents,
+    /// Whether the file was read long enough after it was modified, so that
+    /// a later change moves its modification time. See `MTIME_GRANULARITY`.
+    settled: bool,
+}
+
+/// Reads source files through a `Vfs` for `ReadFileQuery`.
+///
+/// A file is only read again if its length or modification time changed,
+/// or if it was read within `MTIME_GRANULARITY` of being modified, when its
+/// modification time can't tell. Its contents are hashed after reading, so
+/// touching a file without changing it does not invalidate the queries
+/// depending on it.
+pub struct SourceFiles {
+    vfs: Box<dyn Vfs>,
+    size_limit: u64,
+    seen: Mutex<HashMap<PathBuf, SeenFile>>,
+}
+
+impl SourceFiles {
+    pub fn new(vfs: impl Vfs + 'static) -> Self {
+        Self {
+            vfs: Box::new(vfs),
+            size_limit: DEFAULT_SIZE_LIMIT,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Rejects files longer than `size_limit` bytes with `FileOpenErr::FileTooLarge`.
+    pub fn with_size_limit(mut self, size_limit: u64) -> Self {
+        self.size_limit = size_limit;
+        self
+    }
+
+    pub fn vfs(&self) -> &dyn Vfs {
+        &*self.vfs
+    }
+
+    pub fn read(&self, path: &Path) -> Result<FileContents, FileOpenErr> {
+        let metadata = self.vfs.metadata(path)?;
+        if metadata.len > self.size_limit {
+            return Err(FileOpenErr::FileTooLarge);
+        }
+        if let Some(seen) = self.seen.lock().unwrap().get(path) {
+            if seen.settled && seen.metadata == metadata {
+                return Ok(seen.contents.clone());
+            }
+        }
+        let settled = metadata.modified.is_some_and(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .is_ok_and(|age| age >= MTIME_GRANULARITY)
+        });
+
+        let text = self.vfs.read(path)?;
+        // the file may have grown between reading the metadata and the contents
+        if text.len() as u64 > self.size_limit {
+            return Err(FileOpenErr::FileTooLarge);
+        }
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let contents = FileContents::new(text.into(), hasher.finish());
+        self.seen.lock().unwrap().insert(
+            path.to_path_buf(),
+            SeenFile {
+                metadata,
+                contents: contents.clone(),
+                settled,
+            },
+        );
+        Ok(contents)
+    }
+}
+
+impl Default for SourceFiles {
+    fn default() -> Self {
+        Self::new(DiskVfs)
+    }
+}
@@ -0,0 +1,59 @@
+//! The query cache across builds: `hydent build` saves its queries to
+//! `target/hydent-cache` under the project root, and the next build reuses
+//! them instead of parsing again, wherever in the project it is started from.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SOURCE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixture/grammar/extern_functions.hyt"
+);
+
+/// Builds `file` from `dir` with `--timings` and returns the table of the
+/// slowest queries it prints.
+fn build(dir: &Path, file: &str, out: &Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_hydent_lang_compiler"))
+        .current_dir(dir)
+        .args(["--timings", "build", file, "--emit", "ast", "-o"])
+        .arg(out)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "the build failed:\n{stderr}");
+    stderr
+}
+
+/// The outcome `--timings` lists for the query named `query`.
+fn outcome<'a>(timings: &'a str, query: &str) -> &'a str {
+    timings
+        .lines()
+        .find(|line| line.contains(query))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or_else(|| panic!("{query} is not listed in:\n{timings}"))
+}
+
+#[test]
+fn test_second_build_reuses_the_cached_parse() {
+    let dir: PathBuf =
+        std::env::temp_dir().join(format!("hydent-build-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(dir.join("deps.json"), "{}").unwrap();
+    std::fs::copy(SOURCE, dir.join("src/main.hyt")).unwrap();
+    let out = dir.join("main.ast");
+
+    let first = build(&dir, "src/main.hyt", &out);
+    assert_eq!(outcome(&first, "EmitAstQuery"), "recomputed");
+    let printed = std::fs::read_to_string(&out).unwrap();
+    assert!(dir.join("target/hydent-cache").is_dir());
+
+    // started from a subdirectory, the build still finds the cache at the root
+    let second = build(&dir.join("src"), "main.hyt", &out);
+    assert_eq!(outcome(&second, "EmitAstQuery"), "verified");
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), printed);
+    assert!(!dir.join("src/target").exists());
+    assert!(dir.join("target/hydent-timings.json").is_file());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,173 @@
+//! What the snapshot tests share: finding the sources, comparing snapshots
+//! with a readable diff, and writing them under `HYDENT_BLESS=1`.
+
+// every test uses only some of the helpers
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+pub const BLESS_VAR: &str = "HYDENT_BLESS";
+
+/// How many lines around a difference the diff shows.
+const DIFF_CONTEXT: usize = 2;
+/// How many characters around the first difference of a line are shown.
+const EXCERPT_LEN: usize = 40;
+
+/// Whether the snapshots should be written instead of compared.
+pub fn blessing() -> bool {
+    std::env::var(BLESS_VAR).is_ok_and(|bless| bless != "0")
+}
+
+/// Every `.hyt` file under `dir`, sorted. Files whose name starts with `_`
+/// are skipped.
+pub fn sources(dir: &Path) -> Vec<PathBuf> {
+    fn walk(dir: &Path, found: &mut Vec<PathBuf>) {
+        for entry in std::fs::read_dir(dir).expect("the test directory is readable") {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                walk(&path, found);
+            } else if path.extension().is_some_and(|extension| extension == "hyt") {
+                found.push(path);
+            }
+        }
+    }
+    let mut found = Vec::new();
+    walk(dir, &mut found);
+    found.sort();
+    found.retain(|path| {
+        let skipped = path.file_name().unwrap().to_string_lossy().starts_with('_');
+        if skipped {
+            println!("skipped {}", path.display());
+        }
+        !skipped
+    });
+    found
+}
+
+/// `path` relative to `root`, with `/` between the components on every
+/// platform, since it ends up in the snapshots.
+pub fn relative(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Compares or, when blessing, writes the snapshot `path`. `None` means the
+/// snapshot shouldn't exist. Returns what is wrong with it.
+pub fn check_snapshot(root: &Path, path: &Path, actual: Option<String>) -> Option<String> {
+    let expected = std::fs::read_to_string(path).ok();
+    let display = relative(root, path);
+    if expected == actual {
+        return None;
+    }
+    if blessing() {
+        match actual {
+            Some(actual) => std::fs::write(path, actual).unwrap(),
+            None => std::fs::remove_file(path).unwrap(),
+        }
+        println!("blessed {display}");
+        return None;
+    }
+    Some(match (expected, actual) {
+        (Some(expected), Some(actual)) => {
+            format!("{display} doesn't match:\n{}", diff(&expected, &actual))
+        }
+        (None, Some(_)) => format!("{display} is missing"),
+        (Some(_), None) => format!("{display} isn't produced any more"),
+        (None, None) => unreachable!(),
+    })
+}
+
+/// Fails with every failure of a test run, if there are any.
+pub fn assert_no_failures(failures: &[String]) {
+    assert!(
+        failures.is_empty(),
+        "{} checks failed, run with {BLESS_VAR}=1 to update the snapshots:\n\n{}",
+        failures.len(),
+        failures.join("\n\n")
+    );
+}
+
+/// A line diff of `expected` and `actual`, with `-` for removed and `+` for
+/// added lines. Snapshots like the AST are a single long line, so the first
+/// difference of a changed line is pointed out too.
+pub fn diff(expected: &str, actual: &str) -> String {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+
+    // the longest common subsequences of every pair of suffixes
+    let mut common = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push((' ', expected[i]));
+            i += 1;
+            j += 1;
+        } else if j < actual.len() && (i == expected.len() || common[i][j + 1] >= common[i + 1][j])
+        {
+            lines.push(('+', actual[j]));
+            j += 1;
+        } else {
+            lines.push(('-', expected[i]));
+            i += 1;
+        }
+    }
+
+    let shown = |at: usize| {
+        lines[at.saturating_sub(DIFF_CONTEXT)..lines.len().min(at + DIFF_CONTEXT + 1)]
+            .iter()
+            .any(|(kind, _)| *kind != ' ')
+    };
+    let mut out = String::new();
+    let mut skipped = false;
+    for (at, (kind, line)) in lines.iter().enumerate() {
+        if shown(at) {
+            out.push_str(&format!("{kind} {line}\n"));
+            skipped = false;
+        } else if !skipped {
+            out.push_str("  ...\n");
+            skipped = true;
+        }
+    }
+    if let Some(((_, removed), (_, added))) = lines
+        .windows(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|((before, _), (after, _))| *before == '-' && *after == '+')
+    {
+        out.push_str(&first_difference(removed, added));
+    }
+    out
+}
+
+fn first_difference(expected: &str, actual: &str) -> String {
+    let at = expected
+        .char_indices()
+        .zip(actual.chars())
+        .find(|((_, expected), actual)| expected != actual)
+        .map(|((at, _), _)| at)
+        .unwrap_or(expected.len().min(actual.len()));
+    let excerpt = |line: &str| {
+        let start = line.floor_char_boundary(at.saturating_sub(EXCERPT_LEN / 2));
+        let end = line.ceil_char_boundary((at + EXCERPT_LEN / 2).min(line.len()));
+        line[start..end].to_string()
+    };
+    format!(
+        "first difference at column {}:\n  expected `{}`\n  actual   `{}`\n",
+        at + 1,
+        excerpt(expected),
+        excerpt(actual)
+    )
+}
@@ -0,0 +1,219 @@
+//! Checks the registry of diagnostic codes: that every code is used by the
+//! compiler, that its explanation is written and its examples do what they
+//! say, and that the website publishes the explanations as they are. Run with
+//! `HYDENT_BLESS=1` to write the website pages.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use hydent_lang_compiler::diagnostic::diagnotice_patterns::{
+    lookup, DiagnosticCode, DIAGNOSTIC_CODES,
+};
+use hydent_lang_compiler::diagnostic::Diagnostic;
+use hydent_lang_compiler::parser::{parse_expression, parse_module};
+
+const SOURCE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+const REGISTRY_PATH: &str = "diagnostic/diagnotice_patterns.rs";
+const WEBSITE_DIR: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/website/src/content/docs/reference/diagnostics"
+);
+
+/// A `hydent` code block of an explanation.
+struct Example<'a> {
+    tags: Vec<&'a str>,
+    source: String,
+}
+
+fn examples(explanation: &str) -> Vec<Example<'_>> {
+    let mut examples = Vec::new();
+    let mut lines = explanation.lines();
+    while let Some(line) = lines.next() {
+        let Some(info) = line.strip_prefix("```hydent") else {
+            continue;
+        };
+        let source = lines
+            .by_ref()
+            .take_while(|line| *line != "```")
+            .map(|line| format!("{line}\n"))
+            .collect();
+        examples.push(Example {
+            tags: info.split(',').filter(|tag| !tag.is_empty()).collect(),
+            source,
+        });
+    }
+    examples
+}
+
+fn diagnostics(example: &Example<'_>) -> Vec<Diagnostic> {
+    if example.tags.contains(&"expression") {
+        parse_expression(&example.source).1
+    } else {
+        parse_module(&example.source).1
+    }
+}
+
+fn rust_files(dir: &Path, found: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            rust_files(&path, found);
+        } else if path.extension().is_some_and(|extension| extension == "rs") {
+            found.push(path);
+        }
+    }
+}
+
+#[test]
+fn test_codes_are_registered_once() {
+    for pair in DIAGNOSTIC_CODES.windows(2) {
+        assert!(
+            pair[0].id < pair[1].id,
+            "{} and {} are out of order",
+            pair[0].code(),
+            pair[1].code()
+        );
+    }
+    for code in DIAGNOSTIC_CODES {
+        assert_eq!(lookup(&code.code()), Some(code));
+        assert_eq!(lookup(&code.id.to_string()), Some(code));
+        assert!(
+            code.name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c == '_'),
+            "{}: `{}` is not snake case",
+            code.code(),
+            code.name
+        );
+    }
+    assert_eq!(lookup("E9999"), None);
+    assert_eq!(lookup("unknown"), None);
+}
+
+/// Like rustc's tidy check: every code has to be named somewhere in the
+/// compiler outside of the registry, by the constant of its name.
+#[test]
+fn test_codes_are_used() {
+    let mut files = Vec::new();
+    rust_files(Path::new(SOURCE_DIR), &mut files);
+    let sources = files
+        .iter()
+        .filter(|path| !path.ends_with(REGISTRY_PATH))
+        .map(|path| std::fs::read_to_string(path).unwrap())
+        .collect::<Vec<_>>();
+    for code in DIAGNOSTIC_CODES {
+        let constant = code.name.to_uppercase();
+        let used = sources.iter().any(|source| {
+            source.match_indices(&constant).any(|(at, _)| {
+                let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+                !source[..at].ends_with(is_ident)
+                    && !source[at + constant.len()..].starts_with(is_ident)
+            })
+        });
+        assert!(used, "{} (`{constant}`) is never reported", code.code());
+    }
+}
+
+#[test]
+fn test_explanations_are_written() {
+    for code in DIAGNOSTIC_CODES {
+        let summary = code.explanation.lines().next().unwrap_or_default();
+        assert!(
+            summary.ends_with('.') && !summary.starts_with('#'),
+            "{}: the explanation doesn't start with a one-line summary",
+            code.code()
+        );
+        let erroneous = examples(code.explanation)
+            .into_iter()
+            .any(|example| example.tags.contains(&code.code().as_str()));
+        assert!(
+            erroneous,
+            "{}: the explanation has no example of the error",
+            code.code()
+        );
+    }
+}
+
+#[test]
+fn test_explanation_examples() {
+    for code in DIAGNOSTIC_CODES {
+        for example in examples(code.explanation) {
+            if example.tags.contains(&"ignore") {
+                continue;
+            }
+            let diagnostics = diagnostics(&example);
+            let reported = diagnostics
+                .iter()
+                .map(|diagnostic| format!("E{:04}", diagnostic.id))
+                .collect::<Vec<_>>();
+            if example.tags.contains(&"compile_fail") {
+                let expected = example
+                    .tags
+                    .iter()
+                    .filter(|tag| lookup(tag).is_some() && tag.starts_with('E'));
+                for expected in expected {
+                    assert!(
+                        reported.iter().any(|reported| reported == expected),
+                        "{}: the example doesn't report {expected}, only {reported:?}:\n{}",
+                        code.code(),
+                        example.source
+                    );
+                }
+            } else {
+                assert!(
+                    diagnostics.is_empty(),
+                    "{}: the example reports {reported:?}:\n{}",
+                    code.code(),
+                    example.source
+                );
+            }
+        }
+    }
+}
+
+fn website_page(code: &DiagnosticCode) -> String {
+    format!(
+        "---\n\
+         title: '{}: {}'\n\
+         description: '{}'\n\
+         ---\n\
+         \n\
+         <!-- written by `HYDENT_BLESS=1 cargo test --test diagnostic_codes` -->\n\
+         \n\
+         **Level**: {}\n\
+         \n\
+         {}",
+        code.code(),
+        code.name,
+        code.message,
+        code.level,
+        code.published_explanation()
+    )
+}
+
+#[test]
+fn test_website_publishes_explanations() {
+    let root = Path::new(WEBSITE_DIR);
+    if common::blessing() {
+        std::fs::create_dir_all(root).unwrap();
+    }
+    let mut failures = Vec::new();
+    let mut pages = Vec::new();
+    for code in DIAGNOSTIC_CODES {
+        let page = root.join(format!("{}.md", code.code().to_lowercase()));
+        failures.extend(common::check_snapshot(
+            root,
+            &page,
+            Some(website_page(code)),
+        ));
+        pages.push(page);
+    }
+    for entry in std::fs::read_dir(root).into_iter().flatten() {
+        let page = entry.unwrap().path();
+        if !pages.contains(&page) {
+            failures.extend(common::check_snapshot(root, &page, None));
+        }
+    }
+    common::assert_no_failures(&failures);
+}
@@ -0,0 +1,113 @@
+//! Runs the compiler over every `tests/fixture/**/*.hyt` and compares what it
+//! produces with the snapshots next to the fixture:
+//!
+//! - `<name>-ast.json`, the printed AST of a fixture which parses,
+//! - `<name>-error.log.txt`, the diagnostics of a fixture which has any,
+//! - `<name>-hir.txt` and `<name>-mir.txt`, once the AST is lowered.
+//!
+//! It also runs `--fix` over every fixture, which may only add a `;` at the
+//! end of a line.
+//!
+//! A snapshot which the compiler doesn't produce any more must be deleted.
+//! Run with `HYDENT_BLESS=1` to write the snapshots instead of comparing
+//! them. Blessing never deletes an AST though: a fixture which parsed and
+//! stops parsing is a regression of the parser or a broken fixture, and
+//! fails until one of them is fixed. Fixtures whose name starts with `_` are
+//! skipped.
+
+mod common;
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+
+use hydent_lang_compiler::diagnostic::fixer::fix;
+use hydent_lang_compiler::diagnostic::render::render;
+use hydent_lang_compiler::diagnostic::DiagnosticLevel;
+use hydent_lang_compiler::parser::parse_module;
+
+const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixture");
+
+/// What the compiler produced for a fixture, by snapshot suffix.
+struct Outputs {
+    ast: Option<String>,
+    errors: Option<String>,
+}
+
+impl Outputs {
+    fn snapshots(self) -> [(&'static str, Option<String>); 4] {
+        [
+            ("-ast.json", self.ast),
+            ("-error.log.txt", self.errors),
+            // nothing lowers the AST yet
+            ("-hir.txt", None),
+            ("-mir.txt", None),
+        ]
+    }
+}
+
+fn compile(relative: &str, source: &str) -> Outputs {
+    let (ast, diagnostics) = parse_module(source);
+    let failed = diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.level == DiagnosticLevel::Error);
+    let errors = (!diagnostics.is_empty()).then(|| {
+        diagnostics
+            .iter()
+            .map(|diagnostic| render(diagnostic, relative, ast.source_holder()))
+            .collect()
+    });
+    Outputs {
+        ast: (!failed).then(|| ast.to_string()),
+        errors,
+    }
+}
+
+#[test]
+fn test_fixtures() {
+    let root = Path::new(FIXTURE_DIR);
+    let mut failures = Vec::new();
+    for path in common::sources(root) {
+        let relative = common::relative(root, &path);
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let source = std::fs::read_to_string(&path).unwrap();
+        let Ok(outputs) = catch_unwind(AssertUnwindSafe(|| compile(&relative, &source))) else {
+            failures.push(format!("the compiler panicked on {relative}"));
+            continue;
+        };
+        for (suffix, actual) in outputs.snapshots() {
+            let snapshot = path.with_file_name(format!("{name}{suffix}"));
+            if suffix == "-ast.json" && actual.is_none() && snapshot.exists() {
+                failures.push(format!(
+                    "{relative} doesn't parse any more, fix the parser or the fixture \
+                     instead of deleting {}",
+                    common::relative(root, &snapshot)
+                ));
+                continue;
+            }
+            failures.extend(common::check_snapshot(root, &snapshot, actual));
+        }
+    }
+    common::assert_no_failures(&failures);
+}
+
+#[test]
+fn test_fix_fixtures() {
+    let root = Path::new(FIXTURE_DIR);
+    let mut failures = Vec::new();
+    for path in common::sources(root) {
+        let relative = common::relative(root, &path);
+        let source = std::fs::read_to_string(&path).unwrap();
+        let Ok(fixed) = fix(&source, |source| parse_module(source).1) else {
+            continue;
+        };
+        for (line, (before, after)) in source.lines().zip(fixed.source.lines()).enumerate() {
+            if before != after && after != format!("{};", before.trim_end()) {
+                failures.push(format!(
+                    "--fix changed {relative}:{}:\n  before `{before}`\n  after  `{after}`",
+                    line + 1
+                ));
+            }
+        }
+    }
+    common::assert_no_failures(&failures);
+}
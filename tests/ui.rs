@@ -0,0 +1,164 @@
+//! UI tests: every `tests/ui/**/*.hyt` says which diagnostics it expects
+//! with comments like rustc's UI suite, and the rendered diagnostics are
+//! compared with the `<name>.stderr` next to it.
+//!
+//! ```text
+//! @Link "m"; //~ ERROR E0005
+//! @Unknown;
+//! //~^ WARNING unknown annotation
+//! ```
+//!
+//! `//~` expects a diagnostic on its own line, every `^` after it moves the
+//! expectation one line up, and `//~|` expects it on the line of the
+//! expectation before. The level is `ERROR`, `WARNING` or `NOTE`. What
+//! follows is either a code like `E0005`, which the diagnostic has to have,
+//! or a part of its message, and can be left out. Every diagnostic has to be
+//! expected and every expectation has to be met.
+//!
+//! Run with `HYDENT_BLESS=1` to write the `.stderr` files instead of
+//! comparing them.
+
+mod common;
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+
+use hydent_lang_compiler::diagnostic::render::render;
+use hydent_lang_compiler::diagnostic::{Diagnostic, DiagnosticLevel};
+use hydent_lang_compiler::parser::parse_module;
+
+const UI_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/ui");
+const EXPECTATION_MARKER: &str = "//~";
+
+/// A diagnostic a `//~` comment expects.
+#[derive(Debug)]
+struct Expectation {
+    /// The line the comment is on.
+    written_at: usize,
+    /// The line the diagnostic is expected on.
+    line: usize,
+    level: DiagnosticLevel,
+    /// A code like `E0005` or a part of the message. Empty matches anything.
+    pattern: String,
+}
+
+impl Expectation {
+    fn matches(&self, line: usize, diagnostic: &Diagnostic) -> bool {
+        if self.line != line || self.level != diagnostic.level {
+            return false;
+        }
+        match self
+            .pattern
+            .strip_prefix('E')
+            .and_then(|code| code.parse::<u32>().ok())
+        {
+            Some(id) => id == diagnostic.id,
+            None => diagnostic.message.contains(&self.pattern),
+        }
+    }
+}
+
+/// Reads the `//~` comments of `source`.
+fn expectations(relative: &str, source: &str) -> Result<Vec<Expectation>, String> {
+    let mut expectations = Vec::<Expectation>::new();
+    for (index, text) in source.lines().enumerate() {
+        let written_at = index + 1;
+        let Some((_, rest)) = text.split_once(EXPECTATION_MARKER) else {
+            continue;
+        };
+        let location = format!("{relative}:{written_at}");
+        let (line, rest) = if let Some(rest) = rest.strip_prefix('|') {
+            let previous = expectations
+                .last()
+                .filter(|previous| previous.written_at + 1 == written_at)
+                .ok_or_else(|| format!("{location}: `//~|` doesn't follow an expectation"))?;
+            (previous.line, rest)
+        } else {
+            let carets = rest.len() - rest.trim_start_matches('^').len();
+            let line = written_at
+                .checked_sub(carets)
+                .filter(|&line| line > 0)
+                .ok_or_else(|| format!("{location}: `//~` points above the file"))?;
+            (line, &rest[carets..])
+        };
+        let rest = rest.trim();
+        let (level, pattern) = rest.split_once(' ').unwrap_or((rest, ""));
+        let level = match level {
+            "ERROR" => DiagnosticLevel::Error,
+            "WARNING" => DiagnosticLevel::Warning,
+            "NOTE" => DiagnosticLevel::Note,
+            level => return Err(format!("{location}: `{level}` is not a level")),
+        };
+        expectations.push(Expectation {
+            written_at,
+            line,
+            level,
+            pattern: pattern.trim().to_string(),
+        });
+    }
+    Ok(expectations)
+}
+
+/// Checks the diagnostics of the test `relative` against its expectations
+/// and returns them rendered.
+fn run(relative: &str, source: &str, failures: &mut Vec<String>) -> String {
+    let mut expected = match expectations(relative, source) {
+        Ok(expected) => expected.into_iter().map(Some).collect::<Vec<_>>(),
+        Err(err) => {
+            failures.push(err);
+            Vec::new()
+        }
+    };
+    let (ast, diagnostics) = parse_module(source);
+    let source_holder = ast.source_holder();
+
+    for diagnostic in &diagnostics {
+        let (line, _) = source_holder.resolve_position(diagnostic.primary.begin);
+        let found = expected.iter_mut().find(|expectation| {
+            expectation
+                .as_ref()
+                .is_some_and(|expectation| expectation.matches(line, diagnostic))
+        });
+        match found {
+            Some(expectation) => *expectation = None,
+            None => failures.push(format!(
+                "{relative}:{line}: unexpected {}[E{:04}]: {}",
+                diagnostic.level, diagnostic.id, diagnostic.message
+            )),
+        }
+    }
+    for expectation in expected.into_iter().flatten() {
+        failures.push(format!(
+            "{relative}:{}: expected {} `{}` wasn't reported",
+            expectation.line, expectation.level, expectation.pattern
+        ));
+    }
+
+    diagnostics
+        .iter()
+        .map(|diagnostic| render(diagnostic, relative, source_holder))
+        .collect()
+}
+
+#[test]
+fn test_ui() {
+    let root = Path::new(UI_DIR);
+    let mut failures = Vec::new();
+    for path in common::sources(root) {
+        let relative = common::relative(root, &path);
+        let source = std::fs::read_to_string(&path).unwrap();
+        let Ok(rendered) = catch_unwind(AssertUnwindSafe(|| {
+            let mut found = Vec::new();
+            let rendered = run(&relative, &source, &mut found);
+            failures.extend(found);
+            rendered
+        })) else {
+            failures.push(format!("the compiler panicked on {relative}"));
+            continue;
+        };
+        let stderr = path.with_extension("stderr");
+        let actual = (!rendered.is_empty()).then_some(rendered);
+        failures.extend(common::check_snapshot(root, &stderr, actual));
+    }
+    common::assert_no_failures(&failures);
+}